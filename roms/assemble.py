@@ -0,0 +1,163 @@
+#!/usr/bin/env python3
+"""Assembles the self-test ROMs in roms/src into roms/ and roms/eti660/.
+
+The sources use the mnemonics `chip8 disasm` prints, with `name:` labels, `db` for bytes,
+`org` to pad with zeros up to an address and `;` comments. Each ROM is built twice: at 0x200
+for most platforms and at 0x600 for the ETI-660, except the ones for platforms the ETI-660
+can't stand in for.
+
+    python3 roms/assemble.py
+"""
+
+import re
+import sys
+from pathlib import Path
+
+ROMS = Path(__file__).resolve().parent
+ORIGINS = {ROMS: 0x200, ROMS / "eti660": 0x600}
+
+# ROMs that use CHIP-8 HIRES or MEGA-CHIP, which are only built at 0x200.
+ONLY_AT_0X200 = {"7-hires", "8-megachip"}
+
+REGISTER = re.compile(r"^v([0-9a-f])$")
+
+
+def register(text):
+    match = REGISTER.match(text)
+    return int(match.group(1), 16) if match else None
+
+
+def number(text, labels):
+    if text in labels:
+        return labels[text]
+    return int(text, 0)
+
+
+def encode(mnemonic, operands, labels):
+    """The instruction's bytes, given the labels' addresses."""
+    ops = operands
+    regs = [register(op) for op in ops]
+    value = lambda index: number(ops[index], labels)
+    word = None
+    if mnemonic == "db":
+        return bytes(value(i) for i in range(len(ops)))
+    if mnemonic == "cls":
+        word = 0x00E0
+    elif mnemonic == "ret":
+        word = 0x00EE
+    elif mnemonic == "hires" and ops == ["cls"]:
+        word = 0x0230
+    elif mnemonic == "megaoff":
+        word = 0x0010
+    elif mnemonic == "megaon":
+        word = 0x0011
+    elif mnemonic in ("ldpal", "sprw", "sprh"):
+        word = {"ldpal": 0x0200, "sprw": 0x0300, "sprh": 0x0400}[mnemonic] | value(0)
+    elif mnemonic == "jp" and len(ops) == 2 and ops[0] == "v0":
+        word = 0xB000 | value(1)
+    elif mnemonic == "jp":
+        word = 0x1000 | value(0)
+    elif mnemonic == "call":
+        word = 0x2000 | value(0)
+    elif mnemonic in ("se", "sne") and regs[1] is not None:
+        word = (0x5000 if mnemonic == "se" else 0x9000) | regs[0] << 8 | regs[1] << 4
+    elif mnemonic in ("se", "sne"):
+        word = (0x3000 if mnemonic == "se" else 0x4000) | regs[0] << 8 | value(1)
+    elif mnemonic == "ld" and ops[0] == "i":
+        word = 0xA000 | value(1)
+    elif mnemonic == "ld" and ops[0] == "dt":
+        word = 0xF015 | regs[1] << 8
+    elif mnemonic == "ld" and ops[0] == "st":
+        word = 0xF018 | regs[1] << 8
+    elif mnemonic == "ld" and ops[0] == "f":
+        word = 0xF029 | regs[1] << 8
+    elif mnemonic == "ld" and ops[0] == "b":
+        word = 0xF033 | regs[1] << 8
+    elif mnemonic == "ld" and ops[0] == "[i]":
+        word = 0xF055 | regs[1] << 8
+    elif mnemonic == "ld" and ops[1] == "[i]":
+        word = 0xF065 | regs[0] << 8
+    elif mnemonic == "ld" and ops[1] == "dt":
+        word = 0xF007 | regs[0] << 8
+    elif mnemonic == "ld" and ops[1] == "k":
+        word = 0xF00A | regs[0] << 8
+    elif mnemonic == "ld" and regs[1] is not None:
+        word = 0x8000 | regs[0] << 8 | regs[1] << 4
+    elif mnemonic == "ld":
+        word = 0x6000 | regs[0] << 8 | value(1)
+    elif mnemonic == "add" and ops[0] == "i":
+        word = 0xF01E | regs[1] << 8
+    elif mnemonic == "add" and regs[1] is not None:
+        word = 0x8004 | regs[0] << 8 | regs[1] << 4
+    elif mnemonic == "add":
+        word = 0x7000 | regs[0] << 8 | value(1)
+    elif mnemonic in ("or", "and", "xor", "sub", "shr", "subn", "shl"):
+        n = {"or": 1, "and": 2, "xor": 3, "sub": 5, "shr": 6, "subn": 7, "shl": 0xE}[mnemonic]
+        word = 0x8000 | regs[0] << 8 | regs[1] << 4 | n
+    elif mnemonic == "rnd":
+        word = 0xC000 | regs[0] << 8 | value(1)
+    elif mnemonic == "drw":
+        word = 0xD000 | regs[0] << 8 | regs[1] << 4 | value(2)
+    elif mnemonic == "skp":
+        word = 0xE09E | regs[0] << 8
+    elif mnemonic == "sknp":
+        word = 0xE0A1 | regs[0] << 8
+    if word is None:
+        raise ValueError(f"cannot assemble `{mnemonic} {', '.join(ops)}`")
+    return word.to_bytes(2, "big")
+
+
+def parse(source):
+    """The labels and statements in a source file, as (mnemonic, operands) pairs."""
+    statements = []
+    for line in source.splitlines():
+        line = line.split(";")[0].strip().lower()
+        while ":" in line:
+            label, line = line.split(":", 1)
+            statements.append(("label", [label.strip()]))
+            line = line.strip()
+        if line:
+            mnemonic, _, rest = line.partition(" ")
+            operands = [op.strip() for op in rest.split(",")] if rest else []
+            statements.append((mnemonic, operands))
+    return statements
+
+
+def assemble(source, origin):
+    statements = parse(source)
+    # The first pass finds the labels, which every instruction's size is known without.
+    labels = {}
+    address = origin
+    for mnemonic, operands in statements:
+        if mnemonic == "label":
+            labels[operands[0]] = address
+        elif mnemonic == "db":
+            address += len(operands)
+        elif mnemonic == "org":
+            if number(operands[0], labels) < address:
+                raise ValueError(f"`org {operands[0]}` is behind the code before it")
+            address = number(operands[0], labels)
+        else:
+            address += 2
+    program = bytearray()
+    for mnemonic, operands in statements:
+        if mnemonic == "org":
+            program += bytes(number(operands[0], labels) - origin - len(program))
+        elif mnemonic != "label":
+            program += encode(mnemonic, operands, labels)
+    return bytes(program)
+
+
+def main():
+    for source in sorted((ROMS / "src").glob("*.s")):
+        for directory, origin in ORIGINS.items():
+            if origin != 0x200 and source.stem in ONLY_AT_0X200:
+                continue
+            directory.mkdir(exist_ok=True)
+            rom = directory / (source.stem + ".ch8")
+            rom.write_bytes(assemble(source.read_text(), origin))
+            print(f"Assembled {rom.relative_to(ROMS.parent)}", file=sys.stderr)
+
+
+if __name__ == "__main__":
+    main()
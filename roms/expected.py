@@ -0,0 +1,199 @@
+#!/usr/bin/env python3
+"""Draws the screens the self-test ROMs should finish on into roms/selftest/expected/.
+
+The screens are composed from what each ROM's source says it draws when every check passes,
+not captured from the emulator, so `chip8 selftest` holds the emulator to them rather than to
+its own past output. Sprites come from the `db` lines in roms/src and the hex digits from the
+standard CHIP-8 font. The files are in the format `headless --dump screen` writes: a line of
+`#` for lit and `.` for dark pixels for each row.
+
+    python3 roms/expected.py
+"""
+
+import sys
+from pathlib import Path
+
+from assemble import parse
+
+ROMS = Path(__file__).resolve().parent
+EXPECTED = ROMS / "selftest" / "expected"
+
+# The screen each platform starts with.
+SIZES = {
+    "chip8": (64, 32),
+    "eti660": (64, 48),
+    "chip8-hires": (64, 32),
+    "megachip": (64, 32),
+}
+
+FONT = {
+    0x0: [0xF0, 0x90, 0x90, 0x90, 0xF0],
+    0x1: [0x20, 0x60, 0x20, 0x20, 0x70],
+    0x2: [0xF0, 0x10, 0xF0, 0x80, 0xF0],
+    0x3: [0xF0, 0x10, 0xF0, 0x10, 0xF0],
+    0x4: [0x90, 0x90, 0xF0, 0x10, 0x10],
+    0x5: [0xF0, 0x80, 0xF0, 0x10, 0xF0],
+    0x6: [0xF0, 0x80, 0xF0, 0x90, 0xF0],
+    0x7: [0xF0, 0x10, 0x20, 0x40, 0x40],
+    0x8: [0xF0, 0x90, 0xF0, 0x90, 0xF0],
+    0x9: [0xF0, 0x90, 0xF0, 0x10, 0xF0],
+    0xA: [0xF0, 0x90, 0xF0, 0x90, 0x90],
+    0xB: [0xE0, 0x90, 0xE0, 0x90, 0xE0],
+    0xC: [0xF0, 0x80, 0x80, 0x80, 0xF0],
+    0xD: [0xE0, 0x90, 0x90, 0x90, 0xE0],
+    0xE: [0xF0, 0x80, 0xF0, 0x80, 0xF0],
+    0xF: [0xF0, 0x80, 0xF0, 0x80, 0x80],
+}
+
+# The quirks 5-quirks should find, in the order it shows them: shifting VY, FX55 incrementing
+# I, BXNN adding VX and FX1E setting VF. Each platform has the program database's quirks for
+# it, and FX1E sets VF on all of them.
+QUIRKS = {
+    "chip8": [1, 1, 0, 1],
+    "eti660": [1, 1, 0, 1],
+    "chip8-hires": [1, 1, 0, 1],
+    "megachip": [0, 0, 1, 1],
+}
+
+
+class Screen:
+    def __init__(self, width, height):
+        self.width = width
+        self.height = height
+        self.pixels = [[False] * width for _ in range(height)]
+
+    def draw(self, x, y, rows):
+        """XORs an 8-pixel wide sprite on, wrapping its position and clipping the rest."""
+        x %= self.width
+        y %= self.height
+        for dy, row in enumerate(rows):
+            for dx in range(8):
+                if row & (0x80 >> dx) and x + dx < self.width and y + dy < self.height:
+                    self.pixels[y + dy][x + dx] ^= True
+
+    def block(self, x, y, rows):
+        """Lights the non-zero pixels of a MEGA-CHIP sprite, a palette index for each."""
+        for dy, row in enumerate(rows):
+            for dx, index in enumerate(row):
+                if index:
+                    self.pixels[y + dy][x + dx] = True
+
+    def text(self):
+        return "".join(
+            "".join("#" if lit else "." for lit in row) + "\n" for row in self.pixels
+        )
+
+
+def sprites(name):
+    """The bytes after each label in a ROM's source."""
+    found = {}
+    label = None
+    for mnemonic, operands in parse((ROMS / "src" / f"{name}.s").read_text()):
+        if mnemonic == "label":
+            label = operands[0]
+            found[label] = []
+        elif mnemonic == "db" and label is not None:
+            found[label] += [int(op, 0) for op in operands]
+        else:
+            label = None
+    return found
+
+
+def marks(screen, count, tick):
+    """Ticks in the rows of eight that 3-opcodes and 4-flags draw them in."""
+    for mark in range(count):
+        screen.draw(8 * (mark % 8), 1 + 6 * (mark // 8), tick)
+
+
+def chip8_logo(platform):
+    screen = Screen(*SIZES[platform])
+    letters = sprites("1-chip8-logo")
+    order = ["letter_c", "letter_h", "letter_i", "letter_p", "dash", "digit_8"]
+    for place, letter in enumerate(order):
+        screen.draw(8 + 8 * place, 13, letters[letter])
+    return screen
+
+
+def font(platform):
+    screen = Screen(*SIZES[platform])
+    for digit in range(16):
+        screen.draw(4 + 7 * (digit % 8), 8 + 10 * (digit // 8), FONT[digit])
+    return screen
+
+
+def opcodes(platform):
+    screen = Screen(*SIZES[platform])
+    marks(screen, 17, sprites("3-opcodes")["tick"])
+    return screen
+
+
+def flags(platform):
+    # The tick drawn at (40, 40) to check collisions is drawn twice, so it is gone.
+    screen = Screen(*SIZES[platform])
+    marks(screen, 20, sprites("4-flags")["tick"])
+    return screen
+
+
+def quirks(platform, found):
+    screen = Screen(*SIZES[platform])
+    for place, digit in enumerate(found):
+        screen.draw(8 + 12 * place, 13, FONT[digit])
+    return screen
+
+
+def keypad(platform, finished):
+    screen = Screen(*SIZES[platform])
+    screen.draw(8, 13, FONT[3])
+    if finished:
+        screen.draw(20, 13, FONT[5])
+        screen.draw(32, 13, sprites("6-keypad")["tick"])
+    return screen
+
+
+def hires():
+    # The cross is cleared away by 0230 before anything else is drawn.
+    screen = Screen(64, 64)
+    tick = sprites("7-hires")["tick"]
+    screen.draw(0, 0, tick)
+    screen.draw(56, 59, tick)
+    screen.draw(20, 40, FONT[6])
+    screen.draw(32, 40, FONT[4])
+    return screen
+
+
+def megachip():
+    screen = Screen(256, 192)
+    square = sprites("8-megachip")["square"]
+    screen.block(120, 90, [square[row * 4 : row * 4 + 4] for row in range(4)])
+    screen.draw(200, 150, FONT[0xA])
+    return screen
+
+
+def screens():
+    """Each expected screen by file name."""
+    found = {}
+    for platform in SIZES:
+        found[f"1-chip8-logo-{platform}"] = chip8_logo(platform)
+        found[f"2-font-{platform}"] = font(platform)
+        found[f"3-opcodes-{platform}"] = opcodes(platform)
+        found[f"4-flags-{platform}"] = flags(platform)
+        found[f"5-quirks-{platform}"] = quirks(platform, QUIRKS[platform])
+        found[f"6-keypad-{platform}-waiting"] = keypad(platform, False)
+        found[f"6-keypad-{platform}"] = keypad(platform, True)
+    # Every quirk set the other way from the VIP's.
+    found["5-quirks-flipped-chip8"] = quirks("chip8", [0, 0, 1, 0])
+    found["7-hires-chip8-hires"] = hires()
+    found["8-megachip-megachip"] = megachip()
+    return found
+
+
+def main():
+    EXPECTED.mkdir(parents=True, exist_ok=True)
+    for name, screen in screens().items():
+        path = EXPECTED / f"{name}.txt"
+        path.write_text(screen.text())
+        print(f"Wrote {path.relative_to(ROMS.parent)}", file=sys.stderr)
+
+
+if __name__ == "__main__":
+    main()
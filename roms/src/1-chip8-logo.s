@@ -0,0 +1,31 @@
+; Draws "CHIP-8" in the middle of the screen with only 00E0, 6XNN, 7XNN, ANNN, DXYN and
+; 1NNN, the first instructions an interpreter needs.
+
+        cls
+        ld v0, 8
+        ld v1, 13
+        ld i, letter_c
+        drw v0, v1, 5
+        add v0, 8
+        ld i, letter_h
+        drw v0, v1, 5
+        add v0, 8
+        ld i, letter_i
+        drw v0, v1, 5
+        add v0, 8
+        ld i, letter_p
+        drw v0, v1, 5
+        add v0, 8
+        ld i, dash
+        drw v0, v1, 5
+        add v0, 8
+        ld i, digit_8
+        drw v0, v1, 5
+halt:   jp halt
+
+letter_c: db 0xf0, 0x80, 0x80, 0x80, 0xf0
+letter_h: db 0x90, 0x90, 0xf0, 0x90, 0x90
+letter_i: db 0xe0, 0x40, 0x40, 0x40, 0xe0
+letter_p: db 0xf0, 0x90, 0xf0, 0x80, 0x80
+dash:     db 0x00, 0x00, 0xf0, 0x00, 0x00
+digit_8:  db 0xf0, 0x90, 0xf0, 0x90, 0xf0
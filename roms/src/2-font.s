@@ -0,0 +1,21 @@
+; Draws the built-in hex digits 0 to F in two rows using FX29 to find each one's sprite.
+
+        cls
+        ld v0, 0        ; digit
+        ld v1, 4        ; x
+        ld v2, 8        ; y
+next:   ld f, v0
+        drw v1, v2, 5
+        add v0, 1
+        add v1, 7
+        sne v0, 8
+        call newline
+        sne v0, 16
+        jp halt
+        jp next
+halt:   jp halt
+
+newline:
+        ld v1, 4
+        ld v2, 18
+        ret
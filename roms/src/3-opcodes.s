@@ -0,0 +1,150 @@
+; Checks the result of each instruction group, drawing a tick for each that works and a
+; cross for each that doesn't, eight to a row in this order:
+;
+;   3XNN 4XNN 5XY0 9XY0 7XNN 8XY0 8XY1 8XY2
+;   8XY3 2NNN BNNN FX1E FX55 FX33 FX29 FX15
+;   CXNN
+
+        cls
+        ld vd, 0        ; where the next mark goes
+        ld ve, 1
+
+        ld v2, 1        ; 3XNN skips when equal
+        se v2, 1
+        ld v2, 0
+        ld v3, 1
+        call check
+
+        ld v2, 1        ; 4XNN skips when not equal
+        sne v2, 2
+        ld v2, 0
+        call check
+
+        ld v2, 1        ; 5XY0 skips when the registers are equal
+        ld v4, 1
+        se v2, v4
+        ld v2, 0
+        call check
+
+        ld v2, 1        ; 9XY0 skips when they are not
+        ld v4, 2
+        sne v2, v4
+        ld v2, 0
+        call check
+
+        ld v2, 0xff     ; 7XNN wraps around
+        add v2, 2
+        call check
+
+        ld v4, 0x42     ; 8XY0
+        ld v2, v4
+        ld v3, 0x42
+        call check
+
+        ld v2, 0x0f     ; 8XY1
+        ld v4, 0xf0
+        or v2, v4
+        ld v3, 0xff
+        call check
+
+        ld v2, 0x3c     ; 8XY2
+        ld v4, 0x0f
+        and v2, v4
+        ld v3, 0x0c
+        call check
+
+        ld v2, 0x3c     ; 8XY3
+        xor v2, v4
+        ld v3, 0x33
+        call check
+
+        ld v2, 0        ; 2NNN and 00EE
+        call seven
+        ld v3, 7
+        call check
+
+        ld v0, 2        ; BNNN jumps past the first instruction of the table, whether
+        ld v2, 2        ; it adds v0 or, as BXNN, the register its address starts with
+        ld v6, 2
+        jp v0, table
+table:  jp wrong
+        ld v2, 1
+        jp jumped
+wrong:  ld v2, 0
+jumped: ld v3, 1
+        call check
+
+        ld i, data      ; FX1E
+        ld v4, 1
+        add i, v4
+        ld v0, [i]
+        ld v2, v0
+        ld v3, 0x22
+        call check
+
+        ld v4, 0xa5     ; FX55 and FX65
+        ld v5, 0x5a
+        ld i, scratch
+        ld [i], v5
+        ld v5, 0
+        ld i, scratch
+        ld v5, [i]
+        ld v2, v5
+        ld v3, 0x5a
+        call check
+
+        ld v4, 137      ; FX33
+        ld i, scratch
+        ld b, v4
+        ld i, scratch
+        ld v2, [i]
+        ld v4, 1        ; Leaves v2 zero only if the digits were 1, 3 and 7
+        xor v0, v4
+        ld v4, 3
+        xor v1, v4
+        ld v4, 7
+        xor v2, v4
+        or v2, v0
+        or v2, v1
+        ld v3, 0
+        call check
+
+        ld v4, 0x1a     ; FX29 uses the low nibble
+        ld f, v4
+        ld v2, [i]
+        ld v3, 0xf0
+        call check
+
+        ld v4, 10       ; FX15 and FX07
+        ld dt, v4
+        ld v2, dt
+        se v2, 0
+        ld v2, 1
+        ld v3, 1
+        call check
+
+        rnd v2, 0       ; CXNN masks the random number
+        ld v3, 0
+        call check
+
+halt:   jp halt
+
+seven:  ld v2, 7
+        ret
+
+; Draws a tick if v2 equals v3, otherwise a cross, then moves along.
+check:  ld i, tick
+        se v2, v3
+        ld i, cross
+        drw vd, ve, 5
+        add vd, 8
+        se vd, 64
+        ret
+        ld vd, 0
+        add ve, 6
+        ret
+
+tick:    db 0x01, 0x02, 0x84, 0x48, 0x30
+cross:   db 0x88, 0x50, 0x20, 0x50, 0x88
+data:    db 0x11, 0x22
+scratch: db 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0
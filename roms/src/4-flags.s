@@ -0,0 +1,116 @@
+; Checks the results and VF flags of the arithmetic and shift instructions, with and without
+; a carry or borrow, then sprite collisions. Each pair of marks is the result then the flag:
+;
+;   8XY4 8XY4+carry 8XY5 8XY5+borrow
+;   8XY7 8XY7+borrow 8XY6 8XYE
+;   DXYN DXYN+collision
+
+        cls
+        ld vd, 0        ; where the next mark goes
+        ld ve, 1
+
+        ld v2, 0x10     ; 8XY4
+        ld v4, 0x20
+        add v2, v4
+        ld v5, vf
+        ld v3, 0x30
+        call check
+        ld v6, 0
+        call flag
+
+        ld v2, 0xf0     ; 8XY4 with a carry
+        add v2, v4
+        ld v5, vf
+        ld v3, 0x10
+        call check
+        ld v6, 1
+        call flag
+
+        ld v2, 0x30     ; 8XY5
+        ld v4, 0x10
+        sub v2, v4
+        ld v5, vf
+        ld v3, 0x20
+        call check
+        ld v6, 1
+        call flag
+
+        ld v2, 0x10     ; 8XY5 with a borrow
+        ld v4, 0x30
+        sub v2, v4
+        ld v5, vf
+        ld v3, 0xe0
+        call check
+        ld v6, 0
+        call flag
+
+        ld v2, 0x10     ; 8XY7
+        ld v4, 0x30
+        subn v2, v4
+        ld v5, vf
+        ld v3, 0x20
+        call check
+        ld v6, 1
+        call flag
+
+        ld v2, 0x30     ; 8XY7 with a borrow
+        ld v4, 0x10
+        subn v2, v4
+        ld v5, vf
+        ld v3, 0xe0
+        call check
+        ld v6, 0
+        call flag
+
+        ld v2, 0x05     ; 8XY6, shifting VX into itself so the shift quirk doesn't matter
+        shr v2, v2
+        ld v5, vf
+        ld v3, 0x02
+        call check
+        ld v6, 1
+        call flag
+
+        ld v2, 0x81     ; 8XYE
+        shl v2, v2
+        ld v5, vf
+        ld v3, 0x02
+        call check
+        ld v6, 1
+        call flag
+
+        ld v4, 40       ; DXYN, drawn twice so the second collides and erases it
+        ld i, tick
+        drw v4, v4, 5
+        ld v5, vf
+        ld v2, 0
+        ld v3, 0
+        call check
+        ld v6, 0
+        call flag
+        ld i, tick
+        drw v4, v4, 5
+        ld v5, vf
+        call check
+        ld v6, 1
+        call flag
+
+halt:   jp halt
+
+; Draws a mark for the flag in v5, expected to be v6.
+flag:   ld v2, v5
+        ld v3, v6
+
+; Draws a tick if v2 equals v3, otherwise a cross, then moves along.
+check:  ld i, tick
+        se v2, v3
+        ld i, cross
+        drw vd, ve, 5
+        add vd, 8
+        se vd, 64
+        ret
+        ld vd, 0
+        add ve, 6
+        ret
+
+tick:  db 0x01, 0x02, 0x84, 0x48, 0x30
+cross: db 0x88, 0x50, 0x20, 0x50, 0x88
@@ -0,0 +1,48 @@
+; Shows which quirks the interpreter has as a row of digits, 1 for yes and 0 for no:
+;
+;   8XY6 shifts VY   FX55 increments I   BXNN adds VX   FX1E sets VF past 0xFFF
+
+        cls
+        ld vd, 8        ; where the next digit goes
+        ld ve, 13
+
+        ld v2, 1        ; Shifting VY gives 2, shifting VX gives 0
+        ld v4, 4
+        shr v2, v4
+        ld v5, 0
+        sne v2, 2
+        ld v5, 1
+        call show
+
+        ld i, pair      ; A second load reads the next byte if the first moved I on
+        ld v0, [i]
+        ld v0, [i]
+        ld v5, v0
+        call show
+
+        ld v0, 0        ; BXNN lands past the table's first instruction
+        ld v2, 2
+        ld v6, 2
+        jp v0, table
+table:  jp plain
+        ld v5, 1
+        jp jumped
+plain:  ld v5, 0
+jumped: call show
+
+        ld vf, 0        ; Only the quirk sets VF when I passes 0xFFF
+        ld i, 0xfff
+        ld v4, 1
+        add i, v4
+        ld v5, vf
+        call show
+
+halt:   jp halt
+
+; Draws the digit in v5 and moves along.
+show:   ld f, v5
+        drw vd, ve, 5
+        add vd, 12
+        ret
+
+pair:   db 0, 1
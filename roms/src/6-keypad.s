@@ -0,0 +1,30 @@
+; Waits for a key with FX0A and shows it, then waits for key 5 to be pressed with EXA1 and
+; released with EX9E, showing 5 and then a tick.
+
+        cls
+        ld v0, 8
+        ld v1, 13
+        ld v2, k
+        ld f, v2
+        drw v0, v1, 5
+
+        ld v3, 5
+held:   sknp v3
+        jp pressed
+        jp held
+pressed:
+        add v0, 12
+        ld f, v3
+        drw v0, v1, 5
+
+released:
+        skp v3
+        jp done
+        jp released
+done:   add v0, 12
+        ld i, tick
+        drw v0, v1, 5
+
+halt:   jp halt
+
+tick:   db 0x01, 0x02, 0x84, 0x48, 0x30
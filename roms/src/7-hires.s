@@ -0,0 +1,38 @@
+; Boots the two-page VIP interpreter into its 64x64 mode with the 1260 jump, then draws over
+; the whole taller screen: a cross that 0230 clears away, a tick in the top left and bottom
+; right corners, and 6 and 4 in rows a 64x32 screen doesn't have.
+;
+; On a VIP the interpreter's second page sits between the jump and 0x2C0; this ROM leaves it
+; blank, since the emulator provides it.
+
+        jp 0x260
+        org 0x2c0
+
+        ld v0, 28
+        ld v1, 28
+        ld i, cross
+        drw v0, v1, 5
+        hires cls
+
+        ld v0, 0
+        ld v1, 0
+        ld i, tick
+        drw v0, v1, 5
+        ld v0, 56
+        ld v1, 59
+        drw v0, v1, 5
+
+        ld v0, 20
+        ld v1, 40
+        ld v2, 6
+        ld f, v2
+        drw v0, v1, 5
+        add v0, 12
+        ld v2, 4
+        ld f, v2
+        drw v0, v1, 5
+
+halt:   jp halt
+
+tick:   db 0x01, 0x02, 0x84, 0x48, 0x30
+cross:  db 0x88, 0x50, 0x20, 0x50, 0x88
@@ -0,0 +1,29 @@
+; Turns on MEGA-CHIP's 256x192 colour mode with 0011 and draws where a 64x32 screen has no
+; room: a 4x4 square from two palette colours loaded with 02NN and sized with 03NN and 04NN,
+; with a see-through middle, and A from the font near the bottom right corner.
+
+        megaon
+        cls
+        ld i, palette
+        ldpal 2
+        sprw 4
+        sprh 4
+        ld v0, 120
+        ld v1, 90
+        ld i, square
+        drw v0, v1, 0
+
+        ld v0, 200
+        ld v1, 150
+        ld v2, 0xa
+        ld f, v2
+        drw v0, v1, 5
+
+halt:   jp halt
+
+; Red and blue, as ARGB.
+palette: db 0xff, 0xff, 0x00, 0x00, 0xff, 0x00, 0x00, 0xff
+square:  db 1, 1, 1, 1
+         db 1, 0, 0, 2
+         db 1, 0, 0, 2
+         db 2, 2, 2, 2
@@ -0,0 +1,727 @@
+use std::{fs, path::PathBuf};
+
+use crossbeam_channel::{Receiver, Sender, TryRecvError};
+use eframe::egui;
+
+use crate::{
+    chip8::{
+        control::{Control, Reset},
+        display::{Frame, FrameReceiver, PixelFormat},
+        keypad::{Event, Key},
+        platform::Platform,
+        status::{State, Status},
+    },
+    crt::Crt,
+    database::Database,
+    keymap::{self, Keymap, RomKeymap},
+    palette::{self, Palette},
+    recording::Recorder,
+    screen::{Aspect, Flicker, Scaling, Screen},
+    screenshot::{self, Screenshots},
+};
+
+/// The window's ends of the channels to the emulator thread.
+pub struct Channels {
+    pub frames: FrameReceiver,
+    pub events: Sender<Event>,
+    pub controls: Sender<Control>,
+    pub statuses: Receiver<Status>,
+}
+
+pub struct MyApp {
+    /// The last complete frame from the emulator, drawn until a newer one arrives.
+    frame: Frame,
+    channels: Channels,
+    state: State,
+    /// The last thing that went wrong, shown in a banner until dismissed.
+    error: Option<String>,
+    rom: Option<(usize, Platform)>,
+    halted_at: Option<u16>,
+    sound_on: bool,
+    /// Frames emulated and instructions per second, as last reported.
+    stats: Option<(u64, u64)>,
+    screen: Screen,
+    palette: Palette,
+    /// The palette from the command line or the program database, offered alongside the
+    /// built-in ones.
+    starting_palette: Palette,
+    keymap: Keymap,
+    rom_keymap_path: Option<PathBuf>,
+    /// For the keymap of a ROM dropped on the window.
+    database: Database,
+    remap: Option<Remap>,
+    /// The CHIP-8 keys last reported as down, so only changes are sent.
+    held: [bool; 16],
+    screenshots: Screenshots,
+    /// Where the last screenshot was saved, shown in the status bar.
+    saved_screenshot: Option<PathBuf>,
+    recorder: Option<Recorder>,
+}
+
+/// The state of the key remapping window.
+#[derive(Default)]
+struct Remap {
+    /// The CHIP-8 key waiting for the next host key press.
+    capturing: Option<Key>,
+    message: String,
+}
+
+impl MyApp {
+    pub fn new(
+        channels: Channels,
+        palette: Palette,
+        screen: Screen,
+        keymap: RomKeymap,
+        database: Database,
+        screenshots: Screenshots,
+        recorder: Option<Recorder>,
+    ) -> Self {
+        let frame = Frame {
+            number: 0,
+            width: 64,
+            height: 32,
+            format: PixelFormat::Indexed,
+            pixels: vec![0; 64 * 32].into_boxed_slice(),
+        };
+        if recorder.is_some() {
+            channels.frames.set_recording(true);
+        }
+        Self {
+            frame,
+            channels,
+            state: State::Running,
+            error: None,
+            rom: None,
+            halted_at: None,
+            sound_on: false,
+            stats: None,
+            screen,
+            palette,
+            starting_palette: palette,
+            keymap: keymap.keymap,
+            rom_keymap_path: keymap.path,
+            database,
+            remap: None,
+            held: [false; 16],
+            screenshots,
+            saved_screenshot: None,
+            recorder,
+        }
+    }
+
+    /// Sends a key event for each CHIP-8 key that has gone down or up since the last frame. All
+    /// keys are released while the window is unfocused or a key is being remapped, since their
+    /// key ups would otherwise be missed.
+    fn send_keys(&mut self, ctx: &egui::Context) {
+        let capturing = self
+            .remap
+            .as_ref()
+            .is_some_and(|remap| remap.capturing.is_some());
+        if capturing || !ctx.input(|i| i.focused) {
+            if self.held.contains(&true) {
+                self.held = [false; 16];
+                self.send_event(Event::ReleaseAll);
+            }
+            return;
+        }
+        let mut held = [false; 16];
+        ctx.input(|i| {
+            for (host_key, key) in self.keymap.bindings() {
+                if i.key_down(*host_key) {
+                    held[*key as usize] = true;
+                }
+            }
+        });
+        for key in Key::ALL {
+            match (self.held[key as usize], held[key as usize]) {
+                (false, true) => self.send_event(Event::KeyDown(key)),
+                (true, false) => self.send_event(Event::KeyUp(key)),
+                _ => {}
+            }
+        }
+        self.held = held;
+    }
+
+    /// Binds the next bindable key pressed to the CHIP-8 key being captured. Escape cancels.
+    fn capture_key(&mut self, ctx: &egui::Context) {
+        let capturing = match self.remap.as_ref().and_then(|remap| remap.capturing) {
+            Some(key) => key,
+            None => return,
+        };
+        let pressed = ctx.input(|i| {
+            i.events.iter().find_map(|event| match event {
+                egui::Event::Key {
+                    key, pressed: true, ..
+                } => Some(*key),
+                _ => None,
+            })
+        });
+        let remap = self.remap.as_mut().unwrap();
+        match pressed {
+            Some(egui::Key::Escape) => remap.capturing = None,
+            Some(host_key) if keymap::is_bindable(host_key) => {
+                self.keymap.bind(host_key, capturing);
+                remap.capturing = None;
+            }
+            Some(host_key) => remap.message = format!("{} cannot be bound", host_key.name()),
+            None => {}
+        }
+    }
+
+    fn remap_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.remap.is_some();
+        egui::Window::new("Keys")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let remap = self.remap.as_mut().unwrap();
+                egui::Grid::new("keymap").striped(true).show(ui, |ui| {
+                    for key in Key::ALL {
+                        ui.label(format!("{:X}", key as u8));
+                        let names: Vec<&str> =
+                            self.keymap.host_keys(key).map(|k| k.name()).collect();
+                        ui.label(if names.is_empty() {
+                            "unbound".to_string()
+                        } else {
+                            names.join(", ")
+                        });
+                        let add = if remap.capturing == Some(key) {
+                            "Press a key..."
+                        } else {
+                            "Add"
+                        };
+                        if ui.button(add).clicked() {
+                            remap.capturing = Some(key);
+                            remap.message.clear();
+                        }
+                        if ui.button("Clear").clicked() {
+                            self.keymap.unbind(key);
+                        }
+                        ui.end_row();
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let rom_path = self.rom_keymap_path.as_ref();
+                    if ui
+                        .add_enabled(rom_path.is_some(), egui::Button::new("Save for this ROM"))
+                        .clicked()
+                    {
+                        remap.message = save_keymap(&self.keymap, rom_path);
+                    }
+                    if ui.button("Save as default").clicked() {
+                        let path = keymap::default_keymap_path();
+                        remap.message = save_keymap(&self.keymap, path.as_ref());
+                    }
+                    if ui.button("Reset").clicked() {
+                        self.keymap = Keymap::default();
+                    }
+                });
+                if !remap.message.is_empty() {
+                    ui.label(&remap.message);
+                }
+            });
+        if !open {
+            self.remap = None;
+        }
+    }
+
+    // Sends fail once the emulator thread has stopped, which the menu bar shows.
+    fn send_event(&self, event: Event) {
+        let _ = self.channels.events.send(event);
+    }
+
+    fn send_control(&self, control: Control) {
+        let _ = self.channels.controls.send(control);
+    }
+
+    fn toggle_pause(&self) {
+        match self.state {
+            State::Running => self.send_control(Control::Pause),
+            State::Paused => self.send_control(Control::Resume),
+            State::Faulted | State::Stopped => {}
+        }
+    }
+
+    /// Saves the frame on screen as a PNG, drawn as it is shown at the screenshot scale, or
+    /// exactly as the emulator drew it if `raw`.
+    fn save_screenshot(&mut self, raw: bool) {
+        let image = if raw {
+            screenshot::raw(&self.frame, &self.palette)
+        } else {
+            let pixels = self.screen.picture(&self.frame, &self.palette);
+            let size = [self.frame.width, self.frame.height];
+            screenshot::render(pixels, size, &self.screen.crt, self.screenshots.scale)
+        };
+        let path = self.screenshots.path(self.frame.number, raw);
+        match screenshot::save_png(&image, &path) {
+            Ok(()) => self.saved_screenshot = Some(path),
+            Err(error) => self.error = Some(error),
+        }
+    }
+
+    /// Starts recording from the frame on screen, or finishes the recording in progress.
+    fn toggle_recording(&mut self) {
+        match self.recorder.take() {
+            Some(recorder) => {
+                self.channels.frames.set_recording(false);
+                let path = recorder.path().to_path_buf();
+                match recorder.finish(self.frame.number) {
+                    Ok(()) => self.saved_screenshot = Some(path),
+                    Err(error) => self.error = Some(error),
+                }
+            }
+            None => {
+                let path = self.screenshots.recording_path(self.frame.number);
+                match Recorder::new(&path) {
+                    Ok(recorder) => {
+                        self.recorder = Some(recorder);
+                        self.channels.frames.set_recording(true);
+                        self.record_frame();
+                    }
+                    Err(error) => self.error = Some(error),
+                }
+            }
+        }
+    }
+
+    /// Shows the newest frame from the emulator. While recording, every frame since the last
+    /// update is taken in turn and recorded, so the recording has none of the gaps the window
+    /// skips over.
+    fn receive_frames(&mut self, ctx: &egui::Context) {
+        let latest = self.channels.frames.latest();
+        let frames = match &self.recorder {
+            Some(_) => self.channels.frames.recorded(),
+            None => latest.into_iter().collect(),
+        };
+        let mut received = false;
+        for frame in frames {
+            if frame.number <= self.frame.number {
+                continue;
+            }
+            if frame.number > self.frame.number + 1 {
+                log::trace!("Skipped {} frames", frame.number - self.frame.number - 1);
+            }
+            self.frame = frame;
+            self.record_frame();
+            received = true;
+        }
+        if received {
+            self.screen.upload(ctx, &self.frame, &self.palette);
+        }
+    }
+
+    /// Adds the frame on screen to the recording, drawn as it is shown.
+    fn record_frame(&mut self) {
+        let Some(recorder) = &mut self.recorder else {
+            return;
+        };
+        let pixels = self.screen.picture(&self.frame, &self.palette);
+        let size = [self.frame.width, self.frame.height];
+        let image = screenshot::render(pixels, size, &self.screen.crt, self.screenshots.scale);
+        if let Err(error) = recorder.add(image, self.frame.number) {
+            self.recorder = None;
+            self.channels.frames.set_recording(false);
+            self.error = Some(error);
+        }
+    }
+
+    /// Replaces the running program with a ROM file dropped on the window, and switches to the
+    /// keymap for that ROM.
+    fn load_dropped_rom(&mut self, ctx: &egui::Context) {
+        let path = ctx.input(|i| {
+            i.raw
+                .dropped_files
+                .iter()
+                .find_map(|file| file.path.clone())
+        });
+        if let Some(path) = path {
+            match fs::read(&path) {
+                Ok(program) => {
+                    self.error = None;
+                    match RomKeymap::load(&program, self.database.lookup(&program).as_ref()) {
+                        Ok(keymap) => {
+                            self.keymap = keymap.keymap;
+                            self.rom_keymap_path = keymap.path;
+                        }
+                        Err(error) => self.error = Some(error),
+                    }
+                    self.send_control(Control::Load(program));
+                    self.screenshots.name = screenshot::rom_name(&path);
+                }
+                Err(error) => {
+                    self.error = Some(format!("could not read {}: {}", path.display(), error))
+                }
+            }
+        }
+    }
+
+    fn menu_bar(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        egui::TopBottomPanel::top("menu").show(ctx, |ui| {
+            egui::menu::bar(ui, |ui| {
+                ui.menu_button("Emulator", |ui| {
+                    let paused = self.state == State::Paused;
+                    if ui
+                        .button(if paused { "Resume (F6)" } else { "Pause (F6)" })
+                        .clicked()
+                    {
+                        self.toggle_pause();
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(paused, egui::Button::new("Step frame (F7)"))
+                        .clicked()
+                    {
+                        self.send_control(Control::Step);
+                    }
+                    if ui.button("Reset (F5)").clicked() {
+                        self.send_control(Control::Reset(Reset::Soft));
+                        ui.close_menu();
+                    }
+                    if ui.button("Hard reset (Shift+F5)").clicked() {
+                        self.send_control(Control::Reset(Reset::Hard));
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    if ui.button("Keys... (F1)").clicked() {
+                        self.remap = Some(Remap::default());
+                        ui.close_menu();
+                    }
+                    if ui.button("Quit (Esc)").clicked() {
+                        frame.close();
+                    }
+                });
+                ui.menu_button("View", |ui| {
+                    ui.label("Scaling");
+                    ui.radio_value(&mut self.screen.scaling, Scaling::Integer, "Whole pixels");
+                    ui.radio_value(&mut self.screen.scaling, Scaling::Fit, "Fit to window");
+                    ui.separator();
+                    ui.label("Aspect ratio");
+                    ui.radio_value(&mut self.screen.aspect, Aspect::Square, "Square pixels");
+                    ui.radio_value(&mut self.screen.aspect, Aspect::FourThree, "4:3");
+                    ui.radio_value(&mut self.screen.aspect, Aspect::Stretch, "Stretch");
+                    ui.separator();
+                    ui.menu_button("Flicker reduction", |ui| {
+                        let mut flicker = self.screen.flicker;
+                        ui.radio_value(&mut flicker, Flicker::Off, "Off");
+                        let decay = matches!(flicker, Flicker::Decay { .. });
+                        if ui.radio(decay, "Phosphor persistence").clicked() && !decay {
+                            flicker = Flicker::Decay {
+                                fade: Flicker::DEFAULT_FADE,
+                            };
+                        }
+                        if let Flicker::Decay { fade } = &mut flicker {
+                            ui.add(egui::Slider::new(fade, 0.1..=0.95).text("fade"));
+                        }
+                        ui.radio_value(&mut flicker, Flicker::Blend, "Blend last two frames");
+                        let or = matches!(flicker, Flicker::Or { .. });
+                        if ui.radio(or, "Lit in any recent frame").clicked() && !or {
+                            flicker = Flicker::Or {
+                                frames: Flicker::DEFAULT_FRAMES,
+                            };
+                        }
+                        if let Flicker::Or { frames } = &mut flicker {
+                            let range = 2..=Flicker::MAX_FRAMES;
+                            ui.add(egui::Slider::new(frames, range).text("frames"));
+                        }
+                        if flicker != self.screen.flicker {
+                            self.screen.flicker = flicker;
+                            self.screen.upload(ctx, &self.frame, &self.palette);
+                        }
+                    });
+                    ui.menu_button("CRT effects", |ui| {
+                        let mut crt = self.screen.crt;
+                        for name in Crt::NAMES {
+                            let max = Crt::max_strength(name);
+                            let effect = crt.effect_mut(name).unwrap();
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut effect.enabled, name);
+                                ui.add_enabled(
+                                    effect.enabled,
+                                    egui::Slider::new(&mut effect.strength, 0.0..=max),
+                                );
+                            });
+                        }
+                        if crt != self.screen.crt {
+                            self.screen.crt = crt;
+                            self.screen.upload(ctx, &self.frame, &self.palette);
+                        }
+                    });
+                    ui.menu_button("Palette", |ui| {
+                        let mut palette = self.palette;
+                        ui.radio_value(&mut palette, self.starting_palette, "Starting palette");
+                        for (name, _) in palette::BUILT_IN {
+                            let built_in = Palette::built_in(name).unwrap();
+                            ui.radio_value(&mut palette, built_in, *name);
+                        }
+                        if palette != self.palette {
+                            self.palette = palette;
+                            self.screen.upload(ctx, &self.frame, &self.palette);
+                        }
+                    });
+                    ui.separator();
+                    if ui.button("Screenshot (F12)").clicked() {
+                        self.save_screenshot(false);
+                        ui.close_menu();
+                    }
+                    if ui.button("Raw screenshot (Shift+F12)").clicked() {
+                        self.save_screenshot(true);
+                        ui.close_menu();
+                    }
+                    let recording = self.recorder.is_some();
+                    let label = if recording {
+                        "Stop recording (F9)"
+                    } else {
+                        "Start recording (F9)"
+                    };
+                    if ui.button(label).clicked() {
+                        self.toggle_recording();
+                        ui.close_menu();
+                    }
+                    if ui.button("Fullscreen (F11)").clicked() {
+                        toggle_fullscreen(frame);
+                        ui.close_menu();
+                    }
+                });
+            });
+        });
+    }
+
+    fn receive_statuses(&mut self) {
+        loop {
+            match self.channels.statuses.try_recv() {
+                Ok(Status::State(state)) => {
+                    self.state = state;
+                    if state == State::Running {
+                        self.halted_at = None;
+                    }
+                }
+                Ok(Status::Rom { size, platform }) => {
+                    self.rom = Some((size, platform));
+                    self.halted_at = None;
+                }
+                Ok(Status::Fault(fault)) => self.error = Some(fault.to_string()),
+                Ok(Status::Halted { address }) => self.halted_at = Some(address),
+                Ok(Status::Sound(on)) => self.sound_on = on,
+                Ok(Status::Stats {
+                    frames,
+                    instructions_per_second,
+                }) => self.stats = Some((frames, instructions_per_second)),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    if self.state != State::Stopped {
+                        self.error = Some("The emulator thread stopped unexpectedly".to_string());
+                        self.state = State::Stopped;
+                    }
+                    break;
+                }
+            }
+        }
+    }
+
+    fn error_banner(&mut self, ctx: &egui::Context) {
+        let error = match &self.error {
+            Some(error) => error.clone(),
+            None => return,
+        };
+        egui::TopBottomPanel::top("error").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.colored_label(ui.visuals().error_fg_color, format!("⚠ {}", error));
+                if self.state == State::Faulted {
+                    if ui.button("Reset").clicked() {
+                        self.send_control(Control::Reset(Reset::Soft));
+                        self.error = None;
+                    }
+                    if ui.button("Hard reset").clicked() {
+                        self.send_control(Control::Reset(Reset::Hard));
+                        self.error = None;
+                    }
+                }
+                if ui.button("Dismiss").clicked() {
+                    self.error = None;
+                }
+            });
+        });
+    }
+
+    fn status_bar(&self, ctx: &egui::Context) {
+        egui::TopBottomPanel::bottom("status").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let state = match self.state {
+                    State::Running => "Running",
+                    State::Paused => "Paused",
+                    State::Faulted => "Faulted",
+                    State::Stopped => "Stopped",
+                };
+                ui.label(state);
+                if let Some(address) = self.halted_at {
+                    ui.label(format!("halted at {:#05x}", address));
+                }
+                if let Some((size, platform)) = self.rom {
+                    ui.separator();
+                    ui.label(format!("{}, {} bytes", platform.name(), size));
+                }
+                if let Some((frames, instructions_per_second)) = self.stats {
+                    ui.separator();
+                    ui.label(format!("frame {}", frames));
+                    if instructions_per_second > 0 {
+                        ui.label(format!("{} IPS", instructions_per_second));
+                    }
+                }
+                if self.sound_on {
+                    ui.separator();
+                    ui.label("♪");
+                }
+                if let Some(recorder) = &self.recorder {
+                    ui.separator();
+                    ui.label(format!("● recording {}", recorder.path().display()));
+                } else if let Some(path) = &self.saved_screenshot {
+                    ui.separator();
+                    ui.label(format!("saved {}", path.display()));
+                }
+            });
+        });
+    }
+}
+
+impl eframe::App for MyApp {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        self.receive_statuses();
+        self.menu_bar(ctx, frame);
+        self.error_banner(ctx);
+        self.status_bar(ctx);
+        egui::CentralPanel::default().show(ctx, |ui| {
+            self.receive_frames(ctx);
+
+            if ui.input(|i| i.key_pressed(egui::Key::F1)) {
+                self.remap = match self.remap {
+                    Some(_) => None,
+                    None => Some(Remap::default()),
+                };
+            }
+            self.send_keys(ctx);
+            let capturing = self
+                .remap
+                .as_ref()
+                .is_some_and(|remap| remap.capturing.is_some());
+            if capturing {
+                self.capture_key(ctx);
+            } else {
+                if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                    frame.close();
+                }
+                if ui.input(|i| i.key_pressed(egui::Key::F5)) {
+                    let reset = if ui.input(|i| i.modifiers.shift) {
+                        Reset::Hard
+                    } else {
+                        Reset::Soft
+                    };
+                    self.send_control(Control::Reset(reset));
+                }
+                if ui.input(|i| i.key_pressed(egui::Key::F6)) {
+                    self.toggle_pause();
+                }
+                if ui.input(|i| i.key_pressed(egui::Key::F7)) && self.state == State::Paused {
+                    self.send_control(Control::Step);
+                }
+                if ui.input(|i| i.key_pressed(egui::Key::F11)) {
+                    toggle_fullscreen(frame);
+                }
+                if ui.input(|i| i.key_pressed(egui::Key::F9)) {
+                    self.toggle_recording();
+                }
+                if ui.input(|i| i.key_pressed(egui::Key::F12)) {
+                    self.save_screenshot(ui.input(|i| i.modifiers.shift));
+                }
+            }
+            self.load_dropped_rom(ctx);
+
+            self.screen.show(ui);
+            ui.ctx().request_repaint()
+        });
+        if self.remap.is_some() {
+            self.remap_window(ctx);
+        }
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.send_control(Control::Shutdown);
+        if let Some(recorder) = self.recorder.take() {
+            let path = recorder.path().to_path_buf();
+            match recorder.finish(self.frame.number) {
+                Ok(()) => eprintln!("Saved {}", path.display()),
+                Err(error) => eprintln!("error: {}", error),
+            }
+        }
+    }
+}
+
+fn toggle_fullscreen(frame: &mut eframe::Frame) {
+    let fullscreen = frame.info().window_info.fullscreen;
+    frame.set_fullscreen(!fullscreen);
+}
+
+/// Writes the keymap and describes how it went, for the remapping window to show.
+fn save_keymap(keymap: &Keymap, path: Option<&PathBuf>) -> String {
+    match path {
+        Some(path) => match keymap.save(path) {
+            Ok(()) => format!("Saved to {}", path.display()),
+            Err(error) => error,
+        },
+        None => "There is no config directory to save to".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crossbeam_channel::unbounded;
+
+    use super::*;
+    use crate::chip8::display::{frame_slot, Chip8Display};
+
+    #[test]
+    fn records_every_frame_for_a_recording_started_on_the_command_line() {
+        let path = std::env::temp_dir().join(format!("chip8-app-{}.y4m", std::process::id()));
+        let (sender, frames) = frame_slot();
+        let channels = Channels {
+            frames,
+            events: unbounded().0,
+            controls: unbounded().0,
+            statuses: unbounded().1,
+        };
+        let screenshots = Screenshots {
+            directory: std::env::temp_dir(),
+            name: "app".to_string(),
+            scale: 1,
+            recording_format: crate::recording::Format::Y4m,
+        };
+        let recorder = Recorder::new(&path).unwrap();
+        let mut app = MyApp::new(
+            channels,
+            Palette::default(),
+            Screen::new(),
+            RomKeymap {
+                keymap: Keymap::default(),
+                path: None,
+            },
+            Database::bundled(),
+            screenshots,
+            Some(recorder),
+        );
+
+        let mut display = Chip8Display::new(sender, 64, 32);
+        for number in 1..=3 {
+            display.draw_sprite_row(number as usize, 0, 0x80);
+            display.publish(number);
+        }
+        app.receive_frames(&egui::Context::default());
+        assert_eq!(app.frame.number, 3);
+        app.recorder.take().unwrap().finish(3).unwrap();
+
+        let video = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let frames = video.windows(6).filter(|w| w == b"FRAME\n").count();
+        assert_eq!(frames, 3);
+    }
+}
@@ -0,0 +1,253 @@
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+use crate::chip8::audio::AudioSink;
+
+/// Where sound is played.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AudioOutput {
+    /// The system's audio device, if built with the `native-audio` feature.
+    Native,
+    None,
+    Wav(PathBuf),
+}
+
+impl AudioOutput {
+    /// Parses `native`, `none`, or the path of a `.wav` file to write.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "native" => Ok(AudioOutput::Native),
+            "none" => Ok(AudioOutput::None),
+            _ if value.ends_with(".wav") => Ok(AudioOutput::Wav(PathBuf::from(value))),
+            _ => Err(format!(
+                "audio output `{}` must be native, none or a .wav file",
+                value
+            )),
+        }
+    }
+}
+
+/// Writes sound to a 16-bit mono WAV file, so it can be checked without sound hardware. The
+/// file is completed when the sink is dropped.
+pub struct WavSink {
+    writer: Option<hound::WavWriter<BufWriter<File>>>,
+    path: PathBuf,
+}
+
+const WAV_SAMPLE_RATE: u32 = 44_100;
+
+impl WavSink {
+    pub fn create(path: &Path) -> Result<Self, String> {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: WAV_SAMPLE_RATE,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let writer = hound::WavWriter::create(path, spec)
+            .map_err(|error| format!("could not create {}: {}", path.display(), error))?;
+        Ok(Self {
+            writer: Some(writer),
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+impl AudioSink for WavSink {
+    fn sample_rate(&self) -> u32 {
+        WAV_SAMPLE_RATE
+    }
+
+    fn write(&mut self, samples: &[f32]) {
+        let Some(writer) = &mut self.writer else {
+            return;
+        };
+        for &sample in samples {
+            let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            if let Err(error) = writer.write_sample(value) {
+                log::error!("Could not write {}: {}", self.path.display(), error);
+                self.writer = None;
+                return;
+            }
+        }
+    }
+}
+
+impl Drop for WavSink {
+    fn drop(&mut self) {
+        if let Some(writer) = self.writer.take() {
+            if let Err(error) = writer.finalize() {
+                log::error!("Could not finish {}: {}", self.path.display(), error);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "native-audio")]
+pub use native::{open_native, NativeOutput};
+
+/// Stands in for the audio stream when built without a native backend.
+#[cfg(not(feature = "native-audio"))]
+pub struct NativeOutput;
+
+#[cfg(not(feature = "native-audio"))]
+pub fn open_native() -> Result<(Box<dyn AudioSink>, NativeOutput), String> {
+    Err("native audio needs the native-audio feature".to_string())
+}
+
+#[cfg(feature = "native-audio")]
+mod native {
+    use std::{
+        collections::VecDeque,
+        sync::{Arc, Mutex},
+    };
+
+    use cpal::{
+        traits::{DeviceTrait, HostTrait, StreamTrait},
+        FromSample, Sample, SizedSample,
+    };
+
+    use crate::chip8::audio::AudioSink;
+
+    type Queue = Arc<Mutex<VecDeque<f32>>>;
+
+    /// Queues sound for the audio device, which plays it from another thread.
+    pub struct NativeSink {
+        queue: Queue,
+        sample_rate: u32,
+    }
+
+    /// The open audio stream, which plays for as long as it is kept.
+    pub struct NativeOutput {
+        _stream: cpal::Stream,
+    }
+
+    /// Opens the default output device.
+    pub fn open_native() -> Result<(Box<dyn AudioSink>, NativeOutput), String> {
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or("there is no audio output device")?;
+        let supported = device
+            .default_output_config()
+            .map_err(|error| error.to_string())?;
+        let config = supported.config();
+        let queue = Queue::default();
+        let stream = match supported.sample_format() {
+            cpal::SampleFormat::F32 => build::<f32>(&device, &config, queue.clone()),
+            cpal::SampleFormat::I16 => build::<i16>(&device, &config, queue.clone()),
+            cpal::SampleFormat::U16 => build::<u16>(&device, &config, queue.clone()),
+            format => return Err(format!("unsupported audio sample format {}", format)),
+        }
+        .map_err(|error| error.to_string())?;
+        stream.play().map_err(|error| error.to_string())?;
+        let sink = NativeSink {
+            queue,
+            sample_rate: config.sample_rate.0,
+        };
+        Ok((Box::new(sink), NativeOutput { _stream: stream }))
+    }
+
+    fn build<T>(
+        device: &cpal::Device,
+        config: &cpal::StreamConfig,
+        queue: Queue,
+    ) -> Result<cpal::Stream, cpal::BuildStreamError>
+    where
+        T: SizedSample + FromSample<f32>,
+    {
+        let channels = config.channels as usize;
+        device.build_output_stream(
+            config,
+            move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+                let mut queue = queue.lock().unwrap();
+                // Running short, as when the emulator is paused, plays silence.
+                for frame in data.chunks_mut(channels) {
+                    frame.fill(T::from_sample(queue.pop_front().unwrap_or(0.0)));
+                }
+            },
+            |error| log::error!("Audio output failed: {}", error),
+            None,
+        )
+    }
+
+    impl AudioSink for NativeSink {
+        fn sample_rate(&self) -> u32 {
+            self.sample_rate
+        }
+
+        fn write(&mut self, samples: &[f32]) {
+            let mut queue = self.queue.lock().unwrap();
+            queue.extend(samples);
+            // Drop what the device has fallen behind on, such as when running unlimited, so
+            // the sound never lags more than a tenth of a second.
+            let limit = self.sample_rate as usize / 10;
+            if queue.len() > limit {
+                let excess = queue.len() - limit;
+                queue.drain(..excess);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::chip8::audio::{Beeper, Tone, Waveform};
+
+    use super::*;
+
+    /// Plays `frames` through a WAV file, beeping in the frames marked true, and reads it back.
+    fn record(name: &str, tone: Tone, frames: &[bool]) -> (hound::WavSpec, Vec<i16>) {
+        let path = std::env::temp_dir().join(format!("chip8-{}-{}.wav", name, std::process::id()));
+        let mut beeper = Beeper::new(tone, Box::new(WavSink::create(&path).unwrap()));
+        for &on in frames {
+            beeper.play_frame(on);
+        }
+        drop(beeper);
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        let samples = reader.samples::<i16>().map(Result::unwrap).collect();
+        let spec = reader.spec();
+        fs::remove_file(&path).unwrap();
+        (spec, samples)
+    }
+
+    #[test]
+    fn parses_outputs() {
+        assert_eq!(AudioOutput::parse("none"), Ok(AudioOutput::None));
+        assert_eq!(
+            AudioOutput::parse("beep.wav"),
+            Ok(AudioOutput::Wav(PathBuf::from("beep.wav")))
+        );
+        assert!(AudioOutput::parse("beep.mp3").is_err());
+    }
+
+    #[test]
+    fn writes_a_frame_of_samples_for_every_frame() {
+        let (spec, samples) = record("length", Tone::default(), &[true, true, false]);
+        assert_eq!(spec.channels, 1);
+        assert_eq!(spec.sample_rate, WAV_SAMPLE_RATE);
+        assert_eq!(spec.bits_per_sample, 16);
+        assert_eq!(samples.len(), 3 * 735);
+    }
+
+    #[test]
+    fn writes_the_beep_at_its_volume_then_silence() {
+        let tone = Tone {
+            pitch: 44_100.0 / 128.0,
+            volume: 0.5,
+            waveform: Waveform::Square,
+        };
+        let (_, samples) = record("beep", tone, &[true, true, false]);
+        let level = (0.5 * i16::MAX as f32) as i16;
+        for (index, &sample) in samples[..2 * 735].iter().enumerate() {
+            // A cycle every 128 samples, high for the first half.
+            let expected = if index % 128 < 64 { level } else { -level };
+            assert_eq!(sample, expected, "sample {}", index);
+        }
+        assert!(samples[2 * 735..].iter().all(|&sample| sample == 0));
+    }
+}
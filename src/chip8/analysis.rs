@@ -0,0 +1,364 @@
+use std::fmt::Display;
+
+use super::{platform::Platform, Instruction};
+
+/// The kind of machine a ROM looks like it was written for, including ones this emulator does
+/// not run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Family {
+    Chip8,
+    /// A VIP program that calls its own 1802 machine code with `0NNN`.
+    HybridVip,
+    Chip8Hires,
+    Eti660,
+    SuperChip,
+    XoChip,
+    MegaChip,
+}
+
+impl Family {
+    pub fn name(self) -> &'static str {
+        match self {
+            Family::Chip8 => "chip8",
+            Family::HybridVip => "hybrid VIP chip8",
+            Family::Chip8Hires => "chip8-hires",
+            Family::Eti660 => "eti660",
+            Family::SuperChip => "superchip",
+            Family::XoChip => "xo-chip",
+            Family::MegaChip => "megachip",
+        }
+    }
+
+    /// The platform to run the ROM as, if it is one this emulator supports.
+    pub fn platform(self) -> Option<Platform> {
+        match self {
+            Family::Chip8 | Family::HybridVip => Some(Platform::Chip8),
+            Family::Chip8Hires => Some(Platform::Chip8Hires),
+            Family::Eti660 => Some(Platform::Eti660),
+            Family::MegaChip => Some(Platform::MegaChip),
+            Family::SuperChip | Family::XoChip => None,
+        }
+    }
+
+    /// How the family behaves before anything specific to the ROM is taken into account.
+    fn quirks(self) -> Vec<(&'static str, bool)> {
+        let (shift, increment, jump) = match self {
+            Family::Chip8
+            | Family::HybridVip
+            | Family::Chip8Hires
+            | Family::Eti660
+            | Family::XoChip => (true, true, false),
+            Family::SuperChip | Family::MegaChip => (false, false, true),
+        };
+        vec![
+            ("assign-shift", shift),
+            ("load-store-increment", increment),
+            ("jump-with-offset-add", jump),
+            ("xo-chip-audio", self == Family::XoChip),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Confidence {
+    Low,
+    Medium,
+    High,
+}
+
+impl Display for Confidence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Confidence::Low => "low",
+            Confidence::Medium => "medium",
+            Confidence::High => "high",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A guess at what a ROM needs to run, with the instructions that led to it.
+pub struct Analysis {
+    pub family: Family,
+    pub confidence: Confidence,
+    pub quirks: Vec<(&'static str, bool)>,
+    pub evidence: Vec<String>,
+}
+
+/// Families that extend others, checked first so a superset wins when its own instructions
+/// turn up.
+const PRIORITY: [Family; 6] = [
+    Family::MegaChip,
+    Family::XoChip,
+    Family::SuperChip,
+    Family::Chip8Hires,
+    Family::HybridVip,
+    Family::Eti660,
+];
+
+/// Scans a ROM for instructions only some platforms have and for habits that depend on quirks.
+///
+/// Data mixed in with code decodes as instructions too, so a single match is weak evidence; the
+/// confidence grows with the number of different tell-tale instructions found.
+pub fn analyse(program: &[u8]) -> Analysis {
+    let instructions: Vec<Instruction> = program
+        .chunks_exact(2)
+        .map(|pair| Instruction::new(u16::from_be_bytes([pair[0], pair[1]])))
+        .collect();
+
+    let mut clues: Vec<(Family, String)> = Vec::new();
+    let mut add_clue = |family: Family, clue: String| {
+        if !clues.iter().any(|(f, c)| *f == family && *c == clue) {
+            clues.push((family, clue));
+        }
+    };
+
+    if instructions.first().map(|i| i.value) == Some(0x1260) {
+        add_clue(Family::Chip8Hires, "1260 boot jump".to_string());
+    }
+    // MEGA-CHIP's other instructions are only told apart from machine code calls by the program
+    // switching mega mode on somewhere.
+    let mega = instructions.iter().any(|i| i.value == 0x0011);
+    for instruction in &instructions {
+        if let Some((family, clue)) = classify(instruction, mega) {
+            add_clue(family, clue);
+        }
+    }
+    if let Some(clue) = eti660_jumps(&instructions, program.len()) {
+        add_clue(Family::Eti660, clue);
+    }
+
+    let count = |family: Family| clues.iter().filter(|(f, _)| *f == family).count();
+    let family = PRIORITY
+        .into_iter()
+        .find(|family| count(*family) >= 2)
+        .or_else(|| PRIORITY.into_iter().find(|family| count(*family) == 1))
+        .unwrap_or(Family::Chip8);
+    let confidence = match count(family) {
+        // Nothing unusual at all is a fair sign of a plain CHIP-8 program.
+        0 => Confidence::Medium,
+        1 if family == Family::Chip8Hires => Confidence::High,
+        1 => Confidence::Low,
+        2 => Confidence::Medium,
+        _ => Confidence::High,
+    };
+    let mut evidence: Vec<String> = clues
+        .into_iter()
+        .filter(|(f, _)| *f == family)
+        .map(|(_, clue)| clue)
+        .collect();
+
+    let mut quirks = family.quirks();
+    if let Some(clue) = relies_on_increment(&instructions) {
+        quirks.retain(|(name, _)| *name != "load-store-increment");
+        quirks.push(("load-store-increment", true));
+        evidence.push(clue);
+    }
+
+    Analysis {
+        family,
+        confidence,
+        quirks,
+        evidence,
+    }
+}
+
+/// Names the family an instruction belongs to if it is not in the original instruction set.
+fn classify(instruction: &Instruction, mega: bool) -> Option<(Family, String)> {
+    let x = instruction.x();
+    let n = instruction.n();
+    let nn = instruction.nn();
+    let nnn = instruction.nnn();
+    let found = match instruction.first() {
+        0x0 => match nnn {
+            0x000 | 0x0E0 | 0x0EE => return None,
+            0x230 => (Family::Chip8Hires, "0230 hires clear"),
+            0x010 => (Family::MegaChip, "0010 megaoff"),
+            0x011 => (Family::MegaChip, "0011 megaon"),
+            // The rest of MEGA-CHIP's instructions are machine code calls below 0xA00 in a
+            // program that never turns mega mode on.
+            0x100..=0x1FF if mega => (Family::MegaChip, "01NN long index"),
+            0x200..=0x2FF if mega => (Family::MegaChip, "02NN load palette"),
+            0x300..=0x3FF if mega => (Family::MegaChip, "03NN sprite width"),
+            0x400..=0x4FF if mega => (Family::MegaChip, "04NN sprite height"),
+            0x500..=0x5FF if mega => (Family::MegaChip, "05NN alpha"),
+            0x600..=0x60F if mega => (Family::MegaChip, "060N play sample"),
+            0x700 if mega => (Family::MegaChip, "0700 stop sample"),
+            0x800..=0x80F if mega => (Family::MegaChip, "080N blend mode"),
+            0x900..=0x9FF if mega => (Family::MegaChip, "09NN collision colour"),
+            0x0FB => (Family::SuperChip, "00FB scroll right"),
+            0x0FC => (Family::SuperChip, "00FC scroll left"),
+            0x0FD => (Family::SuperChip, "00FD exit"),
+            0x0FE => (Family::SuperChip, "00FE lores"),
+            0x0FF => (Family::SuperChip, "00FF hires"),
+            0x0C1..=0x0CF => (Family::SuperChip, "00CN scroll down"),
+            0x0D1..=0x0DF => (Family::XoChip, "00DN scroll up"),
+            _ => {
+                return Some((
+                    Family::HybridVip,
+                    format!("{} machine code call", instruction),
+                ))
+            }
+        },
+        0x5 if n == 0x2 => (Family::XoChip, "5XY2 save range"),
+        0x5 if n == 0x3 => (Family::XoChip, "5XY3 load range"),
+        0xD if n == 0x0 => (Family::SuperChip, "DXY0 16x16 sprite"),
+        0xF if x == 0x0 && nn == 0x00 => (Family::XoChip, "F000 long index"),
+        0xF if nn == 0x01 => (Family::XoChip, "FN01 plane select"),
+        0xF if x == 0x0 && nn == 0x02 => (Family::XoChip, "F002 audio pattern"),
+        0xF if nn == 0x3A => (Family::XoChip, "FX3A pitch"),
+        0xF if nn == 0x30 => (Family::SuperChip, "FX30 large font"),
+        0xF if nn == 0x75 => (Family::SuperChip, "FX75 save flags"),
+        0xF if nn == 0x85 => (Family::SuperChip, "FX85 load flags"),
+        _ => return None,
+    };
+    Some((found.0, found.1.to_string()))
+}
+
+/// Checks whether the program's jumps and calls only make sense loaded at 0x600.
+fn eti660_jumps(instructions: &[Instruction], length: usize) -> Option<String> {
+    let targets: Vec<u16> = instructions
+        .iter()
+        .filter(|i| i.first() == 0x1 || i.first() == 0x2)
+        .map(|i| i.nnn())
+        .collect();
+    if targets.len() < 2 {
+        return None;
+    }
+    let inside = |start: u16| {
+        targets
+            .iter()
+            .filter(|&&target| target >= start && ((target - start) as usize) < length)
+            .count()
+    };
+    let (vip, eti) = (inside(0x200), inside(0x600));
+    (eti == targets.len() && vip < targets.len())
+        .then(|| format!("all {} jumps land in the program at 0x600", eti))
+}
+
+/// Looks for back-to-back FX55/FX65 with no change to I in between, which only reads or writes
+/// consecutive memory if I is left pointing past the registers.
+fn relies_on_increment(instructions: &[Instruction]) -> Option<String> {
+    let mut last_transfer: Option<&Instruction> = None;
+    for instruction in instructions {
+        let nn = instruction.nn();
+        match instruction.first() {
+            0xA | 0xB | 0x1 | 0x2 => last_transfer = None,
+            0x0 if instruction.nnn() == 0x0EE => last_transfer = None,
+            0xF if nn == 0x1E || nn == 0x29 || nn == 0x30 => last_transfer = None,
+            0xF if nn == 0x55 || nn == 0x65 => {
+                if let Some(previous) = last_transfer {
+                    return Some(format!(
+                        "{} then {} without setting I",
+                        previous, instruction
+                    ));
+                }
+                last_transfer = Some(instruction);
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assemble(instructions: &[u16]) -> Vec<u8> {
+        instructions.iter().flat_map(|i| i.to_be_bytes()).collect()
+    }
+
+    fn family(instructions: &[u16]) -> (Family, Confidence) {
+        let analysis = analyse(&assemble(instructions));
+        (analysis.family, analysis.confidence)
+    }
+
+    #[test]
+    fn plain_programs_are_chip8() {
+        let program = [0x00E0, 0x6005, 0xA20A, 0xD015, 0x1208];
+        assert_eq!(family(&program), (Family::Chip8, Confidence::Medium));
+    }
+
+    #[test]
+    fn machine_code_calls_are_hybrid_vip() {
+        let program = [0x00E0, 0x0A10, 0x0C20, 0x1204];
+        let analysis = analyse(&assemble(&program));
+        assert_eq!(analysis.family, Family::HybridVip);
+        assert_eq!(analysis.confidence, Confidence::Medium);
+        assert_eq!(
+            analysis.evidence,
+            ["0x0a10 machine code call", "0x0c20 machine code call"]
+        );
+    }
+
+    #[test]
+    fn low_calls_without_mega_mode_are_machine_code() {
+        let program = [0x00E0, 0x02A0, 0x0340, 0x1200];
+        let analysis = analyse(&assemble(&program));
+        assert_eq!(analysis.family, Family::HybridVip);
+        assert_eq!(
+            analysis.evidence,
+            ["0x02a0 machine code call", "0x0340 machine code call"]
+        );
+    }
+
+    #[test]
+    fn a_1260_boot_jump_is_chip8_hires() {
+        let program = [0x1260, 0x00E0, 0x0230, 0x1204];
+        assert_eq!(family(&program), (Family::Chip8Hires, Confidence::Medium));
+    }
+
+    #[test]
+    fn jumps_into_a_program_at_0x600_are_eti660() {
+        let program = [0x00E0, 0x2608, 0x1602, 0x00E0, 0x00EE];
+        let analysis = analyse(&assemble(&program));
+        assert_eq!(analysis.family, Family::Eti660);
+        assert_eq!(
+            analysis.evidence,
+            ["all 2 jumps land in the program at 0x600"]
+        );
+    }
+
+    #[test]
+    fn superchip_instructions_are_superchip() {
+        let program = [0x00FF, 0x00C4, 0xD010, 0xF075, 0x00FD];
+        let analysis = analyse(&assemble(&program));
+        assert_eq!(analysis.family, Family::SuperChip);
+        assert_eq!(analysis.confidence, Confidence::High);
+        assert!(analysis.quirks.contains(&("assign-shift", false)));
+    }
+
+    #[test]
+    fn xo_chip_instructions_are_xo_chip() {
+        let program = [0xF000, 0x1234, 0xF201, 0xF002, 0xF33A];
+        assert_eq!(family(&program), (Family::XoChip, Confidence::High));
+    }
+
+    #[test]
+    fn megachip_extensions_are_megachip_not_machine_code() {
+        let program = [
+            0x0011, 0x0101, 0x2345, 0x0203, 0x0310, 0x0410, 0x0580, 0x0601, 0x0700, 0x0802, 0x0901,
+            0x0010,
+        ];
+        let analysis = analyse(&assemble(&program));
+        assert_eq!(analysis.family, Family::MegaChip);
+        assert_eq!(analysis.confidence, Confidence::High);
+        assert!(analysis
+            .evidence
+            .iter()
+            .all(|clue| !clue.contains("machine code")));
+        assert_eq!(analysis.evidence.len(), 11);
+    }
+
+    #[test]
+    fn back_to_back_loads_rely_on_i_incrementing() {
+        let program = [0xA300, 0xF155, 0xF155, 0x1206];
+        let analysis = analyse(&assemble(&program));
+        assert!(analysis.quirks.contains(&("load-store-increment", true)));
+        assert_eq!(
+            analysis.evidence.last().unwrap(),
+            "0xf155 then 0xf155 without setting I"
+        );
+    }
+}
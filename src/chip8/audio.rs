@@ -0,0 +1,367 @@
+use std::f32::consts::TAU;
+
+/// Somewhere the emulator's sound goes, a 60 Hz frame at a time.
+pub trait AudioSink: Send {
+    /// Samples per second the sink expects.
+    fn sample_rate(&self) -> u32;
+
+    /// Plays mono samples from -1 to 1, following on from the last ones written.
+    fn write(&mut self, samples: &[f32]);
+}
+
+/// Discards all sound, for when there is nowhere to play it.
+pub struct NullSink;
+
+impl AudioSink for NullSink {
+    fn sample_rate(&self) -> u32 {
+        44_100
+    }
+
+    fn write(&mut self, _samples: &[f32]) {}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Waveform {
+    Square,
+    Triangle,
+    Sawtooth,
+    Sine,
+}
+
+impl Waveform {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "square" => Some(Waveform::Square),
+            "triangle" => Some(Waveform::Triangle),
+            "sawtooth" => Some(Waveform::Sawtooth),
+            "sine" => Some(Waveform::Sine),
+            _ => None,
+        }
+    }
+
+    /// The wave's level `phase` of the way through a cycle.
+    fn level(self, phase: f32) -> f32 {
+        match self {
+            Waveform::Square if phase < 0.5 => 1.0,
+            Waveform::Square => -1.0,
+            Waveform::Triangle => 4.0 * (phase - 0.5).abs() - 1.0,
+            Waveform::Sawtooth => 2.0 * phase - 1.0,
+            Waveform::Sine => (phase * TAU).sin(),
+        }
+    }
+}
+
+/// What the beep sounds like.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tone {
+    /// Frequency in hertz.
+    pub pitch: f32,
+    /// Loudness from 0 to 1.
+    pub volume: f32,
+    pub waveform: Waveform,
+}
+
+impl Default for Tone {
+    fn default() -> Self {
+        Self {
+            pitch: 440.0,
+            volume: 0.25,
+            waveform: Waveform::Square,
+        }
+    }
+}
+
+/// The bits in an XO-CHIP audio pattern, played oldest first from the most significant bit.
+const PATTERN_BITS: f64 = 128.0;
+
+/// The pitch register value that plays a pattern at 4000 bits a second.
+pub const DEFAULT_PITCH: u8 = 64;
+
+/// Sounds a tone while the sound timer runs, generating exactly one frame of samples for every
+/// emulated frame so the sound keeps time with the emulation rather than the host.
+///
+/// Once a program loads an XO-CHIP audio pattern, the pattern is played instead of the tone.
+/// MEGA-CHIP samples play over the top, whether or not the sound timer runs.
+pub struct Beeper {
+    tone: Tone,
+    sink: Box<dyn AudioSink>,
+    /// How far through a cycle of the tone the next sample is.
+    phase: f32,
+    pattern: Option<[u8; 16]>,
+    pitch: u8,
+    /// How many bits into the pattern the next sample starts.
+    position: f64,
+    /// Frames played, to spread sample rates that do not divide by 60 evenly.
+    frames: u64,
+    samples: Vec<f32>,
+    sample: Option<Sample>,
+}
+
+/// A MEGA-CHIP digitised sound being played.
+struct Sample {
+    /// Unsigned 8-bit PCM, centred on 128.
+    data: Vec<u8>,
+    /// Samples of `data` per second.
+    rate: f64,
+    looping: bool,
+    /// How far into `data` the next output sample is taken from.
+    position: f64,
+}
+
+impl Beeper {
+    pub fn new(tone: Tone, sink: Box<dyn AudioSink>) -> Self {
+        Self {
+            tone,
+            sink,
+            phase: 0.0,
+            pattern: None,
+            pitch: DEFAULT_PITCH,
+            position: 0.0,
+            frames: 0,
+            samples: Vec::new(),
+            sample: None,
+        }
+    }
+
+    /// Plays `pattern` in place of the tone from now on.
+    pub fn set_pattern(&mut self, pattern: [u8; 16]) {
+        self.pattern = Some(pattern);
+    }
+
+    pub fn set_pitch(&mut self, pitch: u8) {
+        self.pitch = pitch;
+    }
+
+    /// Plays unsigned 8-bit PCM at `rate` samples a second from the next frame, once or over
+    /// and over until stopped. Any sample already playing is cut off.
+    pub fn play_sample(&mut self, data: Vec<u8>, rate: u32, looping: bool) {
+        self.sample = (!data.is_empty() && rate > 0).then_some(Sample {
+            data,
+            rate: rate as f64,
+            looping,
+            position: 0.0,
+        });
+    }
+
+    pub fn stop_sample(&mut self) {
+        self.sample = None;
+    }
+
+    /// Goes back to the plain tone, for when the machine is reset.
+    pub fn reset(&mut self) {
+        self.pattern = None;
+        self.pitch = DEFAULT_PITCH;
+        self.sample = None;
+    }
+
+    /// Bits of the pattern played per second.
+    fn pattern_rate(&self) -> f64 {
+        4000.0 * 2f64.powf((self.pitch as f64 - 64.0) / 48.0)
+    }
+
+    /// A beeper that makes no sound.
+    pub fn silent() -> Self {
+        Self::new(Tone::default(), Box::new(NullSink))
+    }
+
+    /// Plays one frame, beeping if `on` and staying quiet otherwise.
+    pub fn play_frame(&mut self, on: bool) {
+        let rate = self.sink.sample_rate() as u64;
+        let count = ((self.frames + 1) * rate / 60 - self.frames * rate / 60) as usize;
+        self.frames += 1;
+
+        self.samples.clear();
+        if let (true, Some(pattern)) = (on, self.pattern) {
+            let step = self.pattern_rate() / rate as f64;
+            for _ in 0..count {
+                let level = pattern_level(&pattern, self.position, step);
+                self.samples.push(self.tone.volume * level as f32);
+                self.position = (self.position + step) % PATTERN_BITS;
+            }
+        } else if on {
+            let step = self.tone.pitch / rate as f32;
+            for _ in 0..count {
+                self.samples
+                    .push(self.tone.volume * self.tone.waveform.level(self.phase));
+                self.phase = (self.phase + step).fract();
+            }
+        } else {
+            // Starting each beep from the same point keeps the output repeatable.
+            self.phase = 0.0;
+            self.position = 0.0;
+            self.samples.resize(count, 0.0);
+        }
+        self.mix_sample(rate);
+        self.sink.write(&self.samples);
+    }
+
+    /// Adds the playing sample to the frame, taking the nearest sample for each output one.
+    fn mix_sample(&mut self, rate: u64) {
+        let Some(sample) = &mut self.sample else {
+            return;
+        };
+        let step = sample.rate / rate as f64;
+        let length = sample.data.len() as f64;
+        for output in &mut self.samples {
+            if sample.position >= length {
+                if !sample.looping {
+                    break;
+                }
+                sample.position %= length;
+            }
+            let level = (sample.data[sample.position as usize] as f32 - 128.0) / 128.0;
+            *output = (*output + self.tone.volume * level).clamp(-1.0, 1.0);
+            sample.position += step;
+        }
+        if !sample.looping && sample.position >= length {
+            self.sample = None;
+        }
+    }
+}
+
+/// The average level of the pattern over the `length` bits from `start`, taking the part of each
+/// bit that falls in the span so the pattern is resampled without aliasing from dropped bits.
+fn pattern_level(pattern: &[u8; 16], start: f64, length: f64) -> f64 {
+    let mut position = start;
+    let mut remaining = length;
+    let mut total = 0.0;
+    while remaining > 0.0 {
+        let bit = position as usize % 128;
+        let level = if pattern[bit / 8] & (0x80 >> (bit % 8)) != 0 {
+            1.0
+        } else {
+            -1.0
+        };
+        let span = (position.floor() + 1.0 - position).min(remaining);
+        total += level * span;
+        position = (position + span) % PATTERN_BITS;
+        remaining -= span;
+    }
+    total / length
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    /// Keeps everything written to it, for checking what was played.
+    pub struct BufferSink {
+        rate: u32,
+        samples: Arc<Mutex<Vec<f32>>>,
+    }
+
+    impl BufferSink {
+        /// A sink at `rate` samples a second and the buffer it fills.
+        pub fn new(rate: u32) -> (Self, Arc<Mutex<Vec<f32>>>) {
+            let samples = Arc::new(Mutex::new(Vec::new()));
+            let sink = Self {
+                rate,
+                samples: samples.clone(),
+            };
+            (sink, samples)
+        }
+    }
+
+    impl AudioSink for BufferSink {
+        fn sample_rate(&self) -> u32 {
+            self.rate
+        }
+
+        fn write(&mut self, samples: &[f32]) {
+            self.samples.lock().unwrap().extend_from_slice(samples);
+        }
+    }
+
+    /// A beeper at `rate` samples a second at full volume.
+    fn beeper(rate: u32) -> (Beeper, Arc<Mutex<Vec<f32>>>) {
+        let (sink, samples) = BufferSink::new(rate);
+        let tone = Tone {
+            volume: 1.0,
+            ..Tone::default()
+        };
+        (Beeper::new(tone, Box::new(sink)), samples)
+    }
+
+    #[test]
+    fn plays_a_sample_once_at_its_own_rate() {
+        let (mut beeper, samples) = beeper(600);
+        beeper.play_sample(vec![0xFF, 0x00, 0x80, 0xC0], 300, false);
+        beeper.play_frame(false);
+        beeper.play_frame(false);
+        let high = 127.0 / 128.0;
+        let expected = [high, high, -1.0, -1.0, 0.0, 0.0, 0.5, 0.5, 0.0, 0.0];
+        assert_eq!(samples.lock().unwrap()[..10], expected);
+        assert!(samples.lock().unwrap()[10..].iter().all(|&s| s == 0.0));
+        assert!(beeper.sample.is_none());
+    }
+
+    #[test]
+    fn loops_a_sample_until_it_is_stopped() {
+        let (mut beeper, samples) = beeper(600);
+        beeper.play_sample(vec![0xC0, 0x40, 0x80], 600, true);
+        beeper.play_frame(false);
+        assert_eq!(
+            *samples.lock().unwrap(),
+            [0.5, -0.5, 0.0, 0.5, -0.5, 0.0, 0.5, -0.5, 0.0, 0.5]
+        );
+        beeper.stop_sample();
+        beeper.play_frame(false);
+        assert!(samples.lock().unwrap()[10..].iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn mixes_a_sample_over_the_beep() {
+        let (mut beeper, samples) = beeper(600);
+        beeper.tone.pitch = 60.0;
+        beeper.play_sample(vec![0x00], 600, true);
+        beeper.play_frame(true);
+        let samples = samples.lock().unwrap();
+        assert_eq!(samples[..5], [0.0; 5]);
+        assert_eq!(samples[5..10], [-1.0; 5]);
+    }
+
+    #[test]
+    fn plays_a_pattern_at_the_default_pitch() {
+        let (mut beeper, samples) = beeper(8000);
+        beeper.set_pattern([0xF0; 16]);
+        beeper.play_frame(true);
+        let samples = samples.lock().unwrap();
+        assert_eq!(samples.len(), 133);
+        let cycle = [[1.0; 8], [-1.0; 8]].concat();
+        assert_eq!(samples[..32], [&cycle[..], &cycle[..]].concat());
+    }
+
+    #[test]
+    fn raises_the_pitch_an_octave_every_48_steps() {
+        let (mut beeper, samples) = beeper(8000);
+        beeper.set_pattern([0xF0; 16]);
+        beeper.set_pitch(112);
+        beeper.play_frame(true);
+        let samples = samples.lock().unwrap();
+        let cycle = [[1.0; 4], [-1.0; 4]].concat();
+        assert_eq!(samples[..16], [&cycle[..], &cycle[..]].concat());
+    }
+
+    #[test]
+    fn averages_bits_that_fall_between_samples() {
+        let (mut beeper, samples) = beeper(2000);
+        beeper.set_pattern([0xAA; 16]);
+        beeper.play_frame(true);
+        assert!(samples.lock().unwrap().iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn starts_the_pattern_over_after_silence() {
+        let (mut beeper, samples) = beeper(8000);
+        beeper.set_pattern([0xF0; 16]);
+        beeper.play_frame(true);
+        beeper.play_frame(false);
+        beeper.play_frame(true);
+        let samples = samples.lock().unwrap();
+        assert_eq!(samples.len(), 400);
+        assert!(samples[133..266].iter().all(|&s| s == 0.0));
+        assert_eq!(samples[266..399], samples[..133]);
+    }
+}
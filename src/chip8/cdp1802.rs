@@ -0,0 +1,507 @@
+/// The memory and I/O lines an RCA CDP1802 is wired to.
+pub trait Bus {
+    fn read(&mut self, address: u16) -> u8;
+    fn write(&mut self, address: u16, value: u8);
+    fn input(&mut self, port: u8) -> u8;
+    fn output(&mut self, port: u8, value: u8);
+    fn flag(&self, number: u8) -> bool;
+}
+
+pub struct Cdp1802 {
+    pub registers: [u16; 16],
+    pub p: u8,
+    pub x: u8,
+    pub d: u8,
+    pub df: bool,
+    pub t: u8,
+    pub ie: bool,
+    pub q: bool,
+    idle: bool,
+}
+
+impl Cdp1802 {
+    pub fn new() -> Self {
+        Self {
+            registers: [0; 16],
+            p: 0,
+            x: 0,
+            d: 0,
+            df: false,
+            t: 0,
+            ie: true,
+            q: false,
+            idle: false,
+        }
+    }
+
+    pub fn interrupt(&mut self) {
+        if self.ie {
+            self.t = (self.x << 4) | self.p;
+            self.x = 2;
+            self.p = 1;
+            self.ie = false;
+            self.idle = false;
+        }
+    }
+
+    /// Executes one instruction and returns the number of machine cycles it took.
+    pub fn step(&mut self, bus: &mut impl Bus) -> u64 {
+        if self.idle {
+            return 2;
+        }
+        let opcode = self.immediate(bus);
+        let n = opcode & 0xF;
+        match opcode >> 4 {
+            0x0 if n == 0 => self.idle = true,
+            0x0 => self.d = bus.read(self.registers[n as usize]),
+            0x1 => self.registers[n as usize] = self.registers[n as usize].wrapping_add(1),
+            0x2 => self.registers[n as usize] = self.registers[n as usize].wrapping_sub(1),
+            0x3 => {
+                let condition = self.condition(bus, n);
+                self.short_branch(bus, condition);
+            }
+            0x4 => {
+                self.d = bus.read(self.registers[n as usize]);
+                self.registers[n as usize] = self.registers[n as usize].wrapping_add(1);
+            }
+            0x5 => bus.write(self.registers[n as usize], self.d),
+            0x6 if n == 0 => self.increment_x(),
+            0x6 if n < 8 => {
+                let value = bus.read(self.rx());
+                bus.output(n, value);
+                self.increment_x();
+            }
+            0x6 if n == 8 => {}
+            0x6 => {
+                let value = bus.input(n - 8);
+                bus.write(self.rx(), value);
+                self.d = value;
+            }
+            0x7 => self.execute_7(bus, n),
+            0x8 => self.d = self.registers[n as usize] as u8,
+            0x9 => self.d = (self.registers[n as usize] >> 8) as u8,
+            0xA => {
+                self.registers[n as usize] = (self.registers[n as usize] & 0xFF00) | self.d as u16
+            }
+            0xB => {
+                self.registers[n as usize] =
+                    (self.registers[n as usize] & 0x00FF) | ((self.d as u16) << 8)
+            }
+            0xC => {
+                self.execute_long(bus, n);
+                return 3;
+            }
+            0xD => self.p = n,
+            0xE => self.x = n,
+            _ => self.execute_f(bus, n),
+        }
+        2
+    }
+
+    fn execute_7(&mut self, bus: &mut impl Bus, n: u8) {
+        match n {
+            0x0 | 0x1 => {
+                let value = bus.read(self.rx());
+                self.increment_x();
+                self.x = value >> 4;
+                self.p = value & 0xF;
+                self.ie = n == 0x0;
+            }
+            0x2 => {
+                self.d = bus.read(self.rx());
+                self.increment_x();
+            }
+            0x3 => {
+                bus.write(self.rx(), self.d);
+                self.registers[self.x as usize] = self.rx().wrapping_sub(1);
+            }
+            0x4 => {
+                let value = bus.read(self.rx());
+                self.add(value, self.df);
+            }
+            0x5 => {
+                let value = bus.read(self.rx());
+                self.subtract(value, self.d, self.df);
+            }
+            0x6 => {
+                let carry = self.d & 0x1 != 0;
+                self.d = (self.d >> 1) | ((self.df as u8) << 7);
+                self.df = carry;
+            }
+            0x7 => {
+                let value = bus.read(self.rx());
+                self.subtract(self.d, value, self.df);
+            }
+            0x8 => bus.write(self.rx(), self.t),
+            0x9 => {
+                self.t = (self.x << 4) | self.p;
+                bus.write(self.registers[2], self.t);
+                self.x = self.p;
+                self.registers[2] = self.registers[2].wrapping_sub(1);
+            }
+            0xA => self.q = false,
+            0xB => self.q = true,
+            0xC => {
+                let value = self.immediate(bus);
+                self.add(value, self.df);
+            }
+            0xD => {
+                let value = self.immediate(bus);
+                self.subtract(value, self.d, self.df);
+            }
+            0xE => {
+                let carry = self.d & 0x80 != 0;
+                self.d = (self.d << 1) | self.df as u8;
+                self.df = carry;
+            }
+            _ => {
+                let value = self.immediate(bus);
+                self.subtract(self.d, value, self.df);
+            }
+        }
+    }
+
+    fn execute_long(&mut self, bus: &mut impl Bus, n: u8) {
+        let condition = match n & 0x3 {
+            0x0 => true,
+            0x1 => self.q,
+            0x2 => self.d == 0,
+            _ => self.df,
+        };
+        match n {
+            // NOP
+            0x4 => {}
+            // LSIE
+            0xC => self.long_skip(self.ie),
+            // LSNQ, LSNZ, LSNF, LSKP
+            0x5..=0x8 => self.long_skip(!condition || n == 0x8),
+            // LSQ, LSZ, LSDF
+            0xD..=0xF => self.long_skip(condition),
+            // LBNQ, LBNZ, LBNF
+            0x9..=0xB => self.long_branch(bus, !condition),
+            _ => self.long_branch(bus, condition),
+        }
+    }
+
+    fn execute_f(&mut self, bus: &mut impl Bus, n: u8) {
+        let value = match n {
+            0x6 | 0xE => 0,
+            0x0..=0x7 => bus.read(self.rx()),
+            _ => self.immediate(bus),
+        };
+        match n & 0x7 {
+            0x0 => self.d = value,
+            0x1 => self.d |= value,
+            0x2 => self.d &= value,
+            0x3 => self.d ^= value,
+            0x4 => self.add(value, false),
+            0x5 => self.subtract(value, self.d, true),
+            0x6 if n == 0x6 => {
+                self.df = self.d & 0x1 != 0;
+                self.d >>= 1;
+            }
+            0x6 => {
+                self.df = self.d & 0x80 != 0;
+                self.d <<= 1;
+            }
+            _ => self.subtract(self.d, value, true),
+        }
+    }
+
+    fn condition(&self, bus: &impl Bus, n: u8) -> bool {
+        let condition = match n & 0x7 {
+            0x0 => true,
+            0x1 => self.q,
+            0x2 => self.d == 0,
+            0x3 => self.df,
+            flag => bus.flag(flag - 3),
+        };
+        // The upper half of each branch group tests the inverse condition, so 0x38 never branches.
+        if n & 0x8 != 0 {
+            !condition
+        } else {
+            condition
+        }
+    }
+
+    fn short_branch(&mut self, bus: &mut impl Bus, condition: bool) {
+        let pc = self.registers[self.p as usize];
+        if condition {
+            let low = bus.read(pc);
+            self.registers[self.p as usize] = (pc & 0xFF00) | low as u16;
+        } else {
+            self.registers[self.p as usize] = pc.wrapping_add(1);
+        }
+    }
+
+    fn long_branch(&mut self, bus: &mut impl Bus, condition: bool) {
+        let pc = self.registers[self.p as usize];
+        if condition {
+            let high = bus.read(pc);
+            let low = bus.read(pc.wrapping_add(1));
+            self.registers[self.p as usize] = u16::from_be_bytes([high, low]);
+        } else {
+            self.registers[self.p as usize] = pc.wrapping_add(2);
+        }
+    }
+
+    fn long_skip(&mut self, condition: bool) {
+        if condition {
+            let pc = self.registers[self.p as usize];
+            self.registers[self.p as usize] = pc.wrapping_add(2);
+        }
+    }
+
+    fn add(&mut self, value: u8, carry: bool) {
+        let result = self.d as u16 + value as u16 + carry as u16;
+        self.d = result as u8;
+        self.df = result > 0xFF;
+    }
+
+    /// Computes `minuend - subtrahend`, setting DF when no borrow occurred.
+    fn subtract(&mut self, minuend: u8, subtrahend: u8, no_borrow: bool) {
+        let result = minuend as i16 - subtrahend as i16 - !no_borrow as i16;
+        self.d = result as u8;
+        self.df = result >= 0;
+    }
+
+    fn immediate(&mut self, bus: &mut impl Bus) -> u8 {
+        let pc = self.registers[self.p as usize];
+        self.registers[self.p as usize] = pc.wrapping_add(1);
+        bus.read(pc)
+    }
+
+    fn rx(&self) -> u16 {
+        self.registers[self.x as usize]
+    }
+
+    fn increment_x(&mut self) {
+        self.registers[self.x as usize] = self.rx().wrapping_add(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestBus {
+        memory: Vec<u8>,
+        inputs: [u8; 8],
+        outputs: Vec<(u8, u8)>,
+        flags: [bool; 5],
+    }
+
+    impl Bus for TestBus {
+        fn read(&mut self, address: u16) -> u8 {
+            self.memory[address as usize]
+        }
+
+        fn write(&mut self, address: u16, value: u8) {
+            self.memory[address as usize] = value;
+        }
+
+        fn input(&mut self, port: u8) -> u8 {
+            self.inputs[port as usize]
+        }
+
+        fn output(&mut self, port: u8, value: u8) {
+            self.outputs.push((port, value));
+        }
+
+        fn flag(&self, number: u8) -> bool {
+            self.flags[number as usize]
+        }
+    }
+
+    /// A CPU about to run `program` from address 0 with R0 as the program counter.
+    fn machine(program: &[u8]) -> (Cdp1802, TestBus) {
+        let mut memory = vec![0; 0x10000];
+        memory[..program.len()].copy_from_slice(program);
+        let bus = TestBus {
+            memory,
+            inputs: [0; 8],
+            outputs: Vec::new(),
+            flags: [false; 5],
+        };
+        (Cdp1802::new(), bus)
+    }
+
+    fn run(cpu: &mut Cdp1802, bus: &mut TestBus, steps: usize) -> u64 {
+        (0..steps).map(|_| cpu.step(bus)).sum()
+    }
+
+    #[test]
+    fn short_branches_stay_in_the_page() {
+        // BZ 0x40 with D = 0, then BNZ 0x00 with D = 0, which falls through.
+        let (mut cpu, mut bus) = machine(&[0x32, 0x40]);
+        bus.memory[0x40..0x42].copy_from_slice(&[0x3A, 0x00]);
+        assert_eq!(run(&mut cpu, &mut bus, 1), 2);
+        assert_eq!(cpu.registers[0], 0x40);
+        run(&mut cpu, &mut bus, 1);
+        assert_eq!(cpu.registers[0], 0x42);
+
+        // B3 tests the third EF line, and SKP (0x38) never branches but skips its byte.
+        let (mut cpu, mut bus) = machine(&[0x36, 0x10, 0x38, 0xFF]);
+        bus.memory[0x10] = 0x38;
+        bus.flags[3] = true;
+        run(&mut cpu, &mut bus, 1);
+        assert_eq!(cpu.registers[0], 0x10);
+        cpu.registers[0] = 0x02;
+        run(&mut cpu, &mut bus, 1);
+        assert_eq!(cpu.registers[0], 0x04);
+    }
+
+    #[test]
+    fn long_branches_take_three_cycles_either_way() {
+        // LBDF 0x1234 with DF clear falls through, then LBR 0x1234.
+        let (mut cpu, mut bus) = machine(&[0xC3, 0x12, 0x34, 0xC0, 0x12, 0x34]);
+        assert_eq!(run(&mut cpu, &mut bus, 1), 3);
+        assert_eq!(cpu.registers[0], 0x03);
+        assert_eq!(run(&mut cpu, &mut bus, 1), 3);
+        assert_eq!(cpu.registers[0], 0x1234);
+
+        // LBNQ with Q set falls through.
+        let (mut cpu, mut bus) = machine(&[0xC9, 0x12, 0x34]);
+        cpu.q = true;
+        run(&mut cpu, &mut bus, 1);
+        assert_eq!(cpu.registers[0], 0x03);
+    }
+
+    #[test]
+    fn long_skips_jump_two_bytes_on_their_condition() {
+        let skipped = |opcode: u8, setup: fn(&mut Cdp1802)| {
+            let (mut cpu, mut bus) = machine(&[opcode]);
+            setup(&mut cpu);
+            assert_eq!(run(&mut cpu, &mut bus, 1), 3);
+            cpu.registers[0] == 0x03
+        };
+        assert!(skipped(0xC8, |_| {}));
+        assert!(!skipped(0xC4, |_| {}));
+        assert!(skipped(0xC5, |_| {}));
+        assert!(!skipped(0xC5, |cpu| cpu.q = true));
+        assert!(skipped(0xCD, |cpu| cpu.q = true));
+        assert!(skipped(0xCE, |_| {}));
+        assert!(skipped(0xC6, |cpu| cpu.d = 1));
+        assert!(skipped(0xCF, |cpu| cpu.df = true));
+        assert!(!skipped(0xC7, |cpu| cpu.df = true));
+        assert!(skipped(0xCC, |_| {}));
+        assert!(!skipped(0xCC, |cpu| cpu.ie = false));
+    }
+
+    #[test]
+    fn adds_and_subtracts_with_df_as_carry_and_no_borrow() {
+        // LDI 0xF0, ADI 0x20, ADCI 0x00
+        let (mut cpu, mut bus) = machine(&[0xF8, 0xF0, 0xFC, 0x20, 0x7C, 0x00]);
+        run(&mut cpu, &mut bus, 2);
+        assert_eq!((cpu.d, cpu.df), (0x10, true));
+        run(&mut cpu, &mut bus, 1);
+        assert_eq!((cpu.d, cpu.df), (0x11, false));
+
+        // LDI 0x10, SMI 0x20 borrows, SMBI 0x00 takes the borrow, SDI 0x05 is 0x05 - D.
+        let (mut cpu, mut bus) = machine(&[0xF8, 0x10, 0xFF, 0x20, 0x7F, 0x00, 0xFD, 0x05]);
+        run(&mut cpu, &mut bus, 2);
+        assert_eq!((cpu.d, cpu.df), (0xF0, false));
+        run(&mut cpu, &mut bus, 1);
+        assert_eq!((cpu.d, cpu.df), (0xEF, true));
+        run(&mut cpu, &mut bus, 1);
+        assert_eq!((cpu.d, cpu.df), (0x16, false));
+    }
+
+    #[test]
+    fn shifts_through_df() {
+        // LDI 0x81, SHR, SHRC, SHLC, SHL
+        let (mut cpu, mut bus) = machine(&[0xF8, 0x81, 0xF6, 0x76, 0x7E, 0xFE]);
+        run(&mut cpu, &mut bus, 2);
+        assert_eq!((cpu.d, cpu.df), (0x40, true));
+        run(&mut cpu, &mut bus, 1);
+        assert_eq!((cpu.d, cpu.df), (0xA0, false));
+        run(&mut cpu, &mut bus, 1);
+        assert_eq!((cpu.d, cpu.df), (0x40, true));
+        run(&mut cpu, &mut bus, 1);
+        assert_eq!((cpu.d, cpu.df), (0x80, false));
+    }
+
+    #[test]
+    fn mark_saves_x_and_p_for_ret_to_restore() {
+        // SEX 5, MARK, SEP 3 to a subroutine at 0x20 that does SEX 2, IRX, RET.
+        let (mut cpu, mut bus) = machine(&[0xE5, 0x79, 0xD3]);
+        bus.memory[0x20..0x23].copy_from_slice(&[0xE2, 0x60, 0x70]);
+        cpu.registers[2] = 0x80;
+        cpu.registers[3] = 0x20;
+        run(&mut cpu, &mut bus, 2);
+        assert_eq!(cpu.t, 0x50);
+        assert_eq!(bus.memory[0x80], 0x50);
+        assert_eq!((cpu.x, cpu.p), (0, 0));
+        assert_eq!(cpu.registers[2], 0x7F);
+
+        cpu.ie = false;
+        run(&mut cpu, &mut bus, 4);
+        assert_eq!((cpu.x, cpu.p), (5, 0));
+        assert_eq!(cpu.registers[0], 0x03);
+        assert_eq!(cpu.registers[2], 0x81);
+        assert!(cpu.ie);
+    }
+
+    #[test]
+    fn dis_restores_x_and_p_with_interrupts_off() {
+        let (mut cpu, mut bus) = machine(&[0xE2, 0x71]);
+        cpu.registers[2] = 0x40;
+        bus.memory[0x40] = 0x34;
+        run(&mut cpu, &mut bus, 2);
+        assert_eq!((cpu.x, cpu.p), (3, 4));
+        assert_eq!(cpu.registers[2], 0x41);
+        assert!(!cpu.ie);
+    }
+
+    #[test]
+    fn interrupts_save_x_and_p_and_wake_the_cpu() {
+        let (mut cpu, mut bus) = machine(&[0xE7, 0xD4]);
+        cpu.registers[4] = 0x10;
+        run(&mut cpu, &mut bus, 2);
+        // IDL at 0x10 waits.
+        run(&mut cpu, &mut bus, 2);
+        assert_eq!(cpu.registers[4], 0x11);
+        cpu.interrupt();
+        assert_eq!(cpu.t, 0x74);
+        assert_eq!((cpu.x, cpu.p, cpu.ie), (2, 1, false));
+        cpu.registers[1] = 0x30;
+        run(&mut cpu, &mut bus, 1);
+        assert_eq!(cpu.registers[1], 0x31);
+        // A second interrupt is held off until they are enabled again.
+        cpu.interrupt();
+        assert_eq!(cpu.p, 1);
+    }
+
+    #[test]
+    fn out_sends_memory_and_inp_reads_into_memory_and_d() {
+        // SEX 2, OUT 1, INP 2
+        let (mut cpu, mut bus) = machine(&[0xE2, 0x61, 0x6A]);
+        cpu.registers[2] = 0x40;
+        bus.memory[0x40] = 0x99;
+        bus.inputs[2] = 0x5A;
+        run(&mut cpu, &mut bus, 3);
+        assert_eq!(bus.outputs, [(1, 0x99)]);
+        assert_eq!(cpu.registers[2], 0x41);
+        assert_eq!(bus.memory[0x41], 0x5A);
+        assert_eq!(cpu.d, 0x5A);
+    }
+
+    #[test]
+    fn moves_bytes_between_d_registers_and_memory() {
+        // LDI 0xAB, PHI 5, LDI 0xCD, PLO 5, SEX 5, STXD, GHI 5, LDXA, GLO 5
+        let program = [
+            0xF8, 0xAB, 0xB5, 0xF8, 0xCD, 0xA5, 0xE5, 0x73, 0x95, 0x72, 0x85,
+        ];
+        let (mut cpu, mut bus) = machine(&program);
+        run(&mut cpu, &mut bus, 6);
+        assert_eq!(cpu.registers[5], 0xABCC);
+        assert_eq!(bus.memory[0xABCD], 0xCD);
+        run(&mut cpu, &mut bus, 1);
+        assert_eq!(cpu.d, 0xAB);
+        run(&mut cpu, &mut bus, 1);
+        assert_eq!(cpu.d, 0x00);
+        assert_eq!(cpu.registers[5], 0xABCD);
+        run(&mut cpu, &mut bus, 1);
+        assert_eq!(cpu.d, 0xCD);
+    }
+}
@@ -0,0 +1,31 @@
+/// Commands from the frontend that change what the emulator is doing, as opposed to the
+/// keypad [`super::keypad::Event`]s a program sees. They are handled between frames.
+pub enum Control {
+    Pause,
+    Resume,
+    Reset(Reset),
+    /// Replaces the program with a new ROM and hard resets, keeping the current settings.
+    Load(Vec<u8>),
+    /// Runs one frame while paused, then pauses again.
+    Step,
+    /// Stops the emulator thread. Dropping the sender has the same effect.
+    Shutdown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reset {
+    /// Restarts the program from its entry point with clear registers, timers, stack and
+    /// display, leaving memory as the program left it.
+    Soft,
+    /// A soft reset that also reloads memory with the font and the original ROM.
+    Hard,
+}
+
+/// What a run loop should do after handling controls at a frame boundary.
+#[derive(PartialEq, Eq)]
+pub(super) enum Flow {
+    Continue,
+    /// The machine was reset, so the loop should start again from scratch.
+    Restart,
+    Shutdown,
+}
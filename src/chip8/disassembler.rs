@@ -0,0 +1,171 @@
+use super::{platform::Platform, Instruction};
+
+/// One decoded instruction of a program listing.
+pub struct Line {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+    pub text: String,
+}
+
+/// Decodes every instruction in `program` in order, without following jumps, so data mixed in
+/// with code is shown as instructions too.
+pub fn disassemble(program: &[u8], platform: Platform) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    while offset < program.len() {
+        let address = platform.program_start() + offset as u16;
+        if offset + 1 == program.len() {
+            lines.push(Line {
+                address,
+                bytes: vec![program[offset]],
+                text: format!("db {:#04x}", program[offset]),
+            });
+            break;
+        }
+        let instruction =
+            Instruction::new(u16::from_be_bytes([program[offset], program[offset + 1]]));
+        let mut length = 2;
+        let text = if platform == Platform::MegaChip && instruction.value >> 8 == 0x01 {
+            let low = program.get(offset + 2..offset + 4).unwrap_or(&[0, 0]);
+            length = 4;
+            format!(
+                "ldhi i, {:#08x}",
+                u32::from_be_bytes([0, instruction.nn(), low[0], low[1]])
+            )
+        } else {
+            mnemonic(&instruction, platform)
+        };
+        let end = (offset + length).min(program.len());
+        lines.push(Line {
+            address,
+            bytes: program[offset..end].to_vec(),
+            text,
+        });
+        offset = end;
+    }
+    lines
+}
+
+fn mnemonic(instruction: &Instruction, platform: Platform) -> String {
+    let x = instruction.x();
+    let y = instruction.y();
+    let n = instruction.n();
+    let nn = instruction.nn();
+    let nnn = instruction.nnn();
+    match instruction.first() {
+        0x0 if nnn == 0x0E0 => "cls".to_string(),
+        0x0 if nnn == 0x0EE => "ret".to_string(),
+        0x0 if nnn == 0x230 && platform == Platform::Chip8Hires => "hires cls".to_string(),
+        0x0 if platform == Platform::MegaChip => megachip_mnemonic(instruction),
+        0x0 => format!("sys {:#05x}", nnn),
+        0x1 => format!("jp {:#05x}", nnn),
+        0x2 => format!("call {:#05x}", nnn),
+        0x3 => format!("se v{:x}, {:#04x}", x, nn),
+        0x4 => format!("sne v{:x}, {:#04x}", x, nn),
+        0x5 if n == 0x0 => format!("se v{:x}, v{:x}", x, y),
+        0x6 => format!("ld v{:x}, {:#04x}", x, nn),
+        0x7 => format!("add v{:x}, {:#04x}", x, nn),
+        0x8 => match n {
+            0x0 => format!("ld v{:x}, v{:x}", x, y),
+            0x1 => format!("or v{:x}, v{:x}", x, y),
+            0x2 => format!("and v{:x}, v{:x}", x, y),
+            0x3 => format!("xor v{:x}, v{:x}", x, y),
+            0x4 => format!("add v{:x}, v{:x}", x, y),
+            0x5 => format!("sub v{:x}, v{:x}", x, y),
+            0x6 => format!("shr v{:x}, v{:x}", x, y),
+            0x7 => format!("subn v{:x}, v{:x}", x, y),
+            0xE => format!("shl v{:x}, v{:x}", x, y),
+            _ => data(instruction),
+        },
+        0x9 if n == 0x0 => format!("sne v{:x}, v{:x}", x, y),
+        0xA => format!("ld i, {:#05x}", nnn),
+        0xB => format!("jp v0, {:#05x}", nnn),
+        0xC => format!("rnd v{:x}, {:#04x}", x, nn),
+        0xD => format!("drw v{:x}, v{:x}, {}", x, y, n),
+        0xE if nn == 0x9E => format!("skp v{:x}", x),
+        0xE if nn == 0xA1 => format!("sknp v{:x}", x),
+        0xF => match nn {
+            0x02 if x == 0x0 => "audio".to_string(),
+            0x07 => format!("ld v{:x}, dt", x),
+            0x0A => format!("ld v{:x}, k", x),
+            0x15 => format!("ld dt, v{:x}", x),
+            0x18 => format!("ld st, v{:x}", x),
+            0x1E => format!("add i, v{:x}", x),
+            0x29 => format!("ld f, v{:x}", x),
+            0x33 => format!("ld b, v{:x}", x),
+            0x3A => format!("pitch v{:x}", x),
+            0x55 => format!("ld [i], v{:x}", x),
+            0x65 => format!("ld v{:x}, [i]", x),
+            _ => data(instruction),
+        },
+        _ => data(instruction),
+    }
+}
+
+fn megachip_mnemonic(instruction: &Instruction) -> String {
+    let n = instruction.n();
+    let nn = instruction.nn();
+    match (instruction.x(), instruction.y()) {
+        (0x0, 0x1) if n == 0x0 => "megaoff".to_string(),
+        (0x0, 0x1) if n == 0x1 => "megaon".to_string(),
+        (0x2, _) => format!("ldpal {}", nn),
+        (0x3, _) => format!("sprw {}", nn),
+        (0x4, _) => format!("sprh {}", nn),
+        (0x5, _) => format!("alpha {:#04x}", nn),
+        (0x6, 0x0) => format!("digisnd {}", n),
+        (0x7, 0x0) if n == 0x0 => "stopsnd".to_string(),
+        (0x8, 0x0) => format!("bmode {}", n),
+        (0x9, _) => format!("ccol {:#04x}", nn),
+        _ => data(instruction),
+    }
+}
+
+fn data(instruction: &Instruction) -> String {
+    format!("dw {}", instruction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(program: &[u8], platform: Platform) -> Vec<String> {
+        disassemble(program, platform)
+            .into_iter()
+            .map(|line| line.text)
+            .collect()
+    }
+
+    #[test]
+    fn decodes_megachip_extensions_by_their_second_nibble() {
+        let program = [
+            0x00, 0x11, 0x01, 0x12, 0x34, 0x56, 0x02, 0x03, 0x03, 0x10, 0x04, 0x08, 0x05, 0x80,
+            0x06, 0x01, 0x07, 0x00, 0x08, 0x02, 0x09, 0x01, 0x00, 0x10, 0x00, 0x13,
+        ];
+        assert_eq!(
+            texts(&program, Platform::MegaChip),
+            [
+                "megaon",
+                "ldhi i, 0x123456",
+                "ldpal 3",
+                "sprw 16",
+                "sprh 8",
+                "alpha 0x80",
+                "digisnd 1",
+                "stopsnd",
+                "bmode 2",
+                "ccol 0x01",
+                "megaoff",
+                "dw 0x0013",
+            ]
+        );
+    }
+
+    #[test]
+    fn decodes_0nnn_as_machine_code_calls_on_other_platforms() {
+        let program = [0x02, 0x03, 0x00, 0x11, 0x00, 0xE0];
+        assert_eq!(
+            texts(&program, Platform::Chip8),
+            ["sys 0x203", "sys 0x011", "cls"]
+        );
+    }
+}
@@ -1,52 +1,388 @@
-use std::fmt::Display;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
 
-use crossbeam_channel::Sender;
+use crossbeam_channel::{bounded, unbounded, Receiver, Sender, TrySendError};
 
+/// How the values in the framebuffer are to be turned into colours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// Each pixel is a palette index of the planes it is lit in: 0 for none, 1 for the first, 2
+    /// for the second and 3 for both. Only the first plane is drawn to so far.
+    Indexed,
+    /// Each pixel is a 32-bit ARGB colour, as drawn by MEGA-CHIP sprites.
+    Argb,
+}
+
+/// The widest monochrome display a row can hold.
+const ROW_BITS: usize = u128::BITS as usize;
+
+/// The emulated display.
+///
+/// Monochrome pixels are packed one bit per pixel into a `u128` per row, with the leftmost pixel
+/// in the most significant bit, so a sprite row is drawn with a shift and an XOR and collisions
+/// found with an AND. MEGA-CHIP colour pixels are kept separately, one ARGB value each.
 pub struct Chip8Display {
-    buffer: Box<[bool]>,
-    sender: Sender<DisplayInstruction>,
+    width: usize,
+    height: usize,
+    format: PixelFormat,
+    rows: Vec<u128>,
+    /// The pixels in [`PixelFormat::Argb`], empty otherwise.
+    colours: Box<[u32]>,
+    sender: FrameSender,
 }
 
-impl Display for Chip8Display {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for y in 0..32 {
-            for x in 0..64 {
-                let value = self.buffer[x + y * 64];
-                let icon = if value { "◽" } else { "◾" };
-                write!(f, "{}", icon)?;
-            }
-            writeln!(f)?;
+/// A complete picture of the display as it was at the end of an emulated frame.
+#[derive(Clone)]
+pub struct Frame {
+    /// The emulated frame this picture was taken at, counting from 1.
+    pub number: u64,
+    pub width: usize,
+    pub height: usize,
+    pub format: PixelFormat,
+    pub pixels: Box<[u32]>,
+}
+
+/// Creates the slot that frames are handed to the frontend through. It holds only the latest
+/// frame, so a slow frontend skips frames instead of falling behind, and publishing never
+/// waits for the frontend. While recording, every frame is also queued so none are missed.
+pub fn frame_slot() -> (FrameSender, FrameReceiver) {
+    let (sender, receiver) = bounded(1);
+    let (recorded_sender, recorded) = unbounded();
+    let recording = Arc::new(AtomicBool::new(false));
+    let sender = FrameSender {
+        sender,
+        stale: receiver.clone(),
+        recording: recording.clone(),
+        recorded: recorded_sender,
+    };
+    let receiver = FrameReceiver {
+        receiver,
+        recording,
+        recorded,
+    };
+    (sender, receiver)
+}
+
+pub struct FrameSender {
+    sender: Sender<Frame>,
+    /// Used to take back a frame the frontend has not picked up to make room for a newer one.
+    stale: Receiver<Frame>,
+    recording: Arc<AtomicBool>,
+    recorded: Sender<Frame>,
+}
+
+impl FrameSender {
+    fn publish(&self, frame: Frame) {
+        if self.recording.load(Ordering::Relaxed) {
+            let _ = self.recorded.send(frame.clone());
+        }
+        if let Err(TrySendError::Full(frame)) = self.sender.try_send(frame) {
+            let _ = self.stale.try_recv();
+            // The frontend may have taken the stale frame first, which leaves room all the same.
+            let _ = self.sender.try_send(frame);
         }
-        Ok(())
     }
 }
 
-pub enum DisplayInstruction {
-    Set { value: bool, index: usize },
-    Clear,
+pub struct FrameReceiver {
+    receiver: Receiver<Frame>,
+    recording: Arc<AtomicBool>,
+    recorded: Receiver<Frame>,
+}
+
+impl FrameReceiver {
+    /// Takes the newest frame published since the last call, if there is one.
+    pub fn latest(&self) -> Option<Frame> {
+        self.receiver.try_recv().ok()
+    }
+
+    /// Starts or stops queueing every frame published for [`FrameReceiver::recorded`].
+    pub fn set_recording(&self, recording: bool) {
+        self.recording.store(recording, Ordering::Relaxed);
+        if !recording {
+            while self.recorded.try_recv().is_ok() {}
+        }
+    }
+
+    /// Takes every frame published while recording since the last call, oldest first.
+    pub fn recorded(&self) -> Vec<Frame> {
+        self.recorded.try_iter().collect()
+    }
 }
 
 impl Chip8Display {
-    pub fn new(sender: Sender<DisplayInstruction>) -> Self {
-        let buffer = vec![false; 2048].into_boxed_slice();
-        Self { buffer, sender }
+    pub fn new(sender: FrameSender, width: usize, height: usize) -> Self {
+        let mut display = Self {
+            width: 0,
+            height: 0,
+            format: PixelFormat::Indexed,
+            rows: Vec::new(),
+            colours: Box::new([]),
+            sender,
+        };
+        display.resize(width, height, PixelFormat::Indexed);
+        display
+    }
+
+    /// Hands the display to the frontend as frame `number`. Every frame is published, changed
+    /// or not, so the frontend's flicker reduction sees each one go by.
+    pub fn publish(&mut self, number: u64) {
+        self.sender.publish(self.frame(number));
+    }
+
+    /// A picture of the display as it is now, labelled as frame `number`.
+    pub fn frame(&self, number: u64) -> Frame {
+        let pixels = match self.format {
+            PixelFormat::Indexed => (0..self.height)
+                .flat_map(|y| (0..self.width).map(move |x| (y, x)))
+                .map(|(y, x)| self.is_lit(x, y) as u32)
+                .collect(),
+            PixelFormat::Argb => self.colours.clone(),
+        };
+        Frame {
+            number,
+            width: self.width,
+            height: self.height,
+            format: self.format,
+            pixels,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn resize(&mut self, width: usize, height: usize, format: PixelFormat) {
+        self.width = width;
+        self.height = height;
+        self.format = format;
+        match format {
+            PixelFormat::Indexed => {
+                assert!(
+                    width <= ROW_BITS,
+                    "monochrome displays are at most 128 wide"
+                );
+                self.rows = vec![0; height];
+                self.colours = Box::new([]);
+            }
+            PixelFormat::Argb => {
+                self.rows = Vec::new();
+                self.colours = vec![0; width * height].into_boxed_slice();
+            }
+        }
+    }
+
+    fn is_lit(&self, x: usize, y: usize) -> bool {
+        match self.format {
+            PixelFormat::Indexed => self.rows[y] & (1 << (ROW_BITS - 1 - x)) != 0,
+            PixelFormat::Argb => self.colours[x + y * self.width] != 0,
+        }
+    }
+
+    /// The bits of a row that fall on the display.
+    fn row_mask(&self) -> u128 {
+        !0 << (ROW_BITS - self.width)
+    }
+
+    /// XORs an 8-pixel sprite row onto row `y` with its left edge at `x`, clipping whatever
+    /// falls off the right edge. Returns whether any lit pixel was turned off.
+    pub fn draw_sprite_row(&mut self, x: usize, y: usize, sprite: u8) -> bool {
+        if sprite == 0 {
+            return false;
+        }
+        if self.format == PixelFormat::Argb {
+            return self.draw_sprite_row_argb(x, y, sprite);
+        }
+        let bits = ((sprite as u128) << (ROW_BITS - 8)) >> x & self.row_mask();
+        let row = &mut self.rows[y];
+        let collided = *row & bits != 0;
+        *row ^= bits;
+        collided
     }
 
-    pub fn set(&mut self, x: usize, y: usize) -> bool {
-        let index = x + y * 64;
-        let existing = self.buffer[index];
-        self.buffer[index] = !existing;
-        self.sender
-            .send(DisplayInstruction::Set {
-                value: !existing,
-                index,
-            })
-            .unwrap();
-        existing
+    /// Monochrome sprites drawn over MEGA-CHIP colour pixels toggle them between black and
+    /// white.
+    fn draw_sprite_row_argb(&mut self, x: usize, y: usize, sprite: u8) -> bool {
+        let mut collided = false;
+        for offset in 0..8 {
+            let index = x + offset;
+            if index >= self.width {
+                break;
+            }
+            if sprite & (0x80 >> offset) != 0 {
+                let pixel = &mut self.colours[index + y * self.width];
+                collided |= *pixel != 0;
+                *pixel = if *pixel == 0 { 0xFFFF_FFFF } else { 0 };
+            }
+        }
+        collided
+    }
+
+    pub fn get_colour(&self, x: usize, y: usize) -> u32 {
+        self.colours[x + y * self.width]
+    }
+
+    pub fn set_colour(&mut self, x: usize, y: usize, colour: u32) {
+        self.colours[x + y * self.width] = colour;
+    }
+
+    pub fn page_len(&self) -> usize {
+        self.width * self.height / 8
+    }
+
+    /// Packs the display into bytes the way the COSMAC VIP stores its display page. Colour
+    /// pixels are stored as lit or unlit.
+    pub fn store_page(&self, page: &mut [u8]) {
+        let bytes_per_row = self.width / 8;
+        for (y, row) in page.chunks_mut(bytes_per_row).enumerate() {
+            if self.format == PixelFormat::Argb {
+                for (column, byte) in row.iter_mut().enumerate() {
+                    *byte = (0..8).fold(0, |byte, bit| {
+                        byte << 1 | self.is_lit(column * 8 + bit, y) as u8
+                    });
+                }
+                continue;
+            }
+            let bytes = self.rows[y].to_be_bytes();
+            row.copy_from_slice(&bytes[..bytes_per_row]);
+        }
+    }
+
+    /// Unpacks a display page stored by [`Self::store_page`], lighting colour pixels white.
+    pub fn load_page(&mut self, page: &[u8]) {
+        let bytes_per_row = self.width / 8;
+        for (y, row) in page.chunks(bytes_per_row).enumerate() {
+            if self.format == PixelFormat::Argb {
+                for x in 0..self.width {
+                    let lit = row[x / 8] & (0x80 >> (x % 8)) != 0;
+                    if lit != self.is_lit(x, y) {
+                        self.set_colour(x, y, if lit { 0xFFFF_FFFF } else { 0 });
+                    }
+                }
+                continue;
+            }
+            let mut bytes = [0; ROW_BITS / 8];
+            bytes[..bytes_per_row].copy_from_slice(row);
+            self.rows[y] = u128::from_be_bytes(bytes);
+        }
     }
 
     pub fn clear(&mut self) {
-        self.buffer.fill(false);
-        self.sender.send(DisplayInstruction::Clear).unwrap();
+        self.rows.fill(0);
+        self.colours.fill(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn display(width: usize, height: usize, format: PixelFormat) -> Chip8Display {
+        let (sender, _) = frame_slot();
+        let mut display = Chip8Display::new(sender, 64, 32);
+        display.resize(width, height, format);
+        display
+    }
+
+    fn lit(display: &Chip8Display, y: usize) -> Vec<usize> {
+        (0..display.width)
+            .filter(|&x| display.is_lit(x, y))
+            .collect()
+    }
+
+    #[test]
+    fn clips_sprite_rows_at_the_right_edge() {
+        for x in 60..64 {
+            let mut display = display(64, 32, PixelFormat::Indexed);
+            assert!(!display.draw_sprite_row(x, 5, 0xFF));
+            assert_eq!(lit(&display, 5), (x..64).collect::<Vec<_>>());
+            assert!(lit(&display, 6).is_empty());
+        }
+    }
+
+    #[test]
+    fn draws_hires_rows_64_pixels_wide() {
+        let mut display = display(64, 64, PixelFormat::Indexed);
+        display.draw_sprite_row(0, 63, 0x81);
+        display.draw_sprite_row(60, 63, 0xFF);
+        assert_eq!(lit(&display, 63), [0, 7, 60, 61, 62, 63]);
+    }
+
+    #[test]
+    fn reports_collisions_when_lit_pixels_are_turned_off() {
+        let mut display = display(64, 32, PixelFormat::Indexed);
+        assert!(!display.draw_sprite_row(0, 0, 0xF0));
+        assert!(!display.draw_sprite_row(4, 0, 0xF0));
+        assert!(display.draw_sprite_row(2, 0, 0xC0));
+        assert_eq!(lit(&display, 0), [0, 1, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn ignores_clipped_bits_for_collisions() {
+        let mut display = display(64, 32, PixelFormat::Indexed);
+        display.draw_sprite_row(0, 0, 0xFF);
+        display.draw_sprite_row(56, 0, 0xFF);
+        // Only the bits past the right edge overlap the pixels at the left.
+        assert!(!display.draw_sprite_row(62, 0, 0x3F));
+        assert!(display.draw_sprite_row(62, 0, 0x40));
+        assert_eq!(
+            lit(&display, 0),
+            [0, 1, 2, 3, 4, 5, 6, 7, 56, 57, 58, 59, 60, 61, 62]
+        );
+    }
+
+    #[test]
+    fn stores_and_loads_monochrome_pages() {
+        let mut display = display(64, 32, PixelFormat::Indexed);
+        display.draw_sprite_row(4, 1, 0b1010_0000);
+        let mut page = vec![0; display.page_len()];
+        display.store_page(&mut page);
+        assert_eq!(page[8], 0b0000_1010);
+        assert_eq!(page.iter().filter(|&&byte| byte != 0).count(), 1);
+
+        page[8] = 0;
+        page[255] = 0x01;
+        display.load_page(&page);
+        assert!(!display.is_lit(4, 1));
+        assert!(display.is_lit(63, 31));
+    }
+
+    #[test]
+    fn stores_and_loads_colour_pages_as_lit_pixels() {
+        let mut display = display(256, 192, PixelFormat::Argb);
+        display.set_colour(9, 2, 0xFF12_3456);
+        let mut page = vec![0; display.page_len()];
+        display.store_page(&mut page);
+        assert_eq!(page[2 * 32 + 1], 0b0100_0000);
+
+        page[2 * 32 + 1] = 0b1000_0000;
+        display.load_page(&page);
+        assert_eq!(display.get_colour(8, 2), 0xFFFF_FFFF);
+        assert_eq!(display.get_colour(9, 2), 0);
+    }
+
+    #[test]
+    fn queues_every_frame_while_recording() {
+        let (sender, receiver) = frame_slot();
+        let mut display = Chip8Display::new(sender, 64, 32);
+        display.publish(1);
+        receiver.set_recording(true);
+        for number in 2..=4 {
+            display.publish(number);
+        }
+        assert_eq!(receiver.latest().map(|frame| frame.number), Some(4));
+        let numbers: Vec<u64> = receiver.recorded().iter().map(|f| f.number).collect();
+        assert_eq!(numbers, [2, 3, 4]);
+
+        display.publish(5);
+        receiver.set_recording(false);
+        display.publish(6);
+        assert!(receiver.recorded().is_empty());
     }
 }
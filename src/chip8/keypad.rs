@@ -1,9 +1,18 @@
+use std::collections::VecDeque;
+
 use crossbeam_channel::Receiver;
 
+/// The state of the hex keypad as the program sees it.
+///
+/// Events from the frontend are queued as they arrive and only applied by [`Keypad::next_frame`],
+/// so the keys a program reads cannot change in the middle of a frame.
 pub struct Keypad {
     receiver: Receiver<Event>,
-    has_stopped: bool,
+    pending: VecDeque<Event>,
     key_states: [bool; 16],
+    /// The frame each key last went down in.
+    pressed_at: [u64; 16],
+    frame: u64,
     last_pressed: LastKeyState,
 }
 
@@ -12,29 +21,64 @@ impl Keypad {
         let key_states = [false; 16];
         Self {
             receiver,
-            has_stopped: false,
+            pending: VecDeque::new(),
             key_states,
+            pressed_at: [0; 16],
+            frame: 0,
             last_pressed: LastKeyState::NotWaiting,
         }
     }
 
-    pub fn process(&mut self) {
-        while let Ok(event) = self.receiver.try_recv() {
+    /// Moves to the next 60 Hz frame, applying the events that have arrived since the last one.
+    ///
+    /// A key released in the same frame it was pressed stays down until the next frame, so even
+    /// the quickest tap is seen by a program that polls once a frame.
+    pub fn next_frame(&mut self) {
+        self.pending.extend(self.receiver.try_iter());
+        self.frame += 1;
+        while let Some(event) = self.pending.pop_front() {
             match event {
-                Event::KeyDown(key) => self.key_states[key as usize] = true,
+                Event::KeyDown(key) => {
+                    if !self.key_states[key as usize] {
+                        log::debug!("Key {:X} down at frame {}", key as u8, self.frame);
+                        self.pressed_at[key as usize] = self.frame;
+                    }
+                    self.key_states[key as usize] = true;
+                }
                 Event::KeyUp(key) => {
-                    self.key_states[key as usize] = false;
-                    self.last_pressed = match self.last_pressed {
-                        LastKeyState::NotWaiting => LastKeyState::NotWaiting,
-                        LastKeyState::Waiting => LastKeyState::Pressed(key as u8),
-                        LastKeyState::Pressed(_) => LastKeyState::Pressed(key as u8),
-                    };
+                    if self.is_new_press(key as usize) {
+                        self.pending.push_front(event);
+                        break;
+                    }
+                    self.release(key);
+                }
+                // Lost focus rather than a key let go of, so a program waiting for a key keeps waiting.
+                Event::ReleaseAll => {
+                    if (0..16).any(|number| self.is_new_press(number)) {
+                        self.pending.push_front(event);
+                        break;
+                    }
+                    log::debug!("All keys up at frame {}", self.frame);
+                    self.key_states = [false; 16];
                 }
-                Event::Stop => self.has_stopped = true,
             }
         }
     }
 
+    fn is_new_press(&self, number: usize) -> bool {
+        self.key_states[number] && self.pressed_at[number] == self.frame
+    }
+
+    fn release(&mut self, key: Key) {
+        log::debug!("Key {:X} up at frame {}", key as u8, self.frame);
+        self.key_states[key as usize] = false;
+        self.last_pressed = match self.last_pressed {
+            LastKeyState::NotWaiting => LastKeyState::NotWaiting,
+            LastKeyState::Waiting => LastKeyState::Pressed(key as u8),
+            LastKeyState::Pressed(_) => LastKeyState::Pressed(key as u8),
+        };
+    }
+
     pub fn is_key_pressed(&self, key_number: u8) -> bool {
         self.key_states[key_number as usize]
     }
@@ -55,14 +99,18 @@ enum LastKeyState {
     Pressed(u8),
 }
 
+/// Input from the frontend. Key events are edge-triggered: one `KeyDown` when a key goes down
+/// and one `KeyUp` when it comes back up, however long it is held.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Event {
     KeyDown(Key),
     KeyUp(Key),
-    Stop,
+    /// Releases every key, for when the frontend loses focus and will miss the key ups.
+    ReleaseAll,
 }
 
 #[repr(u8)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Key {
     Key1 = 0x1,
     Key2 = 0x2,
@@ -81,3 +129,88 @@ pub enum Key {
     KeyC = 0xB,
     KeyV = 0xF,
 }
+
+impl Key {
+    pub const ALL: [Key; 16] = [
+        Key::KeyX,
+        Key::Key1,
+        Key::Key2,
+        Key::Key3,
+        Key::KeyQ,
+        Key::KeyW,
+        Key::KeyE,
+        Key::KeyA,
+        Key::KeyS,
+        Key::KeyD,
+        Key::KeyZ,
+        Key::KeyC,
+        Key::Key4,
+        Key::KeyR,
+        Key::KeyF,
+        Key::KeyV,
+    ];
+
+    /// The key for a hex digit 0-F.
+    pub fn from_number(number: u8) -> Option<Self> {
+        Self::ALL.get(number as usize).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossbeam_channel::unbounded;
+
+    use super::*;
+
+    #[test]
+    fn holds_a_tap_down_for_a_frame() {
+        let (sender, receiver) = unbounded();
+        let mut keypad = Keypad::new(receiver);
+        sender.send(Event::KeyDown(Key::KeyW)).unwrap();
+        sender.send(Event::KeyUp(Key::KeyW)).unwrap();
+        keypad.next_frame();
+        assert!(keypad.is_key_pressed(5));
+        assert_eq!(keypad.pending, [Event::KeyUp(Key::KeyW)]);
+        keypad.next_frame();
+        assert!(!keypad.is_key_pressed(5));
+        assert!(keypad.pending.is_empty());
+    }
+
+    #[test]
+    fn releases_every_key_on_release_all() {
+        let (sender, receiver) = unbounded();
+        let mut keypad = Keypad::new(receiver);
+        sender.send(Event::KeyDown(Key::Key1)).unwrap();
+        sender.send(Event::KeyDown(Key::KeyV)).unwrap();
+        keypad.next_frame();
+        sender.send(Event::ReleaseAll).unwrap();
+        keypad.next_frame();
+        assert!((0..16).all(|key| !keypad.is_key_pressed(key)));
+    }
+
+    #[test]
+    fn reports_the_key_released_while_waiting() {
+        let (sender, receiver) = unbounded();
+        let mut keypad = Keypad::new(receiver);
+        assert_eq!(keypad.last_pressed(), None);
+        sender.send(Event::KeyDown(Key::KeyA)).unwrap();
+        keypad.next_frame();
+        assert_eq!(keypad.last_pressed(), None);
+        sender.send(Event::KeyUp(Key::KeyA)).unwrap();
+        keypad.next_frame();
+        assert_eq!(keypad.last_pressed(), Some(7));
+    }
+
+    #[test]
+    fn release_all_does_not_end_a_wait_for_a_key() {
+        let (sender, receiver) = unbounded();
+        let mut keypad = Keypad::new(receiver);
+        assert_eq!(keypad.last_pressed(), None);
+        sender.send(Event::KeyDown(Key::KeyA)).unwrap();
+        keypad.next_frame();
+        sender.send(Event::ReleaseAll).unwrap();
+        keypad.next_frame();
+        assert!(!keypad.is_key_pressed(7));
+        assert_eq!(keypad.last_pressed(), None);
+    }
+}
@@ -0,0 +1,75 @@
+/// State of the MEGA-CHIP 8 extensions: the 256-colour palette, sprite size and blending.
+/// Samples are played by the [`super::audio::Beeper`].
+pub struct MegaChip {
+    pub enabled: bool,
+    pub palette: [u32; 256],
+    pub sprite_width: usize,
+    pub sprite_height: usize,
+    pub alpha: u8,
+    pub blend_mode: BlendMode,
+    pub collision_colour: u8,
+}
+
+pub const MEGACHIP_WIDTH: usize = 256;
+pub const MEGACHIP_HEIGHT: usize = 192;
+
+impl MegaChip {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            palette: [0; 256],
+            sprite_width: 8,
+            sprite_height: 8,
+            alpha: 0xFF,
+            blend_mode: BlendMode::Normal,
+            collision_colour: 0,
+        }
+    }
+
+    /// Mixes an opaque sprite colour into the ARGB colour already on screen.
+    pub fn blend(&self, source: u32, destination: u32) -> u32 {
+        let channels = |colour: u32| colour.to_be_bytes().map(|channel| channel as u32);
+        let [_, sr, sg, sb] = channels(source);
+        let [_, dr, dg, db] = channels(destination);
+        let mix = |s: u32, d: u32| match self.blend_mode {
+            BlendMode::Normal => s,
+            BlendMode::Alpha25 => (s + 3 * d) / 4,
+            BlendMode::Alpha50 => (s + d) / 2,
+            BlendMode::Alpha75 => (3 * s + d) / 4,
+            BlendMode::Add => (s + d).min(0xFF),
+            BlendMode::Multiply => s * d / 0xFF,
+        };
+        let alpha = self.alpha as u32;
+        let fade = |s: u32, d: u32| (mix(s, d) * alpha + d * (0xFF - alpha)) / 0xFF;
+        u32::from_be_bytes([
+            0xFF,
+            fade(sr, dr) as u8,
+            fade(sg, dg) as u8,
+            fade(sb, db) as u8,
+        ])
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    Normal,
+    Alpha25,
+    Alpha50,
+    Alpha75,
+    Add,
+    Multiply,
+}
+
+impl BlendMode {
+    pub fn from_number(number: u8) -> Option<Self> {
+        match number {
+            0 => Some(BlendMode::Normal),
+            1 => Some(BlendMode::Alpha25),
+            2 => Some(BlendMode::Alpha50),
+            3 => Some(BlendMode::Alpha75),
+            4 => Some(BlendMode::Add),
+            5 => Some(BlendMode::Multiply),
+            _ => None,
+        }
+    }
+}
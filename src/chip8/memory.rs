@@ -2,9 +2,10 @@ pub struct Memory {
     buffer: Box<[u8]>,
 }
 
-pub const PROGRAM_START: u16 = 0x200;
+/// Where the hex digit sprites are kept, five bytes each from 0 to F.
+pub const FONT_ADDRESS: u32 = 0x50;
 
-static FONT: &'static [u8] = &[
+static FONT: &[u8] = &[
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
     0x20, 0x60, 0x20, 0x20, 0x70, // 1
     0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
@@ -24,27 +25,76 @@ static FONT: &'static [u8] = &[
 ];
 
 impl Memory {
-    pub fn new(program: &[u8]) -> Self {
-        let mut buffer = vec![0u8; 4096].into_boxed_slice();
-        let start = PROGRAM_START as usize;
+    /// Lays out memory with the font and the program at `program_start`, failing if the
+    /// program doesn't fit.
+    pub fn new(program: &[u8], program_start: u16, size: usize) -> Result<Self, String> {
+        let start = program_start as usize;
+        let space = size - start;
+        if program.len() > space {
+            return Err(format!(
+                "the program is {} bytes, but only {} fit in memory from {:#05x}",
+                program.len(),
+                space,
+                program_start
+            ));
+        }
+        let mut buffer = vec![0u8; size].into_boxed_slice();
         buffer[start..(program.len() + start)].copy_from_slice(program);
 
-        buffer[0x50..=0x09F].copy_from_slice(FONT);
+        let font = FONT_ADDRESS as usize;
+        buffer[font..font + FONT.len()].copy_from_slice(FONT);
 
-        Self { buffer }
+        Ok(Self { buffer })
     }
 
-    pub fn get_u8(&self, address: u16) -> u8 {
+    pub fn get_u8(&self, address: u32) -> u8 {
         self.buffer[address as usize]
     }
 
-    pub fn get_u16(&self, address: u16) -> u16 {
+    pub fn bytes(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    pub fn size(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn get_u16(&self, address: u32) -> u16 {
         let a = self.buffer[address as usize];
         let b = self.buffer[(address + 1) as usize];
         u16::from_be_bytes([a, b])
     }
 
-    pub fn set_u8(&mut self, address: u16, value: u8) {
+    pub fn set_u8(&mut self, address: u32, value: u8) {
         self.buffer[address as usize] = value;
     }
+
+    pub fn load(&mut self, address: u32, data: &[u8]) {
+        let start = address as usize;
+        self.buffer[start..(data.len() + start)].copy_from_slice(data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_a_program_that_fills_memory() {
+        let program = vec![0xAB; 0x1000 - 0x200];
+        let memory = Memory::new(&program, 0x200, 0x1000).unwrap();
+        assert_eq!(memory.get_u8(0x200), 0xAB);
+        assert_eq!(memory.get_u8(0xFFF), 0xAB);
+        assert_eq!(memory.get_u8(0x50), 0xF0);
+    }
+
+    #[test]
+    fn rejects_a_program_too_big_for_memory() {
+        let program = vec![0; 0x1000 - 0x600 + 1];
+        let error = Memory::new(&program, 0x600, 0x1000).err().unwrap();
+        assert_eq!(
+            error,
+            "the program is 2561 bytes, but only 2560 fit in memory from 0x600"
+        );
+    }
 }
@@ -1,88 +1,513 @@
-use std::{fmt::Display, time::Instant};
+use std::{
+    any::Any,
+    fmt::Display,
+    panic::{self, AssertUnwindSafe},
+    time::Instant,
+};
 
-use crossbeam_channel::{Receiver, Sender};
-use rand::{rngs::ThreadRng, Rng};
+use crossbeam_channel::{Receiver, Sender, TryRecvError};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
 use self::{
-    display::{Chip8Display, DisplayInstruction},
+    audio::Beeper,
+    cdp1802::Cdp1802,
+    control::{Control, Flow, Reset},
+    display::{Chip8Display, FrameSender, PixelFormat},
     keypad::{Event, Keypad},
-    memory::{Memory, PROGRAM_START},
+    megachip::{BlendMode, MegaChip, MEGACHIP_HEIGHT, MEGACHIP_WIDTH},
+    memory::{Memory, FONT_ADDRESS},
+    platform::{Platform, HIRES_ENTRY_POINT},
     registers::Registers,
-    settings::Settings,
+    settings::{Backend, Settings, Timing},
     stack::Stack,
+    status::{Fault, State, Status},
     timer::{Timer, TIMER_DECREMENT},
+    timing::{sleep_until_frame, VipClock, VIP_FETCH_CYCLES},
+    vip::{CosmacVip, VIP_DISPLAY, VIP_INTERPRETER_LOOP, VIP_REGISTERS, VIP_STACK},
 };
 
+pub mod analysis;
+pub mod audio;
+mod cdp1802;
+pub mod control;
+pub mod disassembler;
 pub mod display;
 pub mod keypad;
+mod megachip;
 mod memory;
+pub mod platform;
 mod registers;
 pub mod settings;
 mod stack;
+pub mod status;
+#[cfg(test)]
+mod tests;
 mod timer;
+mod timing;
+mod vip;
 
 pub struct Chip8 {
     settings: Settings,
+    program: Vec<u8>,
     memory: Memory,
     display: Chip8Display,
     stack: Stack,
     registers: Registers,
     program_counter: u16,
-    index_register: u16,
-    rng: ThreadRng,
+    index_register: u32,
+    rng: StdRng,
     keypad: Keypad,
     delay_timer: Timer,
     sound_timer: Timer,
+    megachip: MegaChip,
+    controls: Receiver<Control>,
+    status_sender: Sender<Status>,
+    paused: bool,
+    faulted: bool,
+    /// Where the instruction being executed was fetched from, for reporting faults.
+    instruction_address: u16,
+    instructions: u64,
+    frames: u64,
+    stats: (Instant, u64),
+    sound_on: bool,
+    beeper: Beeper,
+    halted: bool,
 }
 
 impl Chip8 {
     pub fn new(
         settings: Settings,
         program: &[u8],
-        sender: Sender<DisplayInstruction>,
+        sender: FrameSender,
         receiver: Receiver<Event>,
-    ) -> Self {
-        let memory = Memory::new(program);
-        let display = Chip8Display::new(sender);
+        controls: Receiver<Control>,
+        status_sender: Sender<Status>,
+    ) -> Result<Self, String> {
+        let program_start = settings.platform.program_start();
+        let memory = Memory::new(program, program_start, settings.platform.memory_size())?;
+        let (width, height) = settings.platform.display_size();
+        let display = Chip8Display::new(sender, width, height);
         let stack = Stack::new();
         let registers = Registers::new();
-        let rng = rand::thread_rng();
+        let rng = StdRng::from_entropy();
         let keypad = Keypad::new(receiver);
         let delay_timer = Timer::new();
         let sound_timer = Timer::new();
 
-        Self {
+        Ok(Self {
             settings,
+            program: program.to_vec(),
             memory,
             display,
             stack,
             registers,
-            program_counter: PROGRAM_START,
+            program_counter: program_start,
             index_register: 0,
             rng,
             keypad,
             delay_timer,
             sound_timer,
-        }
+            megachip: MegaChip::new(),
+            controls,
+            status_sender,
+            paused: false,
+            faulted: false,
+            instruction_address: program_start,
+            instructions: 0,
+            frames: 0,
+            stats: (Instant::now(), 0),
+            sound_on: false,
+            beeper: Beeper::silent(),
+            halted: false,
+        })
+    }
+
+    /// Plays sound through `beeper` instead of discarding it.
+    pub fn set_beeper(&mut self, beeper: Beeper) {
+        self.beeper = beeper;
+    }
+
+    /// Plays a frame of sound, beeping while the sound timer is running. Run loops do this
+    /// at the end of each frame; headless runs call it themselves.
+    pub fn play_sound(&mut self) {
+        self.beeper.play_frame(self.sound_timer.get_value() > 0);
     }
 
+    /// Runs until the frontend asks the emulator to shut down or goes away.
+    ///
+    /// A panic while running, such as an unknown instruction, is reported as a fault and the
+    /// emulator waits to be reset or given another ROM.
     pub fn run(&mut self) {
+        self.report_rom();
+        self.report(Status::State(State::Running));
+        loop {
+            let flow = match panic::catch_unwind(AssertUnwindSafe(|| self.run_backend())) {
+                Ok(flow) => flow,
+                Err(payload) => {
+                    self.report(Status::Fault(self.fault(payload)));
+                    self.faulted = true;
+                    self.report(Status::State(State::Faulted));
+                    Flow::Restart
+                }
+            };
+            if flow == Flow::Shutdown {
+                break;
+            }
+        }
+        self.report(Status::State(State::Stopped));
+    }
+
+    /// Describes the panic that stopped the instruction being executed.
+    fn fault(&self, payload: Box<dyn Any + Send>) -> Fault {
+        let message = payload
+            .downcast_ref::<String>()
+            .cloned()
+            .or_else(|| payload.downcast_ref::<&str>().map(|m| m.to_string()))
+            .unwrap_or_else(|| "the emulator panicked".to_string());
+        let address = self.instruction_address;
+        let instruction = (address as usize + 1 < self.memory.size())
+            .then(|| self.memory.get_u16(address.into()));
+        Fault {
+            message,
+            address,
+            instruction,
+        }
+    }
+
+    fn run_backend(&mut self) -> Flow {
+        if self.faulted {
+            // Only a reset, a new ROM or shutting down gets the emulator out of a fault.
+            return self.handle_controls();
+        }
+        if let Backend::CosmacVip {
+            interpreter,
+            monitor,
+        } = &self.settings.backend
+        {
+            let interpreter = interpreter.clone();
+            let monitor = monitor.clone();
+            self.run_vip_system(&interpreter, monitor)
+        } else {
+            match self.settings.timing {
+                Timing::Unlimited => self.run_unlimited(),
+                Timing::InstructionsPerSecond(rate) => self.run_fixed(rate),
+                Timing::CosmacVip => self.run_cosmac_vip(),
+            }
+        }
+    }
+
+    fn run_unlimited(&mut self) -> Flow {
         let mut last_decremented = Instant::now();
         loop {
             let time = Instant::now();
             if time - last_decremented >= TIMER_DECREMENT {
-                last_decremented = time;
                 self.delay_timer.decrement();
                 self.sound_timer.decrement();
+                self.keypad.next_frame();
+                match self.end_frame() {
+                    Flow::Continue => {}
+                    flow => return flow,
+                }
+                // Taken after handling controls so time spent paused doesn't count.
+                last_decremented = Instant::now();
+            }
+            let instruction = self.fetch();
+            self.execute(instruction);
+        }
+    }
+
+    fn run_fixed(&mut self, instructions_per_second: u32) -> Flow {
+        let instructions_per_frame = (instructions_per_second / 60).max(1);
+        let mut started = Instant::now();
+        let mut frames = 0;
+        loop {
+            self.run_frame(instructions_per_frame);
+            frames += 1;
+            sleep_until_frame(&mut started, frames);
+            match self.end_frame() {
+                Flow::Continue => {}
+                flow => return flow,
+            }
+        }
+    }
+
+    /// Reports what changed over the frame that just ended, then handles controls.
+    fn end_frame(&mut self) -> Flow {
+        self.frames += 1;
+        self.display.publish(self.frames);
+        let (started, instructions) = self.stats;
+        let elapsed = started.elapsed();
+        if elapsed.as_secs() >= 1 {
+            let executed = self.instructions - instructions;
+            self.report(Status::Stats {
+                frames: self.frames,
+                instructions_per_second: (executed as f64 / elapsed.as_secs_f64()) as u64,
+            });
+            self.stats = (Instant::now(), self.instructions);
+        }
+
+        self.play_sound();
+        let sound_on = self.sound_timer.get_value() > 0;
+        if sound_on != self.sound_on {
+            self.sound_on = sound_on;
+            self.report(Status::Sound(sound_on));
+        }
+
+        let address = self.program_counter;
+        let halted = self.memory.get_u16(address.into()) == 0x1000 | address;
+        if halted && !self.halted {
+            self.report(Status::Halted { address });
+        }
+        self.halted = halted;
+
+        self.handle_controls()
+    }
+
+    /// Applies the controls that have arrived since the last frame, blocking while paused or
+    /// faulted until the emulator is resumed, stepped, reset or shut down.
+    fn handle_controls(&mut self) -> Flow {
+        loop {
+            let control = if self.paused || self.faulted {
+                match self.controls.recv() {
+                    Ok(control) => control,
+                    Err(_) => return Flow::Shutdown,
+                }
+            } else {
+                match self.controls.try_recv() {
+                    Ok(control) => control,
+                    Err(TryRecvError::Empty) => return Flow::Continue,
+                    Err(TryRecvError::Disconnected) => return Flow::Shutdown,
+                }
+            };
+            match control {
+                Control::Pause if !self.paused && !self.faulted => {
+                    self.paused = true;
+                    self.report(Status::State(State::Paused));
+                }
+                Control::Resume if self.paused => {
+                    self.paused = false;
+                    self.report(Status::State(State::Running));
+                }
+                Control::Pause | Control::Resume => {}
+                Control::Step if self.paused => return Flow::Continue,
+                Control::Step => {}
+                Control::Reset(reset) => {
+                    self.reset(reset);
+                    return Flow::Restart;
+                }
+                Control::Load(program) => {
+                    let platform = self.settings.platform;
+                    let program_start = platform.program_start();
+                    match Memory::new(&program, program_start, platform.memory_size()) {
+                        Ok(_) => {
+                            self.program = program;
+                            self.reset(Reset::Hard);
+                            self.report_rom();
+                            return Flow::Restart;
+                        }
+                        // The running program carries on.
+                        Err(message) => self.report(Status::Fault(Fault {
+                            message,
+                            address: program_start,
+                            instruction: None,
+                        })),
+                    }
+                }
+                Control::Shutdown => return Flow::Shutdown,
             }
-            self.keypad.process();
+        }
+    }
+
+    fn reset(&mut self, reset: Reset) {
+        let platform = self.settings.platform;
+        let program_start = platform.program_start();
+        if reset == Reset::Hard {
+            self.memory = Memory::new(&self.program, program_start, platform.memory_size())
+                .expect("the program fitted when it was loaded");
+        }
+        let (width, height) = platform.display_size();
+        self.display.resize(width, height, PixelFormat::Indexed);
+        self.stack = Stack::new();
+        self.registers = Registers::new();
+        self.program_counter = program_start;
+        self.index_register = 0;
+        self.delay_timer = Timer::new();
+        self.sound_timer = Timer::new();
+        self.megachip = MegaChip::new();
+        self.beeper.reset();
+        self.halted = false;
+        if self.faulted {
+            self.faulted = false;
+            let state = if self.paused {
+                State::Paused
+            } else {
+                State::Running
+            };
+            self.report(Status::State(state));
+        }
+    }
+
+    fn report_rom(&self) {
+        self.report(Status::Rom {
+            size: self.program.len(),
+            platform: self.settings.platform,
+        });
+    }
+
+    fn report(&self, status: Status) {
+        // The frontend may already have gone, which is fine.
+        let _ = self.status_sender.send(status);
+    }
+
+    /// Runs one 60 Hz frame of `instructions` instructions, then decrements the timers.
+    pub fn run_frame(&mut self, instructions: u32) {
+        self.keypad.next_frame();
+        for _ in 0..instructions {
             let instruction = self.fetch();
             self.execute(instruction);
         }
+        self.delay_timer.decrement();
+        self.sound_timer.decrement();
+    }
+
+    /// Runs a frame like [`Self::run_frame`], returning a panic as a fault the way
+    /// [`Self::run`] reports it. The machine is left as it was when the fault happened.
+    pub fn try_run_frame(&mut self, instructions: u32) -> Result<(), Fault> {
+        panic::catch_unwind(AssertUnwindSafe(|| self.run_frame(instructions)))
+            .map_err(|payload| self.fault(payload))
+    }
+
+    /// Makes random numbers repeat from run to run, for headless runs and tests.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    pub fn platform(&self) -> Platform {
+        self.settings.platform
+    }
+
+    pub fn screen(&self) -> &Chip8Display {
+        &self.display
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        self.memory.bytes()
+    }
+
+    pub fn cpu_state(&self) -> CpuState {
+        CpuState {
+            registers: std::array::from_fn(|n| self.registers.get_value(n as u8)),
+            index_register: self.index_register,
+            program_counter: self.program_counter,
+            delay_timer: self.delay_timer.get_value(),
+            sound_timer: self.sound_timer.get_value(),
+            stack: self.stack.values().to_vec(),
+        }
+    }
+
+    fn run_cosmac_vip(&mut self) -> Flow {
+        let mut clock = VipClock::new();
+        loop {
+            let instruction = self.fetch();
+            if instruction.first() == 0xD {
+                // The VIP interpreter waits for the next interrupt before drawing a sprite.
+                clock.wait_for_interrupt();
+                match self.vip_interrupt(&mut clock) {
+                    Flow::Continue => {}
+                    flow => return flow,
+                }
+            }
+            clock.add(VIP_FETCH_CYCLES + self.vip_cycles(&instruction));
+            self.execute(instruction);
+            match self.vip_interrupt(&mut clock) {
+                Flow::Continue => {}
+                flow => return flow,
+            }
+        }
+    }
+
+    fn run_vip_system(&mut self, interpreter: &[u8], monitor: Box<[u8]>) -> Flow {
+        self.memory.load(0, interpreter);
+        let mut vip = CosmacVip::new(monitor);
+        let mut clock = VipClock::new();
+        let mut page = vec![0; self.display.page_len()];
+        loop {
+            self.keypad.next_frame();
+            vip.run_frame(&mut self.memory, &self.keypad, &mut page);
+            self.display.load_page(&page);
+            clock.next_frame();
+            clock.sleep_until_frame();
+            match self.end_frame() {
+                Flow::Continue => {}
+                flow => return flow,
+            }
+        }
+    }
+
+    fn vip_interrupt(&mut self, clock: &mut VipClock) -> Flow {
+        while clock.take_interrupt() {
+            self.delay_timer.decrement();
+            self.sound_timer.decrement();
+            self.keypad.next_frame();
+            clock.sleep_until_frame();
+            match self.end_frame() {
+                Flow::Continue => {}
+                flow => return flow,
+            }
+        }
+        Flow::Continue
+    }
+
+    /// Machine cycles the COSMAC VIP interpreter spends executing an instruction, excluding the
+    /// fetch and decode overhead. Must be called before the instruction is executed.
+    fn vip_cycles(&self, instruction: &Instruction) -> u64 {
+        let x_value = self.registers.get_value(instruction.x());
+        let y_value = self.registers.get_value(instruction.y());
+        let skip = |taken: bool, base: u64| if taken { base + 4 } else { base };
+        match instruction.first() {
+            0x0 if instruction.nnn() == 0x0E0 => 3078,
+            0x0 if instruction.nnn() == 0x0EE => 10,
+            0x1 => 12,
+            0x2 => 26,
+            0x3 => skip(x_value == instruction.nn(), 10),
+            0x4 => skip(x_value != instruction.nn(), 10),
+            0x5 => skip(x_value == y_value, 14),
+            0x6 => 6,
+            0x7 => 10,
+            0x8 if instruction.n() == 0x0 => 12,
+            0x8 => 44,
+            0x9 => skip(x_value != y_value, 14),
+            0xA => 12,
+            0xB => 22,
+            0xC => 36,
+            0xD => {
+                // Sprites that are not byte aligned have every row shifted across two bytes.
+                let row_cycles = if x_value & 0x7 == 0 { 34 } else { 46 };
+                26 + row_cycles * instruction.n() as u64
+            }
+            0xE if instruction.nn() == 0x9E => skip(self.keypad.is_key_pressed(x_value), 14),
+            0xE if instruction.nn() == 0xA1 => skip(!self.keypad.is_key_pressed(x_value), 14),
+            0xF if instruction.nn() == 0x07 => 10,
+            0xF if instruction.nn() == 0x0A => 19,
+            0xF if instruction.nn() == 0x15 => 10,
+            0xF if instruction.nn() == 0x18 => 10,
+            0xF if instruction.nn() == 0x1E => 16,
+            0xF if instruction.nn() == 0x29 => 16,
+            0xF if instruction.nn() == 0x33 => {
+                let digits = x_value / 100 + (x_value / 10) % 10 + x_value % 10;
+                80 + 16 * digits as u64
+            }
+            0xF if instruction.nn() == 0x55 || instruction.nn() == 0x65 => {
+                14 + 14 * (instruction.x() as u64 + 1)
+            }
+            _ => 0,
+        }
     }
 
     fn fetch(&mut self) -> Instruction {
-        let instruction = self.memory.get_u16(self.program_counter);
+        self.instruction_address = self.program_counter;
+        self.instructions += 1;
+        let instruction = self.memory.get_u16(self.program_counter.into());
         self.program_counter += 2;
         Instruction::new(instruction)
     }
@@ -91,9 +516,13 @@ impl Chip8 {
         let first = instruction.first();
         match first {
             0x0 if instruction.nnn() == 0x0E0 => self.clear_display(),
+            0x0 if instruction.nnn() == 0x230 && self.settings.platform == Platform::Chip8Hires => {
+                self.clear_display()
+            }
             0x6 => self.set_value(instruction.x(), instruction.nn()),
             0xA => self.set_index(instruction.nnn()),
             0xD => self.display(instruction.x(), instruction.y(), instruction.n()),
+            0x1 if instruction.nnn() == 0x260 && self.is_hires_boot() => self.hires_boot(),
             0x1 => self.jump(instruction.nnn()),
             0x7 => self.add_value(instruction.x(), instruction.nn()),
             0x3 => self.skip_if_equals_value(instruction.x(), instruction.nn()),
@@ -102,6 +531,11 @@ impl Chip8 {
             0x9 => self.skip_if_not_equals_register(instruction.x(), instruction.y()),
             0x2 => self.call_subroutine(instruction.nnn()),
             0x0 if instruction.nnn() == 0x0EE => self.return_subroutine(),
+            // MEGA-CHIP takes over the rest of the 0NNN space, so it has no machine code calls.
+            0x0 if self.settings.platform == Platform::MegaChip => {
+                self.execute_megachip(instruction)
+            }
+            0x0 => self.call_machine_code(instruction.nnn()),
             0x8 if instruction.n() == 0x0 => self.set_register(instruction.x(), instruction.y()),
             0x8 if instruction.n() == 0x1 => self.or_register(instruction.x(), instruction.y()),
             0x8 if instruction.n() == 0x2 => self.and_register(instruction.x(), instruction.y()),
@@ -115,10 +549,21 @@ impl Chip8 {
             0xF if instruction.nn() == 0x65 => self.load_registers(instruction.x()),
             0xF if instruction.nn() == 0x33 => self.binary_coded_decimal(instruction.x()),
             0xF if instruction.nn() == 0x1E => self.add_to_index(instruction.x()),
+            0xF if instruction.nn() == 0x29 => self.set_index_to_font(instruction.x()),
             0xC => self.random(instruction.x(), instruction.nn()),
             0xF if instruction.nn() == 0x07 => self.get_delay_timer_value(instruction.x()),
             0xF if instruction.nn() == 0x15 => self.set_delay_timer_value(instruction.x()),
             0xF if instruction.nn() == 0x18 => self.set_sound_timer_value(instruction.x()),
+            // XO-CHIP's audio instructions, which no other platform has.
+            0xF if instruction.x() == 0x0
+                && instruction.nn() == 0x02
+                && self.settings.xo_chip_audio =>
+            {
+                self.load_audio_pattern()
+            }
+            0xF if instruction.nn() == 0x3A && self.settings.xo_chip_audio => {
+                self.set_pitch(instruction.x())
+            }
             0xE if instruction.nn() == 0x9E => self.skip_if_key_pressed(instruction.x()),
             0xE if instruction.nn() == 0xA1 => self.skip_if_key_not_pressed(instruction.x()),
             0xF if instruction.nn() == 0x0A => self.get_key(instruction.x()),
@@ -127,6 +572,75 @@ impl Chip8 {
         }
     }
 
+    fn execute_megachip(&mut self, instruction: Instruction) {
+        match (instruction.x(), instruction.y()) {
+            (0x0, 0x1) if instruction.n() == 0x0 => self.set_megachip_mode(false),
+            (0x0, 0x1) if instruction.n() == 0x1 => self.set_megachip_mode(true),
+            (0x1, _) => self.set_long_index(instruction.nn()),
+            (0x2, _) => self.load_palette(instruction.nn()),
+            (0x3, _) => self.megachip.sprite_width = sprite_dimension(instruction.nn()),
+            (0x4, _) => self.megachip.sprite_height = sprite_dimension(instruction.nn()),
+            (0x5, _) => self.megachip.alpha = instruction.nn(),
+            (0x6, 0x0) => self.play_sample(instruction.n() == 0x0),
+            (0x7, 0x0) if instruction.n() == 0x0 => self.beeper.stop_sample(),
+            (0x8, 0x0) => self.set_blend_mode(instruction),
+            (0x9, _) => self.megachip.collision_colour = instruction.nn(),
+            _ => panic!("Unknown instruction {}", instruction),
+        }
+    }
+
+    fn set_megachip_mode(&mut self, enabled: bool) {
+        self.megachip.enabled = enabled;
+        if enabled {
+            self.display
+                .resize(MEGACHIP_WIDTH, MEGACHIP_HEIGHT, PixelFormat::Argb);
+        } else {
+            let (width, height) = self.settings.platform.display_size();
+            self.display.resize(width, height, PixelFormat::Indexed);
+        }
+    }
+
+    fn set_long_index(&mut self, high: u8) {
+        let low = self.memory.get_u16(self.program_counter.into());
+        self.program_counter += 2;
+        self.index_register = ((high as u32) << 16) | low as u32;
+    }
+
+    fn load_palette(&mut self, count: u8) {
+        for colour in 0..count as u32 {
+            let address = self.index_register + colour * 4;
+            let bytes = [0, 1, 2, 3].map(|offset| self.memory.get_u8(address + offset));
+            self.megachip.palette[colour as usize + 1] = u32::from_be_bytes(bytes);
+        }
+    }
+
+    /// Plays the sample at I, which starts with a 16-bit rate and a 24-bit length and has its
+    /// PCM data after a reserved byte.
+    fn play_sample(&mut self, looping: bool) {
+        let byte = |offset| self.memory.get_u8(self.index_register + offset) as u32;
+        let rate = (byte(0) << 8) | byte(1);
+        let length = (byte(2) << 16) | (byte(3) << 8) | byte(4);
+        let memory = self.memory.bytes();
+        let start = (self.index_register as usize + 6).min(memory.len());
+        let end = (start + length as usize).min(memory.len());
+        log::debug!(
+            "Playing {} byte sample from {:#08x} at {} Hz, looping: {}",
+            length,
+            start,
+            rate,
+            looping
+        );
+        self.beeper
+            .play_sample(memory[start..end].to_vec(), rate, looping);
+    }
+
+    fn set_blend_mode(&mut self, instruction: Instruction) {
+        match BlendMode::from_number(instruction.n()) {
+            Some(blend_mode) => self.megachip.blend_mode = blend_mode,
+            None => panic!("Unknown instruction {}", instruction),
+        }
+    }
+
     fn clear_display(&mut self) {
         self.display.clear();
     }
@@ -136,27 +650,73 @@ impl Chip8 {
     }
 
     fn set_index(&mut self, value: u16) {
-        self.index_register = value;
+        self.index_register = value.into();
     }
 
     fn display(&mut self, x_register: u8, y_register: u8, sprite_height: u8) {
-        let x_start = self.registers.get_value(x_register) % 64;
-        let y_start = self.registers.get_value(y_register) % 32;
+        // Font sprites are still drawn in monochrome in MEGA-CHIP mode.
+        if self.megachip.enabled
+            && self.index_register >= self.settings.platform.program_start() as u32
+        {
+            return self.display_megachip(x_register, y_register);
+        }
+
+        let width = self.display.width();
+        let height = self.display.height();
+        let x_start = self.registers.get_value(x_register) as usize % width;
+        let y_start = self.registers.get_value(y_register) as usize % height;
 
         let mut flags_value = false;
 
         for row in 0..sprite_height {
+            let y = y_start + row as usize;
+            if y >= height {
+                break;
+            }
+            let sprite_data = self.memory.get_u8(self.index_register + row as u32);
+            flags_value |= self.display.draw_sprite_row(x_start, y, sprite_data);
+        }
+
+        self.registers.set_value(0xF, flags_value as u8);
+    }
+
+    fn is_hires_boot(&self) -> bool {
+        self.settings.platform == Platform::Chip8Hires
+            && self.program_counter == self.settings.platform.program_start() + 2
+    }
+
+    fn hires_boot(&mut self) {
+        self.display.resize(64, 64, PixelFormat::Indexed);
+        self.program_counter = HIRES_ENTRY_POINT;
+    }
+
+    fn display_megachip(&mut self, x_register: u8, y_register: u8) {
+        let x_start = self.registers.get_value(x_register) as usize;
+        let y_start = self.registers.get_value(y_register) as usize;
+        let collision_colour = self.megachip.palette[self.megachip.collision_colour as usize];
+
+        let mut flags_value = false;
+
+        for row in 0..self.megachip.sprite_height {
             let y = y_start + row;
-            if y >= 32 {
+            if y >= MEGACHIP_HEIGHT {
                 break;
             }
-            let sprite_data = self.memory.get_u8(self.index_register + (row as u16));
-            for x_offset in (BitIterator { num: sprite_data }) {
-                let x = x_start + x_offset;
-                if x >= 64 {
+            for column in 0..self.megachip.sprite_width {
+                let x = x_start + column;
+                if x >= MEGACHIP_WIDTH {
                     break;
                 }
-                flags_value |= self.display.set(x as usize, y as usize);
+                let offset = (row * self.megachip.sprite_width + column) as u32;
+                let colour_index = self.memory.get_u8(self.index_register + offset);
+                if colour_index == 0 {
+                    continue;
+                }
+                let existing = self.display.get_colour(x, y);
+                flags_value |= existing == collision_colour;
+                let colour = self.megachip.palette[colour_index as usize];
+                let blended = self.megachip.blend(colour, existing);
+                self.display.set_colour(x, y, blended);
             }
         }
 
@@ -213,6 +773,50 @@ impl Chip8 {
         self.program_counter = address;
     }
 
+    fn call_machine_code(&mut self, address: u16) {
+        // Lay out the interpreter state in memory the way the VIP interpreter does, since
+        // machine code subroutines read and write it directly.
+        for register in 0..16 {
+            let value = self.registers.get_value(register);
+            self.memory
+                .set_u8((VIP_REGISTERS + register as u16).into(), value);
+        }
+        // Larger displays grow down from the top of memory.
+        let mut page = vec![0; self.display.page_len()];
+        let display_start = VIP_DISPLAY + 0x100 - page.len() as u16;
+        self.display.store_page(&mut page);
+        self.memory.load(display_start.into(), &page);
+
+        let mut cpu = Cdp1802::new();
+        cpu.p = 3;
+        cpu.x = 2;
+        cpu.registers[2] = VIP_STACK;
+        cpu.registers[3] = address;
+        cpu.registers[4] = VIP_INTERPRETER_LOOP;
+        cpu.registers[5] = self.program_counter;
+        cpu.registers[6] = VIP_REGISTERS;
+        cpu.registers[7] = VIP_REGISTERS;
+        cpu.registers[8] =
+            u16::from_be_bytes([self.delay_timer.get_value(), self.sound_timer.get_value()]);
+        cpu.registers[0xA] = self.index_register as u16;
+        cpu.registers[0xB] = display_start;
+        vip::run_subroutine(&mut cpu, &mut self.memory, &self.keypad);
+
+        for register in 0..16 {
+            let value = self.memory.get_u8((VIP_REGISTERS + register as u16).into());
+            self.registers.set_value(register, value);
+        }
+        for (offset, byte) in page.iter_mut().enumerate() {
+            *byte = self.memory.get_u8(display_start as u32 + offset as u32);
+        }
+        self.display.load_page(&page);
+        let [delay, sound] = cpu.registers[8].to_be_bytes();
+        self.delay_timer.set_value(delay);
+        self.sound_timer.set_value(sound);
+        self.index_register = (cpu.registers[0xA] & 0x0FFF).into();
+        self.program_counter = cpu.registers[5] & 0x0FFF;
+    }
+
     fn set_register(&mut self, register_number_x: u8, register_number_y: u8) {
         let y_value = self.registers.get_value(register_number_y);
         self.registers.set_value(register_number_x, y_value);
@@ -289,23 +893,23 @@ impl Chip8 {
 
     fn store_registers(&mut self, register_number: u8) {
         for register in 0..=register_number {
-            let address = self.index_register + register as u16;
+            let address = self.index_register + register as u32;
             let x_value = self.registers.get_value(register);
             self.memory.set_u8(address, x_value);
         }
         if self.settings.load_store_increment {
-            self.index_register += register_number as u16 + 1;
+            self.index_register += register_number as u32 + 1;
         }
     }
 
     fn load_registers(&mut self, register_number: u8) {
         for register in 0..=register_number {
-            let address = self.index_register + register as u16;
+            let address = self.index_register + register as u32;
             let memory_value = self.memory.get_u8(address);
             self.registers.set_value(register, memory_value);
         }
         if self.settings.load_store_increment {
-            self.index_register += register_number as u16 + 1;
+            self.index_register += register_number as u32 + 1;
         }
     }
 
@@ -321,13 +925,18 @@ impl Chip8 {
 
     fn add_to_index(&mut self, register_number: u8) {
         let x_value = self.registers.get_value(register_number);
-        self.index_register += x_value as u16;
+        self.index_register += x_value as u32;
         if self.settings.add_to_index_overflow {
             let overflowed = self.index_register > 0x0FFF;
             self.registers.set_value(0xF, overflowed as u8);
         }
     }
 
+    fn set_index_to_font(&mut self, register_number: u8) {
+        let digit = self.registers.get_value(register_number) & 0xF;
+        self.index_register = FONT_ADDRESS + digit as u32 * 5;
+    }
+
     fn random(&mut self, register_number: u8, mask: u8) {
         let random_number: u8 = self.rng.gen();
         let result = random_number & mask;
@@ -349,6 +958,19 @@ impl Chip8 {
         self.sound_timer.set_value(value);
     }
 
+    fn load_audio_pattern(&mut self) {
+        let mut pattern = [0; 16];
+        for (offset, byte) in pattern.iter_mut().enumerate() {
+            *byte = self.memory.get_u8(self.index_register + offset as u32);
+        }
+        self.beeper.set_pattern(pattern);
+    }
+
+    fn set_pitch(&mut self, register_number: u8) {
+        let pitch = self.registers.get_value(register_number);
+        self.beeper.set_pitch(pitch);
+    }
+
     fn skip_if_key_pressed(&mut self, register_number: u8) {
         let key_number = self.registers.get_value(register_number);
         if self.keypad.is_key_pressed(key_number) {
@@ -383,6 +1005,50 @@ impl Chip8 {
     }
 }
 
+/// MEGA-CHIP sprite widths and heights of 0 mean 256.
+fn sprite_dimension(value: u8) -> usize {
+    if value == 0 {
+        256
+    } else {
+        value as usize
+    }
+}
+
+/// The interpreter's registers, timers and stack at the end of a frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CpuState {
+    /// V0 to VF.
+    pub registers: [u8; 16],
+    pub index_register: u32,
+    pub program_counter: u16,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    /// Return addresses, the oldest first.
+    pub stack: Vec<u16>,
+}
+
+impl Display for CpuState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (row, registers) in self.registers.chunks(8).enumerate() {
+            for (column, value) in registers.iter().enumerate() {
+                let separator = if column == 7 { "\n" } else { "  " };
+                write!(f, "V{:X} {:02x}{}", row * 8 + column, value, separator)?;
+            }
+        }
+        writeln!(
+            f,
+            "PC {:#05x}  I {:#05x}  DT {:02x}  ST {:02x}",
+            self.program_counter, self.index_register, self.delay_timer, self.sound_timer
+        )?;
+        let stack: Vec<String> = self.stack.iter().map(|a| format!("{:#05x}", a)).collect();
+        if stack.is_empty() {
+            writeln!(f, "Stack empty")
+        } else {
+            writeln!(f, "Stack {}", stack.join(" "))
+        }
+    }
+}
+
 struct Instruction {
     value: u16,
 }
@@ -422,22 +1088,3 @@ impl Display for Instruction {
         write!(f, "{:#06x}", self.value)
     }
 }
-
-struct BitIterator {
-    num: u8,
-}
-
-impl Iterator for BitIterator {
-    type Item = u8;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        let value = self.num.leading_zeros();
-        if value < 8 {
-            let mask = 0x80 >> value;
-            self.num = self.num ^ (mask as u8);
-            Some(value as u8)
-        } else {
-            None
-        }
-    }
-}
@@ -0,0 +1,62 @@
+/// The machine a program was written for, which decides where it is loaded and the shape of the
+/// display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    /// The original COSMAC VIP interpreter.
+    Chip8,
+    /// The ETI-660 learning computer, with programs at 0x600 and a 64x48 display.
+    Eti660,
+    /// The two-page VIP interpreter, booted with a `1260` jump into its 64x64 mode.
+    Chip8Hires,
+    /// Revival Studios' MEGA-CHIP 8, with a 256x192 colour mode and 16MB of memory.
+    MegaChip,
+}
+
+/// Where a CHIP-8 HIRES program's own code starts once the boot jump has been taken.
+pub const HIRES_ENTRY_POINT: u16 = 0x2C0;
+
+impl Platform {
+    pub const ALL: [Platform; 4] = [
+        Platform::Chip8,
+        Platform::Eti660,
+        Platform::Chip8Hires,
+        Platform::MegaChip,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|platform| platform.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Platform::Chip8 => "chip8",
+            Platform::Eti660 => "eti660",
+            Platform::Chip8Hires => "chip8-hires",
+            Platform::MegaChip => "megachip",
+        }
+    }
+
+    pub fn program_start(self) -> u16 {
+        match self {
+            Platform::Chip8 | Platform::Chip8Hires | Platform::MegaChip => 0x200,
+            Platform::Eti660 => 0x600,
+        }
+    }
+
+    pub fn memory_size(self) -> usize {
+        match self {
+            Platform::Chip8 | Platform::Eti660 | Platform::Chip8Hires => 0x1000,
+            Platform::MegaChip => 0x100_0000,
+        }
+    }
+
+    /// The display size a program sees when it starts.
+    pub fn display_size(self) -> (usize, usize) {
+        match self {
+            Platform::Chip8 | Platform::Chip8Hires | Platform::MegaChip => (64, 32),
+            Platform::Eti660 => (64, 48),
+        }
+    }
+}
@@ -5,9 +5,7 @@ pub struct Registers {
 impl Registers {
     pub fn new() -> Self {
         let registers = [0; 16];
-        Self {
-            registers: registers,
-        }
+        Self { registers }
     }
 
     pub fn set_value(&mut self, register: u8, value: u8) {
@@ -1,17 +1,74 @@
+use super::platform::Platform;
+
 pub struct Settings {
+    pub platform: Platform,
     pub assign_shift: bool,
     pub load_store_increment: bool,
     pub add_to_index_overflow: bool,
     pub jump_with_offset_add: bool,
+    /// Whether XO-CHIP's F002 audio pattern and FX3A pitch instructions are available.
+    pub xo_chip_audio: bool,
+    pub timing: Timing,
+    pub backend: Backend,
 }
 
 impl Default for Settings {
     fn default() -> Self {
         Self {
+            platform: Platform::Chip8,
             assign_shift: false,
             load_store_increment: false,
             add_to_index_overflow: true,
             jump_with_offset_add: false,
+            xo_chip_audio: false,
+            timing: Timing::Unlimited,
+            backend: Backend::Interpreter,
         }
     }
 }
+
+impl Settings {
+    pub const QUIRKS: [&'static str; 5] = [
+        "assign-shift",
+        "load-store-increment",
+        "add-to-index-overflow",
+        "jump-with-offset-add",
+        "xo-chip-audio",
+    ];
+
+    pub fn set_quirk(&mut self, name: &str, value: bool) -> Result<(), String> {
+        let quirk = match name {
+            "assign-shift" => &mut self.assign_shift,
+            "load-store-increment" => &mut self.load_store_increment,
+            "add-to-index-overflow" => &mut self.add_to_index_overflow,
+            "jump-with-offset-add" => &mut self.jump_with_offset_add,
+            "xo-chip-audio" => &mut self.xo_chip_audio,
+            _ => return Err(format!("unknown quirk `{}`", name)),
+        };
+        *quirk = value;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timing {
+    /// Run instructions as fast as possible, decrementing the timers against wall-clock time.
+    Unlimited,
+    /// Run a fixed number of instructions in each 60 Hz frame.
+    InstructionsPerSecond(u32),
+    /// Charge each instruction the machine cycles it took in the COSMAC VIP interpreter and
+    /// drive the timers from the 60 Hz interrupt of the emulated machine.
+    CosmacVip,
+}
+
+pub enum Backend {
+    /// Interpret CHIP-8 instructions directly, running `0NNN` machine code subroutines on an
+    /// emulated CDP1802.
+    Interpreter,
+    /// Emulate a whole COSMAC VIP running the original interpreter image from 0x000, with the
+    /// monitor ROM mapped at 0x8000.
+    CosmacVip {
+        interpreter: Box<[u8]>,
+        monitor: Box<[u8]>,
+    },
+}
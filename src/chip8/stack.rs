@@ -16,4 +16,8 @@ impl Stack {
     pub fn pop(&mut self) -> Option<u16> {
         self.buffer.pop()
     }
+
+    pub fn values(&self) -> &[u16] {
+        &self.buffer
+    }
 }
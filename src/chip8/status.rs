@@ -0,0 +1,57 @@
+use std::fmt::Display;
+
+use super::platform::Platform;
+
+/// What the emulator is doing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Running,
+    Paused,
+    /// Stopped by a fault, waiting to be reset or given a new ROM.
+    Faulted,
+    Stopped,
+}
+
+/// News from the emulator thread for the frontend, sent as things change.
+pub enum Status {
+    State(State),
+    /// A program has been loaded, at startup or by [`super::control::Control::Load`].
+    Rom {
+        size: usize,
+        platform: Platform,
+    },
+    Fault(Fault),
+    /// The program is stuck in a jump to itself, the usual way CHIP-8 programs end.
+    Halted {
+        address: u16,
+    },
+    /// The sound timer has started or stopped the buzzer.
+    Sound(bool),
+    /// Sent once a second.
+    Stats {
+        frames: u64,
+        instructions_per_second: u64,
+    },
+}
+
+/// Something the program did that the emulator could not carry on from.
+pub struct Fault {
+    pub message: String,
+    /// The address of the instruction that faulted.
+    pub address: u16,
+    /// The instruction at that address, unless the address is outside memory.
+    pub instruction: Option<u16>,
+}
+
+impl Display for Fault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.instruction {
+            Some(instruction) => write!(
+                f,
+                "{} (instruction {:04x} at {:#05x})",
+                self.message, instruction, self.address
+            ),
+            None => write!(f, "{} (at {:#05x})", self.message, self.address),
+        }
+    }
+}
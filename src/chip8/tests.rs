@@ -0,0 +1,257 @@
+use crossbeam_channel::unbounded;
+
+use super::{
+    audio::{tests::BufferSink, Beeper, Tone},
+    display::{self, PixelFormat},
+    megachip::{BlendMode, MEGACHIP_HEIGHT, MEGACHIP_WIDTH},
+    platform::Platform,
+    settings::Settings,
+    Chip8, Instruction,
+};
+
+/// A machine for `platform` with `program` loaded, nothing listening to it.
+fn machine(platform: Platform, program: &[u8]) -> Chip8 {
+    let settings = Settings {
+        platform,
+        ..Settings::default()
+    };
+    let (frames, _) = display::frame_slot();
+    let (_, events) = unbounded();
+    let (_, controls) = unbounded();
+    let (statuses, _) = unbounded();
+    Chip8::new(settings, program, frames, events, controls, statuses).unwrap()
+}
+
+/// Fetches and executes `count` instructions.
+fn step(chip8: &mut Chip8, count: usize) {
+    for _ in 0..count {
+        let instruction = chip8.fetch();
+        chip8.execute(instruction);
+    }
+}
+
+/// The message of the fault the next instruction raises.
+fn fault(chip8: &mut Chip8) -> String {
+    chip8.try_run_frame(1).err().unwrap().message
+}
+
+#[test]
+fn megachip_0010_and_0011_switch_colour_mode() {
+    let mut chip8 = machine(Platform::MegaChip, &[0x00, 0x11, 0x00, 0x10]);
+    step(&mut chip8, 1);
+    assert!(chip8.megachip.enabled);
+    assert_eq!(chip8.display.width(), MEGACHIP_WIDTH);
+    assert_eq!(chip8.display.height(), MEGACHIP_HEIGHT);
+    assert_eq!(chip8.display.frame(0).format, PixelFormat::Argb);
+    step(&mut chip8, 1);
+    assert!(!chip8.megachip.enabled);
+    assert_eq!((chip8.display.width(), chip8.display.height()), (64, 32));
+}
+
+#[test]
+fn megachip_01nn_loads_a_24_bit_index() {
+    let mut chip8 = machine(Platform::MegaChip, &[0x01, 0x12, 0x34, 0x56]);
+    step(&mut chip8, 1);
+    assert_eq!(chip8.index_register, 0x12_3456);
+    assert_eq!(chip8.program_counter, 0x204);
+}
+
+#[test]
+fn megachip_02nn_loads_palette_colours_from_i() {
+    let program = [
+        0xA2, 0x06, // I = 0x206
+        0x02, 0x02, // Load two colours
+        0x00, 0x00, //
+        0xFF, 0x11, 0x22, 0x33, //
+        0x80, 0x44, 0x55, 0x66,
+    ];
+    let mut chip8 = machine(Platform::MegaChip, &program);
+    step(&mut chip8, 2);
+    assert_eq!(chip8.megachip.palette[0], 0);
+    assert_eq!(chip8.megachip.palette[1], 0xFF11_2233);
+    assert_eq!(chip8.megachip.palette[2], 0x8044_5566);
+    assert_eq!(chip8.megachip.palette[3], 0);
+}
+
+#[test]
+fn megachip_03nn_and_04nn_set_the_sprite_size() {
+    let mut chip8 = machine(Platform::MegaChip, &[0x03, 0x10, 0x04, 0x00]);
+    step(&mut chip8, 2);
+    assert_eq!(chip8.megachip.sprite_width, 16);
+    assert_eq!(chip8.megachip.sprite_height, 256);
+}
+
+#[test]
+fn megachip_05nn_sets_the_alpha() {
+    let mut chip8 = machine(Platform::MegaChip, &[0x05, 0x80]);
+    step(&mut chip8, 1);
+    assert_eq!(chip8.megachip.alpha, 0x80);
+}
+
+#[test]
+fn megachip_060n_plays_a_sample_and_0700_stops_it() {
+    let program = [
+        0xA2, 0x08, // I = 0x208
+        0x06, 0x00, // Play looping
+        0x07, 0x00, // Stop
+        0x00, 0x00, //
+        0x02, 0x58, // 600 Hz
+        0x00, 0x00, 0x02, // 2 bytes
+        0x00, //
+        0xC0, 0x40,
+    ];
+    let mut chip8 = machine(Platform::MegaChip, &program);
+    let (sink, samples) = BufferSink::new(600);
+    let tone = Tone {
+        volume: 1.0,
+        ..Tone::default()
+    };
+    chip8.set_beeper(Beeper::new(tone, Box::new(sink)));
+    step(&mut chip8, 2);
+    chip8.play_sound();
+    assert_eq!(samples.lock().unwrap()[..4], [0.5, -0.5, 0.5, -0.5]);
+    step(&mut chip8, 1);
+    chip8.play_sound();
+    assert_eq!(samples.lock().unwrap()[10..], [0.0; 10]);
+}
+
+#[test]
+fn megachip_080n_sets_the_blend_mode() {
+    let mut chip8 = machine(Platform::MegaChip, &[0x08, 0x05, 0x08, 0x06]);
+    step(&mut chip8, 1);
+    assert_eq!(chip8.megachip.blend_mode, BlendMode::Multiply);
+    assert!(fault(&mut chip8).starts_with("Unknown instruction"));
+}
+
+#[test]
+fn megachip_09nn_sets_the_collision_colour() {
+    let mut chip8 = machine(Platform::MegaChip, &[0x09, 0x07]);
+    step(&mut chip8, 1);
+    assert_eq!(chip8.megachip.collision_colour, 7);
+}
+
+#[test]
+fn megachip_rejects_malformed_extensions() {
+    for instruction in [[0x00, 0x13], [0x06, 0x10], [0x07, 0x01], [0x08, 0x10]] {
+        let mut chip8 = machine(Platform::MegaChip, &instruction);
+        assert!(fault(&mut chip8).starts_with("Unknown instruction"));
+        assert_eq!(chip8.index_register, 0);
+    }
+}
+
+#[test]
+fn megachip_never_calls_machine_code() {
+    let mut chip8 = machine(Platform::MegaChip, &[0x00, 0x11, 0x0A, 0x00]);
+    step(&mut chip8, 1);
+    assert!(fault(&mut chip8).starts_with("Unknown instruction"));
+}
+
+#[test]
+fn other_platforms_call_machine_code_for_megachip_opcodes() {
+    // 0204 calls machine code at 0x204, which returns straight away with SEP R4.
+    let mut chip8 = machine(Platform::Chip8, &[0x02, 0x04, 0x00, 0x00, 0xD4]);
+    step(&mut chip8, 1);
+    assert_eq!(chip8.program_counter, 0x202);
+    assert_eq!(chip8.megachip.palette, [0; 256]);
+}
+
+#[test]
+fn fx29_points_i_at_the_digit_in_the_low_nibble() {
+    let program = [
+        0x63, 0x1B, // V3 = 0x1B
+        0xF3, 0x29, // I = sprite for B
+        0xD0, 0x05, // Draw it at 0, 0
+    ];
+    let mut chip8 = machine(Platform::Chip8, &program);
+    step(&mut chip8, 2);
+    assert_eq!(chip8.index_register, 0x50 + 0xB * 5);
+    step(&mut chip8, 1);
+    let pixels = chip8.display.frame(0).pixels;
+    let row = |y: usize| pixels[y * 64..y * 64 + 4].to_vec();
+    assert_eq!(row(0), [1, 1, 1, 0]);
+    assert_eq!(row(1), [1, 0, 0, 1]);
+}
+
+#[test]
+fn xo_chip_audio_needs_its_quirk() {
+    let program = [0xF0, 0x02, 0xF3, 0x3A];
+    let mut chip8 = machine(Platform::Chip8, &program);
+    assert!(fault(&mut chip8).starts_with("Unknown instruction"));
+
+    let mut chip8 = machine(Platform::Chip8, &program);
+    chip8.settings.xo_chip_audio = true;
+    assert!(chip8.try_run_frame(2).is_ok());
+}
+
+#[test]
+fn f002_loads_the_pattern_the_sound_timer_plays() {
+    let program = [
+        0xA2, 0x08, // I = 0x208
+        0xF0, 0x02, // Load the pattern
+        0x60, 0x02, // V0 = 2
+        0xF0, 0x18, // Sound timer = V0
+        0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, //
+        0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0,
+    ];
+    let mut chip8 = machine(Platform::Chip8, &program);
+    chip8.settings.xo_chip_audio = true;
+    let (sink, samples) = BufferSink::new(8000);
+    let tone = Tone {
+        volume: 1.0,
+        ..Tone::default()
+    };
+    chip8.set_beeper(Beeper::new(tone, Box::new(sink)));
+    step(&mut chip8, 4);
+    chip8.play_sound();
+    assert_eq!(
+        samples.lock().unwrap()[..16],
+        [[1.0; 8], [-1.0; 8]].concat()
+    );
+}
+
+#[test]
+fn vip_cycles_follow_the_interpreter_routines() {
+    let mut chip8 = machine(Platform::Chip8, &[]);
+    chip8.registers.set_value(0x1, 0x12);
+    chip8.registers.set_value(0x2, 0x12);
+    chip8.registers.set_value(0x3, 255);
+    let cycles = |chip8: &Chip8, value: u16| chip8.vip_cycles(&Instruction::new(value));
+    let expected = [
+        (0x00E0, 3078),
+        (0x00EE, 10),
+        (0x1200, 12),
+        (0x2200, 26),
+        (0x3112, 14),
+        (0x3113, 10),
+        (0x4113, 14),
+        (0x5120, 18),
+        (0x5130, 14),
+        (0x6100, 6),
+        (0x7101, 10),
+        (0x8120, 12),
+        (0x8124, 44),
+        (0x9130, 18),
+        (0xA200, 12),
+        (0xB200, 22),
+        (0xC1FF, 36),
+        (0xE09E, 14),
+        (0xE0A1, 18),
+        (0xF107, 10),
+        (0xF10A, 19),
+        (0xF115, 10),
+        (0xF118, 10),
+        (0xF11E, 16),
+        (0xF129, 16),
+        (0xF555, 14 + 14 * 6),
+        (0xF065, 28),
+    ];
+    for (instruction, expected) in expected {
+        assert_eq!(cycles(&chip8, instruction), expected, "{:04X}", instruction);
+    }
+    // BCD takes longer for larger digits: 255 is 2 + 5 + 5.
+    assert_eq!(cycles(&chip8, 0xF333), 80 + 16 * 12);
+    // Sprites cost more per row when they straddle two bytes.
+    chip8.registers.set_value(0x4, 8);
+    assert_eq!(cycles(&chip8, 0xD445), 26 + 34 * 5);
+    assert_eq!(cycles(&chip8, 0xD145), 26 + 46 * 5);
+}
@@ -0,0 +1,131 @@
+use std::time::{Duration, Instant};
+
+use super::timer::TIMER_DECREMENT;
+
+/// Machine cycles in one 60 Hz frame of a COSMAC VIP (1.7609 MHz clock, 8 clocks per cycle).
+pub const VIP_CYCLES_PER_FRAME: u64 = 3668;
+
+/// Machine cycles taken by the interpreter's interrupt routine and the 1861 display DMA.
+pub const VIP_INTERRUPT_CYCLES: u64 = 1832;
+
+/// Machine cycles the interpreter spends fetching and decoding each instruction.
+pub const VIP_FETCH_CYCLES: u64 = 68;
+
+pub struct VipClock {
+    cycles: u64,
+    next_interrupt: u64,
+    frames: u32,
+    started: Instant,
+}
+
+impl VipClock {
+    pub fn new() -> Self {
+        Self {
+            cycles: 0,
+            next_interrupt: VIP_CYCLES_PER_FRAME,
+            frames: 0,
+            started: Instant::now(),
+        }
+    }
+
+    pub fn add(&mut self, cycles: u64) {
+        self.cycles += cycles;
+    }
+
+    pub fn wait_for_interrupt(&mut self) {
+        self.cycles = self.cycles.max(self.next_interrupt);
+    }
+
+    pub fn take_interrupt(&mut self) -> bool {
+        if self.cycles < self.next_interrupt {
+            return false;
+        }
+        self.cycles += VIP_INTERRUPT_CYCLES;
+        self.next_interrupt += VIP_CYCLES_PER_FRAME;
+        self.frames += 1;
+        true
+    }
+
+    pub fn next_frame(&mut self) {
+        self.wait_for_interrupt();
+        self.take_interrupt();
+    }
+
+    pub fn sleep_until_frame(&mut self) {
+        sleep_until_frame(&mut self.started, self.frames);
+    }
+}
+
+/// How far behind real time the emulator may fall before it stops trying to catch up.
+const MAX_LAG: Duration = Duration::from_millis(100);
+
+/// Sleeps until the start of frame `frames`, counting from `started`. After a pause or a stall
+/// that leaves the emulator well behind, `started` is moved forward so it carries on at normal
+/// speed instead of racing through the missed frames.
+pub fn sleep_until_frame(started: &mut Instant, frames: u32) {
+    let target = *started + TIMER_DECREMENT * frames;
+    let now = Instant::now();
+    if target > now {
+        std::thread::sleep(target - now);
+    } else if now - target > MAX_LAG {
+        *started += now - target;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interrupts_every_3668_cycles() {
+        let mut clock = VipClock::new();
+        clock.add(VIP_CYCLES_PER_FRAME - 1);
+        assert!(!clock.take_interrupt());
+        clock.add(1);
+        assert!(clock.take_interrupt());
+        assert!(!clock.take_interrupt());
+        assert_eq!(clock.frames, 1);
+        assert_eq!(clock.next_interrupt, 2 * VIP_CYCLES_PER_FRAME);
+    }
+
+    #[test]
+    fn charges_1832_cycles_for_each_interrupt() {
+        let mut clock = VipClock::new();
+        clock.add(VIP_CYCLES_PER_FRAME);
+        clock.take_interrupt();
+        assert_eq!(clock.cycles, VIP_CYCLES_PER_FRAME + VIP_INTERRUPT_CYCLES);
+        // That leaves 1836 cycles for the interpreter in every frame.
+        clock.add(VIP_CYCLES_PER_FRAME - VIP_INTERRUPT_CYCLES - 1);
+        assert!(!clock.take_interrupt());
+        clock.add(1);
+        assert!(clock.take_interrupt());
+    }
+
+    #[test]
+    fn waiting_for_an_interrupt_skips_to_the_next_frame() {
+        let mut clock = VipClock::new();
+        clock.add(100);
+        clock.wait_for_interrupt();
+        assert_eq!(clock.cycles, VIP_CYCLES_PER_FRAME);
+        assert!(clock.take_interrupt());
+
+        for frame in 2..=5 {
+            clock.next_frame();
+            assert_eq!(clock.frames, frame);
+            assert_eq!(
+                clock.cycles,
+                frame as u64 * VIP_CYCLES_PER_FRAME + VIP_INTERRUPT_CYCLES
+            );
+        }
+    }
+
+    #[test]
+    fn takes_each_missed_interrupt_in_turn() {
+        let mut clock = VipClock::new();
+        clock.add(2 * VIP_CYCLES_PER_FRAME);
+        assert!(clock.take_interrupt());
+        assert!(clock.take_interrupt());
+        assert!(!clock.take_interrupt());
+        assert_eq!(clock.frames, 2);
+    }
+}
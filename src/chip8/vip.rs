@@ -0,0 +1,246 @@
+use super::{
+    cdp1802::{Bus, Cdp1802},
+    keypad::Keypad,
+    memory::Memory,
+};
+
+/// Where the VIP interpreter keeps its stack, V registers and display page in a 4K machine.
+pub const VIP_STACK: u16 = 0x0ECF;
+pub const VIP_REGISTERS: u16 = 0x0EF0;
+pub const VIP_DISPLAY: u16 = 0x0F00;
+
+/// The interpreter's fetch loop, which machine code subroutines return to with `SEP R4`.
+pub const VIP_INTERPRETER_LOOP: u16 = 0x001B;
+
+/// Machine cycles a subroutine may run for before it is assumed never to return.
+const MACHINE_CODE_CYCLE_LIMIT: u64 = 1_000_000;
+
+const LINES_PER_FRAME: u16 = 262;
+const CYCLES_PER_LINE: i64 = 14;
+const INTERRUPT_LINE: u16 = 62;
+const DISPLAY_START_LINE: u16 = 64;
+const DISPLAY_END_LINE: u16 = 192;
+const DMA_BYTES_PER_LINE: u16 = 8;
+
+/// A COSMAC VIP: a CDP1802 with 4K of RAM, the monitor ROM at 0x8000, a CDP1861 video chip and
+/// the hex keypad.
+pub struct CosmacVip {
+    cpu: Cdp1802,
+    io: VipIo,
+    monitor: Box<[u8]>,
+    overrun: i64,
+}
+
+impl CosmacVip {
+    pub fn new(monitor: Box<[u8]>) -> Self {
+        let mut cpu = Cdp1802::new();
+        // Start the interpreter at 0x0000 the way the monitor does when RUN is pressed without C
+        // held, leaving the top RAM page in R1.
+        cpu.registers[1] = VIP_DISPLAY;
+        Self {
+            cpu,
+            io: VipIo::new(),
+            monitor,
+            overrun: 0,
+        }
+    }
+
+    /// Runs the machine for one 60 Hz frame, copying the displayed page into `page`.
+    pub fn run_frame(&mut self, memory: &mut Memory, keypad: &Keypad, page: &mut [u8]) {
+        let mut bus = VipBus {
+            memory,
+            monitor: &self.monitor,
+            keypad,
+            io: &mut self.io,
+        };
+        for line in 0..LINES_PER_FRAME {
+            let mut budget = CYCLES_PER_LINE + self.overrun;
+            if bus.io.display_enabled {
+                if line == INTERRUPT_LINE {
+                    self.cpu.interrupt();
+                }
+                bus.io.ef1 = (INTERRUPT_LINE - 2..DISPLAY_START_LINE).contains(&line)
+                    || (DISPLAY_END_LINE - 4..DISPLAY_END_LINE).contains(&line);
+                if (DISPLAY_START_LINE..DISPLAY_END_LINE).contains(&line) {
+                    // Each row of the CHIP-8 display is repeated over four scanlines.
+                    let row = line - DISPLAY_START_LINE;
+                    for byte in 0..DMA_BYTES_PER_LINE {
+                        let value = bus.read(self.cpu.registers[0]);
+                        if row.is_multiple_of(4) {
+                            page[((row / 4) * DMA_BYTES_PER_LINE + byte) as usize] = value;
+                        }
+                        self.cpu.registers[0] = self.cpu.registers[0].wrapping_add(1);
+                    }
+                    budget -= DMA_BYTES_PER_LINE as i64;
+                }
+            }
+            while budget > 0 {
+                budget -= self.cpu.step(&mut bus) as i64;
+            }
+            self.overrun = budget;
+        }
+    }
+}
+
+/// Runs a machine code subroutine called with `0NNN` until it returns to the interpreter.
+pub fn run_subroutine(cpu: &mut Cdp1802, memory: &mut Memory, keypad: &Keypad) {
+    let address = cpu.registers[cpu.p as usize];
+    let mut io = VipIo::new();
+    let mut bus = VipBus {
+        memory,
+        monitor: &[],
+        keypad,
+        io: &mut io,
+    };
+    let mut cycles = 0;
+    while cpu.p != 4 {
+        cycles += cpu.step(&mut bus);
+        if cycles > MACHINE_CODE_CYCLE_LIMIT {
+            panic!("Machine code subroutine at {:#05x} did not return", address);
+        }
+    }
+}
+
+struct VipIo {
+    key_latch: u8,
+    display_enabled: bool,
+    ef1: bool,
+}
+
+impl VipIo {
+    fn new() -> Self {
+        Self {
+            key_latch: 0,
+            display_enabled: false,
+            ef1: false,
+        }
+    }
+}
+
+struct VipBus<'a> {
+    memory: &'a mut Memory,
+    monitor: &'a [u8],
+    keypad: &'a Keypad,
+    io: &'a mut VipIo,
+}
+
+impl Bus for VipBus<'_> {
+    fn read(&mut self, address: u16) -> u8 {
+        if address & 0x8000 != 0 {
+            let offset = (address & 0x01FF) as usize;
+            self.monitor.get(offset).copied().unwrap_or(0)
+        } else {
+            self.memory.get_u8((address & 0x0FFF).into())
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        if address & 0x8000 == 0 {
+            self.memory.set_u8((address & 0x0FFF).into(), value);
+        }
+    }
+
+    fn input(&mut self, port: u8) -> u8 {
+        if port == 1 {
+            self.io.display_enabled = true;
+        }
+        0
+    }
+
+    fn output(&mut self, port: u8, value: u8) {
+        match port {
+            1 => self.io.display_enabled = false,
+            2 => self.io.key_latch = value & 0xF,
+            _ => {}
+        }
+    }
+
+    fn flag(&self, number: u8) -> bool {
+        match number {
+            1 => self.io.ef1,
+            3 => self.keypad.is_key_pressed(self.io.key_latch),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossbeam_channel::unbounded;
+
+    use super::*;
+
+    fn keypad() -> Keypad {
+        Keypad::new(unbounded().1)
+    }
+
+    #[test]
+    fn interrupts_and_copies_the_display_page_once_the_display_is_on() {
+        // SEX 2, INP 1 to turn the display on, IDL. The interrupt routine at 0x10 points the
+        // DMA at 0x40 and loops.
+        let mut memory = Memory::new(&[], 0x200, 0x1000).unwrap();
+        memory.load(0, &[0xE2, 0x69, 0x00]);
+        memory.load(0x10, &[0xF8, 0x00, 0xB0, 0xF8, 0x40, 0xA0, 0x30, 0x16]);
+        memory.set_u8(0x40, 0xAA);
+        // Only the first of each row's four lines is kept, and nothing moves R0 back between
+        // them, so row 31 is read from 0x40 + 31 * 32.
+        memory.set_u8(0x40 + 31 * 32 + 7, 0x55);
+        let mut vip = CosmacVip::new(Box::new([]));
+        vip.cpu.registers[1] = 0x10;
+        let mut page = vec![0; 256];
+        vip.run_frame(&mut memory, &keypad(), &mut page);
+        assert_eq!(vip.cpu.t, 0x20);
+        assert_eq!((vip.cpu.x, vip.cpu.p), (2, 1));
+        assert!(!vip.cpu.ie);
+        assert_eq!(page[0], 0xAA);
+        assert_eq!(page[255], 0x55);
+        assert_eq!(vip.cpu.registers[0], 0x40 + 4 * 256);
+    }
+
+    #[test]
+    fn does_not_interrupt_with_the_display_off() {
+        let mut memory = Memory::new(&[], 0x200, 0x1000).unwrap();
+        let mut vip = CosmacVip::new(Box::new([]));
+        let mut page = vec![0xFF; 256];
+        vip.run_frame(&mut memory, &keypad(), &mut page);
+        assert_eq!(vip.cpu.p, 0);
+        assert!(vip.cpu.ie);
+        assert!(page.iter().all(|&byte| byte == 0xFF));
+    }
+
+    #[test]
+    fn reads_the_monitor_at_0x8000() {
+        // LBR 0x8000, where the monitor loads 0x42 into D.
+        let mut memory = Memory::new(&[], 0x200, 0x1000).unwrap();
+        memory.load(0, &[0xC0, 0x80, 0x00]);
+        let mut vip = CosmacVip::new(Box::new([0xF8, 0x42, 0x30, 0x02]));
+        vip.run_frame(&mut memory, &keypad(), &mut [0; 256]);
+        assert_eq!(vip.cpu.d, 0x42);
+        assert_eq!(vip.cpu.registers[0] & 0x8000, 0x8000);
+    }
+
+    #[test]
+    fn runs_machine_code_until_it_returns_to_the_interpreter() {
+        // LDI 0x07, PLO 5, SEP 4
+        let mut memory = Memory::new(&[], 0x200, 0x1000).unwrap();
+        memory.load(0x300, &[0xF8, 0x07, 0xA5, 0xD4]);
+        let mut cpu = Cdp1802::new();
+        cpu.p = 3;
+        cpu.registers[3] = 0x300;
+        run_subroutine(&mut cpu, &mut memory, &keypad());
+        assert_eq!(cpu.p, 4);
+        assert_eq!(cpu.registers[5], 0x07);
+        assert_eq!(cpu.registers[3], 0x304);
+    }
+
+    #[test]
+    #[should_panic(expected = "Machine code subroutine at 0x300 did not return")]
+    fn gives_up_on_machine_code_that_never_returns() {
+        let mut memory = Memory::new(&[], 0x200, 0x1000).unwrap();
+        memory.load(0x300, &[0x30, 0x00]);
+        let mut cpu = Cdp1802::new();
+        cpu.p = 3;
+        cpu.registers[3] = 0x300;
+        run_subroutine(&mut cpu, &mut memory, &keypad());
+    }
+}
@@ -0,0 +1,645 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    audio::AudioOutput,
+    chip8::{
+        analysis::{Analysis, Confidence},
+        audio::{Tone, Waveform},
+        platform::Platform,
+        settings::{Settings, Timing},
+    },
+    crt::Crt,
+    database::Entry,
+    dump::{Dump, Item},
+    keymap::Keymap,
+    movie::Movie,
+    palette::Palette,
+    recording::Format,
+    screen::Flicker,
+    tui::Render,
+};
+
+pub const USAGE: &str = "\
+Usage: chip8 <command> [options]
+
+Commands:
+  run <rom>        Run a ROM in a window
+  tui <rom>        Run a ROM in the terminal, such as over SSH where no window can open
+  info <rom>       Show the size, SHA-1, database entry and detected platform of a ROM
+  disasm <rom>     Print a listing of a ROM
+  test <script>... Run test scripts, which press keys and check the screen, registers and
+                   memory; see Test scripts below
+  selftest         Run the CHIP-8 test ROMs in roms/ on every platform and
+                   compare the screens they finish on with the expected ones
+  headless <rom>   Run a ROM without a window for a fixed number of frames, then print
+                   the final screen or dump the machine's state; fails if the program
+                   faults
+
+Emulator options (run, tui, headless):
+  --platform <name>          chip8, eti660, chip8-hires or megachip (default from the
+                             program database, otherwise detected from the ROM)
+  --ips <n>                  Instructions per second, run in 60 Hz frames (default as
+                             fast as possible, or 600 for headless)
+  --vip-timing               Time instructions like the COSMAC VIP interpreter (run
+                             and tui only)
+  --quirk <name>=<bool>      Set a quirk: assign-shift, load-store-increment,
+                             add-to-index-overflow, jump-with-offset-add or
+                             xo-chip-audio
+  --vip-interpreter <file>   Run on an emulated COSMAC VIP with this interpreter image
+                             (run and tui only)
+  --vip-monitor <file>       Monitor ROM for the emulated COSMAC VIP (run and tui only)
+  --database <file>          Program database to use ahead of the bundled one and
+                             programs.json in the config directory. The bundled one
+                             only lists the test ROMs in roms/, so games are only
+                             recognised from one of these
+  --audio <output>           native, none, or a .wav file to write the sound to (default
+                             native for run if built with the native-audio feature,
+                             otherwise none; tui also rings the terminal bell)
+  --pitch <hz>               Pitch of the beep (default 440)
+  --volume <level>           Volume of the beep from 0 to 1 (default 0.25)
+  --waveform <name>          square, triangle, sawtooth or sine (default square)
+
+Window options (run, and headless for screenshots and recordings):
+  --scale <n>                Size of each CHIP-8 pixel in screen pixels, which sets the
+                             starting window size and the screenshot size (default 12,
+                             or 1 for headless)
+  --palette <name|colours>   classic, green, amber, lcd, high-contrast, colour-blind or
+                             octo, or hex colours: <bg>,<fg> for unlit and lit pixels,
+                             or four for XO-CHIP's second plane and both planes too
+  --flicker <mode>           Hide sprite flicker: off, decay[=<fade>] to fade pixels out,
+                             blend to mix the last two frames, or or[=<n>] to light
+                             pixels lit in any of the last n frames (default off)
+  --crt <effects>            CRT effects to draw, separated by commas, each optionally
+                             with a strength: scanlines, grid, bloom, curvature and
+                             vignette, as in scanlines,bloom=0.5
+  --screenshot-dir <dir>     Directory screenshots and recordings are saved to, as
+                             <rom>-<frame>.png (default the current directory)
+  --record <file>            Record gameplay from the start to a .gif, .png (animated
+                             PNG) or .y4m file, or - for Y4M video on standard output
+  --record-format <format>   Format F9 records in: gif, apng or y4m (run only; default gif)
+  --keymap <keys|file>       16 host keys for CHIP-8 keys 0-F, or a keymap file (run and
+                             tui only; default X123QWEASDZC4RFV)
+
+Window keys:
+  F1 remaps keys and saves them for the ROM or as the default. F5 resets, Shift+F5 hard
+  resets, F6 pauses and resumes, F7 steps a frame while paused, F9 starts and stops
+  recording, F11 toggles fullscreen, F12 saves a screenshot, Shift+F12 saves a raw one with
+  one pixel per CHIP-8 pixel and no effects, and Esc quits. Drop a ROM file on the window to
+  load it in place of the running one.
+
+Terminal options (tui, which also takes --palette and --keymap):
+  --render <mode>            half to draw two pixels to a character in full colour, or
+                             braille to draw eight in one colour (default half)
+
+Terminal keys:
+  F5 resets, Shift+F5 hard resets, F6 pauses and resumes, F7 steps a frame while paused,
+  and Esc or Ctrl+C quits. Terminals that don't report key releases hold each key for a
+  moment after it was last pressed or repeated.
+
+Headless options:
+  --frames <n>               Number of 60 Hz frames to run (default 600)
+  --screenshot <n>           Save a screenshot after frame n; can be given more than once
+  --input <file>             Key presses to play back, one per line as
+                             `<frame> down <key>` or `<frame> up <key>`
+  --seed <n>                 Seed for random numbers, so runs repeat exactly (default 0)
+  --dump <item>[=<file>]     Write out at the end, to standard output or a file: screen as
+                             text (the default), screen-hash for the SHA-1 of its pixels,
+                             screen-png (file only), registers, or memory as a hex dump or
+                             raw bytes in a file; can be given more than once. Written even
+                             when the program faults
+
+Test options:
+  --update                   Write the screen to golden screen files that are missing or
+                             don't match, instead of failing
+  --database <file>          Program database to use ahead of the bundled one
+
+Selftest options:
+  --suite <dir>              Directory of the test scripts to run (default roms/selftest
+                             in the source tree)
+  --update                   Save the screens the emulator draws as the expected ones
+                             instead of failing; roms/expected.py makes the real ones
+
+Test scripts:
+  One statement per line, with # starting a comment:
+    rom <file>                          The ROM to test, relative to the script
+    rom <platform> <file>               A build of it to use on one platform instead
+    platform <name>, ips <n>,           Settings, otherwise taken from the program
+    quirk <name>=<bool>                 database or the ROM as for run
+    seed <n>                            Seed for random numbers (default 0)
+    platforms <name>...                 The platforms selftest runs the script on
+                                        (default all of them)
+    press <key> at frame <n>            Press a key at the start of frame n, counting
+      [for <n> frames]                  from 1, and hold it for one frame or n
+    run <n> frames                      Run the next n frames
+    expect screen matches <file>        Compare with a screen saved by
+                                        `headless --dump screen=<file>`
+    expect screen hash <sha1>           Compare with `headless --dump screen-hash`
+    expect <target> == <value>          Check v0-vf, i, pc, dt, st, mem[a] or mem[a..b]
+    expect <target> != <value>          against a number or a list like [1, 2, 0x3]
+
+Disassembly options:
+  --platform <name>          Platform whose instructions to decode
+";
+
+pub enum Command {
+    Run(RunOptions),
+    Info {
+        rom: PathBuf,
+        database: Option<PathBuf>,
+    },
+    Disasm {
+        rom: PathBuf,
+        platform: Platform,
+    },
+    Headless(HeadlessOptions),
+    Tui(TuiOptions),
+    Test(TestOptions),
+    Selftest(SelftestOptions),
+    Help,
+}
+
+pub struct EmulatorOptions {
+    pub rom: PathBuf,
+    pub platform: Option<Platform>,
+    pub timing: Option<Timing>,
+    /// The timing used when neither the command line nor the program database gives one.
+    pub default_timing: Timing,
+    pub quirks: Vec<(String, bool)>,
+    pub vip_interpreter: Option<PathBuf>,
+    pub vip_monitor: Option<PathBuf>,
+    pub database: Option<PathBuf>,
+    pub audio: Option<AudioOutput>,
+    pub tone: Tone,
+}
+
+impl EmulatorOptions {
+    pub fn new(rom: PathBuf) -> Self {
+        Self {
+            rom,
+            platform: None,
+            timing: None,
+            default_timing: Timing::Unlimited,
+            quirks: Vec::new(),
+            vip_interpreter: None,
+            vip_monitor: None,
+            database: None,
+            audio: None,
+            tone: Tone::default(),
+        }
+    }
+
+    /// Builds the settings for these options, filling in anything not given on the command line
+    /// from the ROM's database entry, or failing that from what was detected in the ROM. Quirks
+    /// from the database are the ones for the platform the ROM ends up running on. The backend
+    /// is left for the caller to load.
+    pub fn settings(
+        &self,
+        entry: Option<&Entry>,
+        detected: Option<&Analysis>,
+    ) -> Result<Settings, String> {
+        let mut settings = Settings {
+            timing: self.default_timing,
+            ..Settings::default()
+        };
+        if let Some(entry) = entry {
+            if let Some(platform) = entry.platform() {
+                settings.platform = platform;
+            }
+            if let Some(tickrate) = entry.tickrate() {
+                let rate = tickrate.checked_mul(60).ok_or_else(|| {
+                    format!("the database's tickrate of {} is too high", tickrate)
+                })?;
+                settings.timing = Timing::InstructionsPerSecond(rate);
+            }
+        } else if let Some(analysis) = detected.filter(|a| a.confidence >= Confidence::Medium) {
+            if let Some(platform) = analysis.family.platform() {
+                settings.platform = platform;
+            }
+            for (name, value) in &analysis.quirks {
+                settings.set_quirk(name, *value)?;
+            }
+        }
+        if let Some(platform) = self.platform {
+            settings.platform = platform;
+        }
+        if let Some(entry) = entry {
+            for (name, value) in entry.quirks(self.platform) {
+                settings.set_quirk(name, value)?;
+            }
+        }
+        if let Some(timing) = self.timing {
+            settings.timing = timing;
+        }
+        for (name, value) in &self.quirks {
+            settings.set_quirk(name, *value)?;
+        }
+        Ok(settings)
+    }
+}
+
+pub struct RunOptions {
+    pub emulator: EmulatorOptions,
+    pub scale: usize,
+    pub palette: Option<Palette>,
+    pub flicker: Flicker,
+    pub crt: Crt,
+    pub keymap: Option<Keymap>,
+    pub screenshot_dir: PathBuf,
+    pub record: Option<PathBuf>,
+    pub recording_format: Format,
+}
+
+pub struct TuiOptions {
+    pub emulator: EmulatorOptions,
+    pub palette: Option<Palette>,
+    pub keymap: Option<Keymap>,
+    pub render: Render,
+}
+
+pub struct TestOptions {
+    pub scripts: Vec<PathBuf>,
+    pub database: Option<PathBuf>,
+    pub update: bool,
+}
+
+pub struct SelftestOptions {
+    pub suite: PathBuf,
+    pub update: bool,
+}
+
+/// Where the conformance suite is kept in the source tree.
+const SELFTEST_SUITE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/roms/selftest");
+
+pub struct HeadlessOptions {
+    pub emulator: EmulatorOptions,
+    pub frames: u32,
+    /// The frames to save screenshots after, counting from 1.
+    pub screenshots: Vec<u32>,
+    pub screenshot_dir: PathBuf,
+    pub scale: usize,
+    pub palette: Option<Palette>,
+    pub crt: Crt,
+    pub record: Option<PathBuf>,
+    pub input: Option<Movie>,
+    pub seed: u64,
+    /// What to write out at the end, in order.
+    pub dumps: Vec<Dump>,
+}
+
+pub const DEFAULT_HEADLESS_IPS: u32 = 600;
+
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+    let mut args = args.into_iter();
+    let command = match args.next() {
+        Some(command) => command,
+        None => return Ok(Command::Help),
+    };
+    match command.as_str() {
+        "help" | "--help" | "-h" => return Ok(Command::Help),
+        "test" => return parse_test(args),
+        "selftest" => return parse_selftest(args),
+        "run" | "tui" | "info" | "disasm" | "headless" => {}
+        _ => return Err(format!("unknown command `{}`", command)),
+    }
+
+    let mut rom = None;
+    let mut options = Vec::new();
+    while let Some(arg) = args.next() {
+        if arg == "--vip-timing" {
+            options.push((arg, String::new()));
+        } else if arg.starts_with("--") {
+            let value = args
+                .next()
+                .ok_or_else(|| format!("`{}` needs a value", arg))?;
+            options.push((arg, value));
+        } else if rom.is_none() {
+            rom = Some(PathBuf::from(arg));
+        } else {
+            return Err(format!("unexpected argument `{}`", arg));
+        }
+    }
+    let rom = rom.ok_or_else(|| format!("`{}` needs a ROM file", command))?;
+
+    let mut emulator = EmulatorOptions::new(rom);
+    if command == "headless" {
+        emulator.default_timing = Timing::InstructionsPerSecond(DEFAULT_HEADLESS_IPS);
+    }
+    let mut scale = None;
+    let mut palette = None;
+    let mut flicker = Flicker::Off;
+    let mut crt = Crt::default();
+    let mut keymap = None;
+    let mut frames = 600;
+    let mut screenshots = Vec::new();
+    let mut screenshot_dir = PathBuf::from(".");
+    let mut record = None;
+    let mut recording_format = Format::Gif;
+    let mut input = None;
+    let mut render = Render::HalfBlock;
+    let mut seed = 0;
+    let mut dumps = Vec::new();
+    // Whether the emulator runs in real time, and whether it runs at all.
+    let live = command == "run" || command == "tui";
+    let emulates = live || command == "headless";
+    for (name, value) in options {
+        let allowed = match name.as_str() {
+            "--platform" => {
+                emulator.platform = Some(
+                    Platform::from_name(&value)
+                        .ok_or_else(|| format!("unknown platform `{}`", value))?,
+                );
+                true
+            }
+            "--ips" => {
+                emulator.timing = Some(Timing::InstructionsPerSecond(parse_number(&name, &value)?));
+                emulates
+            }
+            "--vip-timing" => {
+                emulator.timing = Some(Timing::CosmacVip);
+                live
+            }
+            "--quirk" => {
+                emulator.quirks.push(parse_quirk(&value)?);
+                emulates
+            }
+            "--vip-interpreter" => {
+                emulator.vip_interpreter = Some(PathBuf::from(value));
+                live
+            }
+            "--vip-monitor" => {
+                emulator.vip_monitor = Some(PathBuf::from(value));
+                live
+            }
+            "--audio" => {
+                emulator.audio = Some(AudioOutput::parse(&value)?);
+                emulates
+            }
+            "--pitch" => {
+                emulator.tone.pitch = parse_number(&name, &value)? as f32;
+                emulates
+            }
+            "--volume" => {
+                emulator.tone.volume = match value.parse() {
+                    Ok(volume) if (0.0..=1.0).contains(&volume) => volume,
+                    _ => return Err(format!("volume `{}` must be from 0 to 1", value)),
+                };
+                emulates
+            }
+            "--waveform" => {
+                emulator.tone.waveform = Waveform::from_name(&value)
+                    .ok_or_else(|| format!("unknown waveform `{}`", value))?;
+                emulates
+            }
+            "--scale" => {
+                scale = Some(parse_number(&name, &value)? as usize);
+                command == "run" || command == "headless"
+            }
+            "--palette" => {
+                palette = Some(Palette::parse(&value)?);
+                emulates
+            }
+            "--flicker" => {
+                flicker = Flicker::parse(&value)?;
+                command == "run"
+            }
+            "--crt" => {
+                crt = Crt::parse(&value)?;
+                command == "run" || command == "headless"
+            }
+            "--screenshot-dir" => {
+                screenshot_dir = PathBuf::from(value);
+                command == "run" || command == "headless"
+            }
+            "--record" => {
+                record = Some(PathBuf::from(value));
+                command == "run" || command == "headless"
+            }
+            "--record-format" => {
+                recording_format = Format::from_name(&value)
+                    .ok_or_else(|| format!("unknown recording format `{}`", value))?;
+                command == "run"
+            }
+            "--render" => {
+                render = Render::parse(&value)?;
+                command == "tui"
+            }
+            "--input" => {
+                input = Some(Movie::load(Path::new(&value))?);
+                command == "headless"
+            }
+            "--seed" => {
+                seed = value
+                    .parse()
+                    .map_err(|_| format!("`--seed` must be a number, not `{}`", value))?;
+                command == "headless"
+            }
+            "--dump" => {
+                dumps.push(Dump::parse(&value)?);
+                command == "headless"
+            }
+            "--screenshot" => {
+                screenshots.push(parse_number(&name, &value)?);
+                command == "headless"
+            }
+            "--keymap" => {
+                let path = Path::new(&value);
+                keymap = Some(if path.is_file() {
+                    Keymap::load(path)?
+                } else {
+                    Keymap::from_layout(&value)?
+                });
+                live
+            }
+            "--database" => {
+                emulator.database = Some(PathBuf::from(value));
+                command != "disasm"
+            }
+            "--frames" => {
+                frames = parse_number(&name, &value)?;
+                command == "headless"
+            }
+            _ => return Err(format!("unknown option `{}`", name)),
+        };
+        if !allowed {
+            return Err(format!("`{}` cannot be used with `{}`", name, command));
+        }
+    }
+
+    if let Some(&last) = screenshots.iter().max().filter(|&&last| last > frames) {
+        return Err(format!(
+            "cannot take a screenshot at frame {} of {}",
+            last, frames
+        ));
+    }
+
+    let to_stdout = record.as_deref() == Some(Path::new("-"));
+    if to_stdout && dumps.iter().any(|dump| dump.path.is_none()) {
+        return Err("cannot dump to standard output while recording to it".to_string());
+    }
+    // Print the screen unless asked for something else, or standard output has the video.
+    if dumps.is_empty() && !to_stdout {
+        dumps.push(Dump {
+            item: Item::Screen,
+            path: None,
+        });
+    }
+
+    match command.as_str() {
+        "run" => Ok(Command::Run(RunOptions {
+            emulator,
+            scale: scale.unwrap_or(12),
+            palette,
+            flicker,
+            crt,
+            keymap,
+            screenshot_dir,
+            record,
+            recording_format,
+        })),
+        "tui" => Ok(Command::Tui(TuiOptions {
+            emulator,
+            palette,
+            keymap,
+            render,
+        })),
+        "info" => Ok(Command::Info {
+            rom: emulator.rom,
+            database: emulator.database,
+        }),
+        "disasm" => Ok(Command::Disasm {
+            rom: emulator.rom,
+            platform: emulator.platform.unwrap_or(Platform::Chip8),
+        }),
+        _ => Ok(Command::Headless(HeadlessOptions {
+            emulator,
+            frames,
+            screenshots,
+            screenshot_dir,
+            scale: scale.unwrap_or(1),
+            palette,
+            crt,
+            record,
+            input,
+            seed,
+            dumps,
+        })),
+    }
+}
+
+fn parse_test(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut options = TestOptions {
+        scripts: Vec::new(),
+        database: None,
+        update: false,
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--update" => options.update = true,
+            "--database" => {
+                let value = args.next().ok_or("`--database` needs a value")?;
+                options.database = Some(PathBuf::from(value));
+            }
+            _ if arg.starts_with("--") => {
+                return Err(format!("`{}` cannot be used with `test`", arg))
+            }
+            _ => options.scripts.push(PathBuf::from(arg)),
+        }
+    }
+    if options.scripts.is_empty() {
+        return Err("`test` needs at least one script".to_string());
+    }
+    Ok(Command::Test(options))
+}
+
+fn parse_selftest(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut options = SelftestOptions {
+        suite: PathBuf::from(SELFTEST_SUITE),
+        update: false,
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--update" => options.update = true,
+            "--suite" => {
+                let value = args.next().ok_or("`--suite` needs a value")?;
+                options.suite = PathBuf::from(value);
+            }
+            _ if arg.starts_with("--") => {
+                return Err(format!("`{}` cannot be used with `selftest`", arg))
+            }
+            _ => return Err(format!("unexpected argument `{}`", arg)),
+        }
+    }
+    Ok(Command::Selftest(options))
+}
+
+fn parse_number(name: &str, value: &str) -> Result<u32, String> {
+    match value.parse() {
+        Ok(number) if number > 0 => Ok(number),
+        _ => Err(format!(
+            "`{}` must be a positive number, not `{}`",
+            name, value
+        )),
+    }
+}
+
+fn parse_quirk(value: &str) -> Result<(String, bool), String> {
+    let (name, setting) = value
+        .split_once('=')
+        .ok_or_else(|| format!("quirk `{}` must look like name=true", value))?;
+    let setting = match setting {
+        "true" | "on" | "1" => true,
+        "false" | "off" | "0" => false,
+        _ => return Err(format!("quirk value `{}` must be true or false", setting)),
+    };
+    if !Settings::QUIRKS.contains(&name) {
+        return Err(format!("unknown quirk `{}`", name));
+    }
+    Ok((name.to_string(), setting))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_args(args: &str) -> Result<Command, String> {
+        parse(args.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn keeps_vip_timing_to_live_runs() {
+        for args in [
+            "headless rom.ch8 --vip-timing",
+            "headless rom.ch8 --vip-interpreter vip.bin",
+        ] {
+            let error = parse_args(args).err().unwrap();
+            assert!(
+                error.ends_with("cannot be used with `headless`"),
+                "{}",
+                error
+            );
+        }
+        assert!(parse_args("tui rom.ch8 --vip-timing").is_ok());
+    }
+
+    #[test]
+    fn runs_headless_at_a_fixed_rate_unless_told_otherwise() {
+        let Ok(Command::Headless(options)) = parse_args("headless rom.ch8") else {
+            panic!("not a headless run");
+        };
+        let settings = options.emulator.settings(None, None).unwrap();
+        let rate = Timing::InstructionsPerSecond(DEFAULT_HEADLESS_IPS);
+        assert_eq!(settings.timing, rate);
+
+        let Ok(Command::Headless(options)) = parse_args("headless rom.ch8 --ips 1200") else {
+            panic!("not a headless run");
+        };
+        let settings = options.emulator.settings(None, None).unwrap();
+        assert_eq!(settings.timing, Timing::InstructionsPerSecond(1200));
+
+        let Ok(Command::Run(options)) = parse_args("run rom.ch8") else {
+            panic!("not a window run");
+        };
+        let settings = options.emulator.settings(None, None).unwrap();
+        assert_eq!(settings.timing, Timing::Unlimited);
+    }
+}
@@ -0,0 +1,88 @@
+use std::path::Path;
+
+use eframe::epaint::ColorImage;
+
+use crate::{
+    audio::AudioOutput,
+    chip8::Chip8,
+    cli::HeadlessOptions,
+    crt::Crt,
+    palette::Palette,
+    recording::{self, Recorder},
+    screenshot::{self, Screenshots},
+};
+
+use super::{frame_by_frame, load_database, load_settings, open_audio, read_file};
+
+/// Runs a ROM for a fixed number of frames without a window, then writes out the dumps.
+pub fn run(options: HeadlessOptions) -> Result<(), String> {
+    let program = read_file(&options.emulator.rom)?;
+    let database = load_database(options.emulator.database.as_deref())?;
+    let entry = database.lookup(&program);
+    let settings = load_settings(&options.emulator, entry.as_ref(), &program)?;
+    let palette = options
+        .palette
+        .or_else(|| entry.as_ref().and_then(|entry| entry.palette()))
+        .unwrap_or_default();
+    let screenshots = Screenshots {
+        directory: options.screenshot_dir,
+        name: screenshot::rom_name(&options.emulator.rom),
+        scale: options.scale,
+        recording_format: recording::Format::Gif,
+    };
+    let mut recorder = options.record.as_deref().map(Recorder::new).transpose()?;
+    let (beeper, _audio_output) = open_audio(&options.emulator, AudioOutput::None)?;
+    let (mut chip8, event_sender, instructions_per_frame) = frame_by_frame(settings, &program)?;
+    chip8.set_beeper(beeper);
+    chip8.set_seed(options.seed);
+    let mut fault = None;
+    let mut last = options.frames;
+    for number in 1..=options.frames {
+        if let Some(movie) = &options.input {
+            for event in movie.events_at(number) {
+                let _ = event_sender.send(event);
+            }
+        }
+        if let Err(error) = chip8.try_run_frame(instructions_per_frame) {
+            fault = Some(format!(
+                "the program faulted in frame {}: {}",
+                number, error
+            ));
+            last = number;
+            break;
+        }
+        chip8.play_sound();
+        if recorder.is_none() && !options.screenshots.contains(&number) {
+            continue;
+        }
+        let image = picture(&chip8, &palette, &options.crt, screenshots.scale);
+        if options.screenshots.contains(&number) {
+            let path = screenshots.path(number as u64, false);
+            screenshot::save_png(&image, &path)?;
+            eprintln!("Saved {}", path.display());
+        }
+        if let Some(recorder) = &mut recorder {
+            recorder.add(image, number as u64)?;
+        }
+    }
+    if let Some(recorder) = recorder {
+        let path = recorder.path().to_path_buf();
+        recorder.finish(last as u64)?;
+        if path != Path::new("-") {
+            eprintln!("Saved {}", path.display());
+        }
+    }
+    for dump in &options.dumps {
+        dump.write(&chip8, || {
+            picture(&chip8, &palette, &options.crt, screenshots.scale)
+        })?;
+    }
+    fault.map_or(Ok(()), Err)
+}
+
+/// The display as a screenshot would show it.
+fn picture(chip8: &Chip8, palette: &Palette, crt: &Crt, scale: usize) -> ColorImage {
+    let frame = chip8.screen().frame(0);
+    let pixels = screenshot::raw(&frame, palette).pixels;
+    screenshot::render(pixels, [frame.width, frame.height], crt, scale)
+}
@@ -0,0 +1,62 @@
+use std::path::Path;
+
+use crate::{
+    chip8::{
+        analysis::{self, Analysis},
+        disassembler,
+        platform::Platform,
+    },
+    database,
+};
+
+use super::{load_database, read_file};
+
+/// Prints what the database knows about a ROM and what analysing it finds.
+pub fn info(rom: &Path, database: Option<&Path>) -> Result<(), String> {
+    let program = read_file(rom)?;
+    let database = load_database(database)?;
+    println!("File:  {}", rom.display());
+    println!("Size:  {} bytes", program.len());
+    println!("SHA-1: {}", database::sha1_hex(&program));
+    match database.lookup(&program) {
+        Some(entry) => {
+            println!("Title: {}", entry.program().title);
+            if !entry.program().authors.is_empty() {
+                println!("By:    {}", entry.program().authors.join(", "));
+            }
+            if let Some(platform) = entry.platform() {
+                println!("Platform: {}", platform.name());
+            }
+        }
+        None => println!("Not in the program database"),
+    }
+    print_analysis(&analysis::analyse(&program));
+    Ok(())
+}
+
+fn print_analysis(analysis: &Analysis) {
+    println!(
+        "Detected: {} ({} confidence)",
+        analysis.family.name(),
+        analysis.confidence
+    );
+    for clue in &analysis.evidence {
+        println!("  {}", clue);
+    }
+    let quirks: Vec<String> = analysis
+        .quirks
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect();
+    println!("Quirks:   {}", quirks.join(" "));
+}
+
+/// Prints each instruction in a ROM with its address and bytes.
+pub fn disasm(rom: &Path, platform: Platform) -> Result<(), String> {
+    let program = read_file(rom)?;
+    for line in disassembler::disassemble(&program, platform) {
+        let bytes: String = line.bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        println!("{:04x}  {:<8}  {}", line.address, bytes, line.text);
+    }
+    Ok(())
+}
@@ -0,0 +1,181 @@
+pub mod headless;
+pub mod info;
+pub mod scripts;
+pub mod terminal;
+pub mod window;
+
+use std::{
+    fs::File,
+    io::{BufReader, Read},
+    path::Path,
+    thread::{self, JoinHandle},
+};
+
+use crossbeam_channel::{unbounded, Sender};
+
+use crate::{
+    app::Channels,
+    audio::{self, AudioOutput, NativeOutput, WavSink},
+    chip8::{
+        analysis,
+        audio::{AudioSink, Beeper, NullSink},
+        display,
+        keypad::Event,
+        settings::{Backend, Settings, Timing},
+        Chip8,
+    },
+    cli::EmulatorOptions,
+    config,
+    database::{self, Database},
+};
+
+/// Runs the emulator on its own thread, returning the frontend's ends of the channels to it.
+pub(super) fn start_emulator(
+    settings: Settings,
+    program: &[u8],
+    beeper: Beeper,
+) -> Result<(Channels, JoinHandle<()>), String> {
+    let (frame_sender, frame_receiver) = display::frame_slot();
+    let (event_sender, event_receiver) = unbounded();
+    let (control_sender, control_receiver) = unbounded();
+    let (status_sender, status_receiver) = unbounded();
+    let mut chip8 = Chip8::new(
+        settings,
+        program,
+        frame_sender,
+        event_receiver,
+        control_receiver,
+        status_sender,
+    )?;
+    chip8.set_beeper(beeper);
+    let emulator = thread::spawn(move || chip8.run());
+    let channels = Channels {
+        frames: frame_receiver,
+        events: event_sender,
+        controls: control_sender,
+        statuses: status_receiver,
+    };
+    Ok((channels, emulator))
+}
+
+/// Sets up a machine to be run a frame at a time on this thread, returning it with the sender
+/// for its key events and the instructions to run each frame. Only a fixed instruction rate
+/// can be run that way, since the other timings and the COSMAC VIP backend keep their own time.
+pub(super) fn frame_by_frame(
+    settings: Settings,
+    program: &[u8],
+) -> Result<(Chip8, Sender<Event>, u32), String> {
+    let instructions_per_frame = match settings.timing {
+        Timing::InstructionsPerSecond(rate) => (rate / 60).max(1),
+        Timing::Unlimited => {
+            return Err("running a frame at a time needs an instruction rate".to_string())
+        }
+        Timing::CosmacVip => {
+            return Err("COSMAC VIP timing cannot be run a frame at a time".to_string())
+        }
+    };
+    if let Backend::CosmacVip { .. } = settings.backend {
+        return Err("the COSMAC VIP backend cannot be run a frame at a time".to_string());
+    }
+    // Nothing listens for frames, statuses or controls, which the machine doesn't mind.
+    let (frame_sender, _) = display::frame_slot();
+    let (event_sender, event_receiver) = unbounded();
+    let (_, control_receiver) = unbounded();
+    let (status_sender, _) = unbounded();
+    let chip8 = Chip8::new(
+        settings,
+        program,
+        frame_sender,
+        event_receiver,
+        control_receiver,
+        status_sender,
+    )?;
+    Ok((chip8, event_sender, instructions_per_frame))
+}
+
+/// Opens where the emulator's sound goes, or `default` if the options don't say. The audio
+/// device stays open for as long as the returned output is kept.
+pub(super) fn open_audio(
+    options: &EmulatorOptions,
+    default: AudioOutput,
+) -> Result<(Beeper, Option<NativeOutput>), String> {
+    let (sink, output): (Box<dyn AudioSink>, _) = match options.audio.clone().unwrap_or(default) {
+        AudioOutput::None => (Box::new(NullSink), None),
+        AudioOutput::Wav(path) => (Box::new(WavSink::create(&path)?), None),
+        AudioOutput::Native => match audio::open_native() {
+            Ok((sink, output)) => (sink, Some(output)),
+            // Carry on without sound unless it was asked for.
+            Err(error) if options.audio.is_none() => {
+                eprintln!("warning: no sound: {}", error);
+                (Box::new(NullSink), None)
+            }
+            Err(error) => return Err(error),
+        },
+    };
+    Ok((Beeper::new(options.tone, sink), output))
+}
+
+/// Loads the bundled program database, then the one in the config directory, then `path`, with
+/// later files taking precedence.
+pub(super) fn load_database(path: Option<&Path>) -> Result<Database, String> {
+    let mut database = Database::bundled();
+    if let Some(user_file) = config::config_dir().map(|dir| dir.join(database::USER_DATABASE_FILE))
+    {
+        if user_file.exists() {
+            database.load_override(&user_file)?;
+        }
+    }
+    if let Some(path) = path {
+        database.load_override(path)?;
+    }
+    Ok(database)
+}
+
+pub(super) fn load_settings(
+    options: &EmulatorOptions,
+    entry: Option<&database::Entry>,
+    program: &[u8],
+) -> Result<Settings, String> {
+    let detected = match entry {
+        Some(entry) => {
+            log::info!("Found {} in the program database", entry.program().title);
+            None
+        }
+        None => Some(analysis::analyse(program)),
+    };
+    if let Some(analysis) = &detected {
+        log::info!(
+            "Looks like a {} program ({} confidence)",
+            analysis.family.name(),
+            analysis.confidence
+        );
+        if analysis.family.platform().is_none() && options.platform.is_none() {
+            eprintln!(
+                "warning: this looks like a {} program, which may not run correctly",
+                analysis.family.name()
+            );
+        }
+    }
+    let mut settings = options.settings(entry, detected.as_ref())?;
+    if let Some(interpreter) = &options.vip_interpreter {
+        let monitor = match &options.vip_monitor {
+            Some(monitor) => read_file(monitor)?,
+            None => Vec::new(),
+        };
+        settings.backend = Backend::CosmacVip {
+            interpreter: read_file(interpreter)?.into_boxed_slice(),
+            monitor: monitor.into_boxed_slice(),
+        };
+    }
+    Ok(settings)
+}
+
+pub(super) fn read_file(path: &Path) -> Result<Vec<u8>, String> {
+    let error = |error: std::io::Error| format!("could not read {}: {}", path.display(), error);
+    let file = File::open(path).map_err(error)?;
+    let mut reader = BufReader::new(file);
+    let mut contents = Vec::new();
+
+    reader.read_to_end(&mut contents).map_err(error)?;
+    Ok(contents)
+}
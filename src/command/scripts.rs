@@ -0,0 +1,138 @@
+use std::{
+    any::Any,
+    fs, panic,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    chip8::{platform::Platform, settings::Timing},
+    cli::{self, EmulatorOptions, SelftestOptions, TestOptions},
+    database::Database,
+    script::{self, Script},
+};
+
+use super::{frame_by_frame, load_database, load_settings, read_file};
+
+/// Runs the test scripts named on the command line.
+pub fn test(options: TestOptions) -> Result<(), String> {
+    let database = load_database(options.database.as_deref())?;
+    let runs: Vec<(PathBuf, Option<Platform>)> = options
+        .scripts
+        .into_iter()
+        .map(|path| (path, None))
+        .collect();
+    run_scripts(&runs, &database, options.update)
+}
+
+/// Runs every script in the conformance suite on each of the platforms it is for.
+pub fn selftest(options: SelftestOptions) -> Result<(), String> {
+    let database = load_database(None)?;
+    let error =
+        |error: std::io::Error| format!("could not read {}: {}", options.suite.display(), error);
+    let mut scripts: Vec<PathBuf> = fs::read_dir(&options.suite)
+        .map_err(error)?
+        .map(|entry| entry.map(|entry| entry.path()).map_err(error))
+        .collect::<Result<_, _>>()?;
+    scripts.retain(|path| {
+        path.extension()
+            .is_some_and(|extension| extension == "test")
+    });
+    scripts.sort();
+    if scripts.is_empty() {
+        return Err(format!(
+            "there are no .test scripts in {}",
+            options.suite.display()
+        ));
+    }
+    let mut runs = Vec::new();
+    for path in scripts {
+        // A script that can't be read fails once rather than once for each platform.
+        let platforms = match Script::load(&path) {
+            Ok(script) => script.platforms.into_iter().map(Some).collect(),
+            Err(_) => vec![None],
+        };
+        runs.extend(
+            platforms
+                .into_iter()
+                .map(|platform| (path.clone(), platform)),
+        );
+    }
+    run_scripts(&runs, &database, options.update)
+}
+
+/// Runs test scripts, on the given platform in place of the script's own if there is one,
+/// printing how each went. Fails if any of them do.
+fn run_scripts(
+    runs: &[(PathBuf, Option<Platform>)],
+    database: &Database,
+    update: bool,
+) -> Result<(), String> {
+    let mut failed = 0;
+    for (path, platform) in runs {
+        let name = match platform {
+            Some(platform) => format!("{} on {}", path.display(), platform.name()),
+            None => path.display().to_string(),
+        };
+        // A script that panics outside the machine, which reports its own faults, fails on
+        // its own rather than ending the whole run.
+        let outcome = panic::catch_unwind(|| run_script(path, *platform, database, update))
+            .unwrap_or_else(|payload| {
+                Err(format!("the run panicked: {}", panic_message(&*payload)))
+            });
+        match outcome {
+            Ok(outcome) if outcome.failures.is_empty() => {
+                println!("PASS  {} ({} checks)", name, outcome.checks);
+            }
+            Ok(outcome) => {
+                failed += 1;
+                println!("FAIL  {}", name);
+                for failure in outcome.failures {
+                    println!("  {}", failure.replace('\n', "\n    "));
+                }
+            }
+            Err(error) => {
+                failed += 1;
+                println!("FAIL  {}\n  {}", name, error);
+            }
+        }
+    }
+    let total = runs.len();
+    println!("{} passed, {} failed", total - failed, failed);
+    if failed > 0 {
+        Err(format!("{} of {} scripts failed", failed, total))
+    } else {
+        Ok(())
+    }
+}
+
+/// Loads a test script and runs it, on `platform` in place of the script's own if given.
+fn run_script(
+    path: &Path,
+    platform: Option<Platform>,
+    database: &Database,
+    update: bool,
+) -> Result<script::Outcome, String> {
+    let script = Script::load(path)?;
+    let rom = script.rom(platform);
+    let mut emulator = EmulatorOptions::new(rom.to_path_buf());
+    emulator.default_timing = Timing::InstructionsPerSecond(cli::DEFAULT_HEADLESS_IPS);
+    emulator.platform = platform.or(script.platform);
+    emulator.timing = script.ips.map(Timing::InstructionsPerSecond);
+    emulator.quirks = script.quirks.clone();
+    let program = read_file(rom)?;
+    let settings = load_settings(&emulator, database.lookup(&program).as_ref(), &program)?;
+    let (mut chip8, events, instructions_per_frame) = frame_by_frame(settings, &program)?;
+    chip8.set_seed(script.seed);
+    Ok(script.run(&mut chip8, &events, instructions_per_frame, update))
+}
+
+/// The message a panic was raised with.
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown error"
+    }
+}
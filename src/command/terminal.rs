@@ -0,0 +1,28 @@
+use crate::{audio::AudioOutput, cli::TuiOptions, keymap::RomKeymap, screenshot, tui};
+
+use super::{load_database, load_settings, open_audio, read_file, start_emulator};
+
+/// Runs a ROM in the terminal until the user quits.
+pub fn run(options: TuiOptions) -> Result<(), String> {
+    let program = read_file(&options.emulator.rom)?;
+    let database = load_database(options.emulator.database.as_deref())?;
+    let entry = database.lookup(&program);
+    let settings = load_settings(&options.emulator, entry.as_ref(), &program)?;
+    let palette = options
+        .palette
+        .or_else(|| entry.as_ref().and_then(|entry| entry.palette()))
+        .unwrap_or_default();
+    let keymap = match options.keymap {
+        Some(keymap) => keymap,
+        None => RomKeymap::load(&program, entry.as_ref())?.keymap,
+    };
+    // Over SSH the sound would play on the remote machine, so only the bell sounds by default.
+    let (beeper, _audio_output) = open_audio(&options.emulator, AudioOutput::None)?;
+    let (channels, emulator) = start_emulator(settings, &program, beeper)?;
+    let name = screenshot::rom_name(&options.emulator.rom);
+    let result = tui::run(channels, palette, keymap, options.render, name);
+    emulator
+        .join()
+        .map_err(|_| "the emulator thread panicked".to_string())?;
+    result
+}
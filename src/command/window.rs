@@ -0,0 +1,80 @@
+use eframe::egui;
+
+use crate::{
+    app::MyApp,
+    audio::AudioOutput,
+    cli::RunOptions,
+    keymap::{self, RomKeymap},
+    recording::Recorder,
+    screen::Screen,
+    screenshot::{self, Screenshots},
+};
+
+use super::{load_database, load_settings, open_audio, read_file, start_emulator};
+
+/// Runs a ROM in a window until it is closed.
+pub fn run(options: RunOptions) -> Result<(), String> {
+    let program = read_file(&options.emulator.rom)?;
+    let database = load_database(options.emulator.database.as_deref())?;
+    let entry = database.lookup(&program);
+    let settings = load_settings(&options.emulator, entry.as_ref(), &program)?;
+    let palette = options
+        .palette
+        .or_else(|| entry.as_ref().and_then(|entry| entry.palette()))
+        .unwrap_or_default();
+    let keymap = match options.keymap {
+        Some(keymap) => RomKeymap {
+            keymap,
+            path: keymap::rom_keymap_path(&program),
+        },
+        None => RomKeymap::load(&program, entry.as_ref())?,
+    };
+
+    let (width, height) = settings.platform.display_size();
+    let scale = options.scale as f32;
+    let window_options = eframe::NativeOptions {
+        initial_window_size: Some(egui::vec2(
+            (width as f32 * scale + 16.0).max(400.0),
+            (height as f32 * scale + 64.0).max(300.0),
+        )),
+        ..Default::default()
+    };
+
+    let default_audio = if cfg!(feature = "native-audio") {
+        AudioOutput::Native
+    } else {
+        AudioOutput::None
+    };
+    let (beeper, _audio_output) = open_audio(&options.emulator, default_audio)?;
+    let (channels, emulator) = start_emulator(settings, &program, beeper)?;
+
+    let mut screen = Screen::new();
+    screen.flicker = options.flicker;
+    screen.crt = options.crt;
+    let screenshots = Screenshots {
+        directory: options.screenshot_dir,
+        name: screenshot::rom_name(&options.emulator.rom),
+        scale: options.scale,
+        recording_format: options.recording_format,
+    };
+    let recorder = options.record.as_deref().map(Recorder::new).transpose()?;
+    eframe::run_native(
+        "Chip8 Emulator",
+        window_options,
+        Box::new(move |_cc| {
+            Box::new(MyApp::new(
+                channels,
+                palette,
+                screen,
+                keymap,
+                database,
+                screenshots,
+                recorder,
+            ))
+        }),
+    )
+    .map_err(|error| error.to_string())?;
+    emulator
+        .join()
+        .map_err(|_| "the emulator thread panicked".to_string())
+}
@@ -0,0 +1,10 @@
+use std::{env, path::PathBuf};
+
+/// The directory user configuration is kept in, such as `~/.config/chip8`.
+pub fn config_dir() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .or_else(|| env::var_os("APPDATA"))
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("chip8"))
+}
@@ -0,0 +1,347 @@
+use eframe::epaint::{Color32, ColorImage};
+
+/// One post-processing effect, which keeps its strength while switched off.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Effect {
+    pub enabled: bool,
+    pub strength: f32,
+}
+
+impl Effect {
+    const fn off(strength: f32) -> Self {
+        Self {
+            enabled: false,
+            strength,
+        }
+    }
+
+    /// The strength when enabled, otherwise none.
+    fn amount(self) -> f32 {
+        if self.enabled {
+            self.strength
+        } else {
+            0.0
+        }
+    }
+}
+
+/// A CRT look drawn on the CPU, so it works the same with egui's software renderer.
+///
+/// The picture is scaled up by a whole number first so the effects have room to work within
+/// each emulated pixel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Crt {
+    /// Darkens the lower part of each pixel row, like the gaps between a CRT's scan lines.
+    pub scanlines: Effect,
+    /// Darkens the edges of every pixel, like an LCD's pixel grid.
+    pub grid: Effect,
+    /// Lets lit pixels glow onto their neighbours.
+    pub bloom: Effect,
+    /// Bows the picture out like the glass of a tube.
+    pub curvature: Effect,
+    /// Darkens the corners.
+    pub vignette: Effect,
+}
+
+impl Default for Crt {
+    fn default() -> Self {
+        Self {
+            scanlines: Effect::off(0.4),
+            grid: Effect::off(0.25),
+            bloom: Effect::off(0.35),
+            curvature: Effect::off(0.1),
+            vignette: Effect::off(0.35),
+        }
+    }
+}
+
+/// The widest the scaled-up picture is made, which bounds the work done each frame.
+const MAX_WIDTH: usize = 768;
+
+impl Crt {
+    pub const NAMES: [&'static str; 5] = ["scanlines", "grid", "bloom", "curvature", "vignette"];
+
+    pub fn effect_mut(&mut self, name: &str) -> Option<&mut Effect> {
+        match name {
+            "scanlines" => Some(&mut self.scanlines),
+            "grid" => Some(&mut self.grid),
+            "bloom" => Some(&mut self.bloom),
+            "curvature" => Some(&mut self.curvature),
+            "vignette" => Some(&mut self.vignette),
+            _ => None,
+        }
+    }
+
+    /// The largest strength an effect goes up to.
+    pub fn max_strength(name: &str) -> f32 {
+        match name {
+            "curvature" => 0.3,
+            _ => 1.0,
+        }
+    }
+
+    /// Parses effects to switch on separated by commas, each optionally followed by `=` and
+    /// its strength, as in `scanlines,bloom=0.5`.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let mut crt = Self::default();
+        for item in value.split(',') {
+            let (name, strength) = match item.split_once('=') {
+                Some((name, strength)) => (name, Some(strength)),
+                None => (item, None),
+            };
+            let max = Self::max_strength(name);
+            let effect = crt.effect_mut(name).ok_or_else(|| {
+                format!(
+                    "unknown CRT effect `{}`, expected one of {}",
+                    name,
+                    Self::NAMES.join(", ")
+                )
+            })?;
+            effect.enabled = true;
+            if let Some(strength) = strength {
+                effect.strength = match strength.parse() {
+                    Ok(strength) if (0.0..=max).contains(&strength) => strength,
+                    _ => {
+                        return Err(format!(
+                            "{} strength `{}` must be from 0 to {}",
+                            name, strength, max
+                        ))
+                    }
+                };
+            }
+        }
+        Ok(crt)
+    }
+
+    pub fn is_active(&self) -> bool {
+        [
+            self.scanlines,
+            self.grid,
+            self.bloom,
+            self.curvature,
+            self.vignette,
+        ]
+        .iter()
+        .any(|effect| effect.enabled)
+    }
+
+    /// Applies the effects to a picture of `size` pixels, giving a larger image.
+    pub fn apply(&self, pixels: &[Color32], size: [usize; 2]) -> ColorImage {
+        let [width, height] = size;
+        let scale = (MAX_WIDTH / width.max(1)).clamp(2, 8);
+        let out = [width * scale, height * scale];
+
+        let mut image: Vec<[f32; 3]> = Vec::with_capacity(out[0] * out[1]);
+        for y in 0..out[1] {
+            let row = &pixels[y / scale * width..][..width];
+            for x in 0..out[0] {
+                let colour = row[x / scale];
+                image.push([colour.r(), colour.g(), colour.b()].map(|c| c as f32 / 255.0));
+            }
+        }
+        let glow = self.bloom.enabled.then(|| blur(&image, out, scale));
+
+        let scanlines = self.scanlines.amount();
+        let grid = self.grid.amount();
+        if scanlines > 0.0 || grid > 0.0 {
+            for (i, pixel) in image.iter_mut().enumerate() {
+                let (x, y) = (i % out[0] % scale, i / out[0] % scale);
+                let mut shade = 1.0;
+                if y >= scale.div_ceil(2) {
+                    shade -= scanlines;
+                }
+                if x == scale - 1 || y == scale - 1 {
+                    shade *= 1.0 - grid;
+                }
+                *pixel = pixel.map(|c| c * shade);
+            }
+        }
+        if let Some(glow) = glow {
+            let bloom = self.bloom.strength;
+            for (pixel, glow) in image.iter_mut().zip(glow) {
+                for (c, g) in pixel.iter_mut().zip(glow) {
+                    *c += g * bloom;
+                }
+            }
+        }
+
+        let curvature = self.curvature.amount();
+        let vignette = self.vignette.amount();
+        let half = [out[0] as f32 / 2.0, out[1] as f32 / 2.0];
+        let mut result = Vec::with_capacity(image.len());
+        for y in 0..out[1] {
+            for x in 0..out[0] {
+                // Positions from -1 to 1 across the picture.
+                let u = (x as f32 + 0.5) / half[0] - 1.0;
+                let v = (y as f32 + 0.5) / half[1] - 1.0;
+                let (su, sv) = (u * (1.0 + curvature * v * v), v * (1.0 + curvature * u * u));
+                if su.abs() > 1.0 || sv.abs() > 1.0 {
+                    result.push(Color32::BLACK);
+                    continue;
+                }
+                let sx = (((su + 1.0) * half[0]) as usize).min(out[0] - 1);
+                let sy = (((sv + 1.0) * half[1]) as usize).min(out[1] - 1);
+                let shade = (1.0 - vignette * (u * u + v * v) / 2.0).max(0.0);
+                let [r, g, b] = image[sy * out[0] + sx].map(|c| (c * shade).clamp(0.0, 1.0));
+                result.push(Color32::from_rgb(
+                    (r * 255.0).round() as u8,
+                    (g * 255.0).round() as u8,
+                    (b * 255.0).round() as u8,
+                ));
+            }
+        }
+        ColorImage {
+            size: out,
+            pixels: result,
+        }
+    }
+}
+
+/// A box blur `radius` pixels each way, done across then down.
+fn blur(image: &[[f32; 3]], size: [usize; 2], radius: usize) -> Vec<[f32; 3]> {
+    let [width, height] = size;
+    let across = blur_lines(image, width, height, 1, width, radius);
+    blur_lines(&across, height, width, width, 1, radius)
+}
+
+/// Blurs `lines` lines of `length` pixels, with `step` between the pixels of a line and
+/// `stride` between the starts of lines, keeping a running sum over each window.
+fn blur_lines(
+    image: &[[f32; 3]],
+    length: usize,
+    lines: usize,
+    step: usize,
+    stride: usize,
+    radius: usize,
+) -> Vec<[f32; 3]> {
+    let mut result = vec![[0.0; 3]; image.len()];
+    let window = (2 * radius + 1) as f32;
+    for line in 0..lines {
+        let at = |i: usize| line * stride + i * step;
+        let mut sum = [0.0; 3];
+        for i in 0..radius.min(length) {
+            add(&mut sum, image[at(i)], 1.0);
+        }
+        for i in 0..length {
+            if i + radius < length {
+                add(&mut sum, image[at(i + radius)], 1.0);
+            }
+            if i > radius {
+                add(&mut sum, image[at(i - radius - 1)], -1.0);
+            }
+            result[at(i)] = sum.map(|c| c / window);
+        }
+    }
+    result
+}
+
+fn add(sum: &mut [f32; 3], pixel: [f32; 3], sign: f32) {
+    for (s, c) in sum.iter_mut().zip(pixel) {
+        *s += c * sign;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, path::PathBuf};
+
+    use super::*;
+    use crate::screenshot;
+
+    const SIZE: [usize; 2] = [16, 8];
+
+    /// A border, a diagonal and a block, in white, grey and red on black.
+    fn picture() -> Vec<Color32> {
+        let grey = Color32::from_rgb(0x80, 0x80, 0x80);
+        let red = Color32::from_rgb(0xFF, 0x20, 0x20);
+        (0..SIZE[0] * SIZE[1])
+            .map(|i| {
+                let (x, y) = (i % SIZE[0], i / SIZE[0]);
+                if x == 0 || y == 0 || x == SIZE[0] - 1 || y == SIZE[1] - 1 {
+                    Color32::WHITE
+                } else if x == y + 2 {
+                    grey
+                } else if (10..13).contains(&x) && (3..5).contains(&y) {
+                    red
+                } else {
+                    Color32::BLACK
+                }
+            })
+            .collect()
+    }
+
+    /// Compares the picture drawn with `effects` with its golden image, writing the golden
+    /// instead when `UPDATE_GOLDENS` is set.
+    fn check_golden(effects: &str, name: &str) {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("testdata/crt")
+            .join(format!("{}.png", name));
+        let image = Crt::parse(effects).unwrap().apply(&picture(), SIZE);
+        if std::env::var_os("UPDATE_GOLDENS").is_some() {
+            screenshot::save_png(&image, &path).unwrap();
+            return;
+        }
+        let decoder = png::Decoder::new(File::open(&path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data).unwrap();
+        assert_eq!([info.width as usize, info.height as usize], image.size);
+        let expected = data.chunks(3).map(|p| Color32::from_rgb(p[0], p[1], p[2]));
+        for (index, (actual, expected)) in image.pixels.iter().zip(expected).enumerate() {
+            // Allow for rounding differences in the floating point maths.
+            let close = actual
+                .to_array()
+                .iter()
+                .zip(expected.to_array())
+                .all(|(&a, e)| a.abs_diff(e) <= 1);
+            assert!(
+                close,
+                "{} differs from {} at pixel {}: {:?}, not {:?}",
+                effects,
+                path.display(),
+                index,
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn scanlines_match_golden() {
+        check_golden("scanlines", "scanlines");
+    }
+
+    #[test]
+    fn grid_matches_golden() {
+        check_golden("grid", "grid");
+    }
+
+    #[test]
+    fn bloom_matches_golden() {
+        check_golden("bloom", "bloom");
+    }
+
+    #[test]
+    fn curvature_matches_golden() {
+        check_golden("curvature=0.3", "curvature");
+    }
+
+    #[test]
+    fn vignette_matches_golden() {
+        check_golden("vignette", "vignette");
+    }
+
+    #[test]
+    fn all_effects_match_golden() {
+        check_golden("scanlines,grid,bloom,curvature,vignette", "all");
+    }
+
+    #[test]
+    fn parses_effects_and_strengths() {
+        let crt = Crt::parse("scanlines,bloom=0.5").unwrap();
+        assert!(crt.scanlines.enabled && crt.bloom.enabled && !crt.grid.enabled);
+        assert_eq!(crt.bloom.strength, 0.5);
+        assert!(Crt::parse("curvature=0.5").is_err());
+        assert!(Crt::parse("sparkle").is_err());
+    }
+}
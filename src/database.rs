@@ -0,0 +1,341 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use eframe::egui;
+use serde::Deserialize;
+use sha1::{Digest, Sha1};
+
+use crate::{
+    chip8::{keypad::Key, platform::Platform},
+    keymap::Keymap,
+    palette::Palette,
+};
+
+/// The database shipped with the emulator, in the shape of the chip-8-database project's
+/// `programs.json`. It only lists the test ROMs in `roms/`; games are only recognised from a
+/// database the user provides.
+static BUNDLED_PROGRAMS: &str = include_str!("../database/programs.json");
+
+/// The name of the override file looked for in the user's config directory.
+pub const USER_DATABASE_FILE: &str = "programs.json";
+
+/// The most instructions a frame a ROM may ask for, so the rate per second fits in a `u32`.
+pub const MAX_TICKRATE: u32 = u32::MAX / 60;
+
+#[derive(Deserialize)]
+pub struct Program {
+    pub title: String,
+    #[serde(default)]
+    pub authors: Vec<String>,
+    #[serde(default)]
+    roms: HashMap<String, Rom>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Rom {
+    #[serde(default)]
+    platforms: Vec<String>,
+    #[serde(default)]
+    quirky_platforms: HashMap<String, HashMap<String, bool>>,
+    tickrate: Option<u32>,
+    colors: Option<Colours>,
+    #[serde(default)]
+    keys: HashMap<String, u8>,
+}
+
+#[derive(Deserialize)]
+struct Colours {
+    #[serde(default)]
+    pixels: Vec<String>,
+}
+
+/// Programs indexed by the SHA-1 of their ROMs.
+pub struct Database {
+    programs: Vec<Program>,
+    hashes: HashMap<String, usize>,
+}
+
+/// What the database knows about one ROM.
+pub struct Entry<'a> {
+    program: &'a Program,
+    rom: &'a Rom,
+}
+
+impl Database {
+    pub fn bundled() -> Self {
+        let mut database = Self {
+            programs: Vec::new(),
+            hashes: HashMap::new(),
+        };
+        database
+            .add(BUNDLED_PROGRAMS)
+            .expect("the bundled program database is valid");
+        database
+    }
+
+    /// Adds the programs in a user file, which take precedence over those already loaded.
+    pub fn load_override(&mut self, path: &Path) -> Result<(), String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|error| format!("could not read {}: {}", path.display(), error))?;
+        self.add(&contents).map_err(|error| {
+            format!(
+                "{} is not a valid program database: {}",
+                path.display(),
+                error
+            )
+        })
+    }
+
+    fn add(&mut self, json: &str) -> Result<(), String> {
+        let programs: Vec<Program> =
+            serde_json::from_str(json).map_err(|error| error.to_string())?;
+        for program in &programs {
+            for rom in program.roms.values() {
+                if rom
+                    .tickrate
+                    .is_some_and(|rate| !(1..=MAX_TICKRATE).contains(&rate))
+                {
+                    return Err(format!(
+                        "the tickrate of {} must be from 1 to {}",
+                        program.title, MAX_TICKRATE
+                    ));
+                }
+            }
+        }
+        for program in programs {
+            let index = self.programs.len();
+            for hash in program.roms.keys() {
+                self.hashes.insert(hash.to_ascii_lowercase(), index);
+            }
+            self.programs.push(program);
+        }
+        Ok(())
+    }
+
+    pub fn lookup(&self, program: &[u8]) -> Option<Entry<'_>> {
+        let hash = sha1_hex(program);
+        let program = &self.programs[*self.hashes.get(&hash)?];
+        let rom = program
+            .roms
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(&hash))
+            .map(|(_, rom)| rom)?;
+        Some(Entry { program, rom })
+    }
+}
+
+pub fn sha1_hex(program: &[u8]) -> String {
+    format!("{:x}", Sha1::digest(program))
+}
+
+impl Entry<'_> {
+    pub fn program(&self) -> &Program {
+        self.program
+    }
+
+    /// The database id of the first platform the ROM runs on that this emulator supports.
+    fn platform_id(&self) -> Option<&str> {
+        self.rom
+            .platforms
+            .iter()
+            .map(String::as_str)
+            .find(|id| platform_from_id(id).is_some())
+    }
+
+    pub fn platform(&self) -> Option<Platform> {
+        self.platform_id().and_then(platform_from_id)
+    }
+
+    /// Quirk settings for running the ROM on `platform`, or on its own platform if not given,
+    /// named as in [`crate::chip8::settings::Settings`]. A platform the ROM isn't listed for
+    /// gets that platform's usual quirks.
+    pub fn quirks(&self, platform: Option<Platform>) -> Vec<(&'static str, bool)> {
+        // XO-CHIP isn't a platform here, but its sound can still be played.
+        let xo_chip = self.rom.platforms.iter().any(|id| id == "xochip");
+        let id = match platform.or_else(|| self.platform()) {
+            Some(platform) => self
+                .rom
+                .platforms
+                .iter()
+                .map(String::as_str)
+                .find(|id| platform_from_id(id) == Some(platform))
+                .unwrap_or(platform.name()),
+            None if xo_chip => return vec![("xo-chip-audio", true)],
+            None => return Vec::new(),
+        };
+        let mut quirks = platform_quirks(id);
+        if xo_chip {
+            quirks.push(("xo-chip-audio", true));
+        }
+        for (name, value) in self.rom.quirky_platforms.get(id).into_iter().flatten() {
+            let quirk = match name.as_str() {
+                "shift" => ("assign-shift", !value),
+                "memoryLeaveIUnchanged" => ("load-store-increment", !value),
+                "jump" => ("jump-with-offset-add", *value),
+                _ => continue,
+            };
+            quirks.retain(|(existing, _)| *existing != quirk.0);
+            quirks.push(quirk);
+        }
+        quirks
+    }
+
+    /// The number of instructions to run in each 60 Hz frame.
+    pub fn tickrate(&self) -> Option<u32> {
+        self.rom.tickrate
+    }
+
+    /// The ROM's colours, using the first four for XO-CHIP ROMs that give them.
+    pub fn palette(&self) -> Option<Palette> {
+        let pixels = &self.rom.colors.as_ref()?.pixels;
+        let colours = match pixels.len() {
+            0 | 1 => return None,
+            2 | 3 => &pixels[..2],
+            _ => &pixels[..4],
+        };
+        Palette::parse(&colours.join(",")).ok()
+    }
+
+    /// Binds the ROM's game controls to the arrow keys, space and enter.
+    pub fn add_controls(&self, keymap: &mut Keymap) {
+        for (action, number) in &self.rom.keys {
+            let host_key = match action.as_str() {
+                "up" => egui::Key::ArrowUp,
+                "down" => egui::Key::ArrowDown,
+                "left" => egui::Key::ArrowLeft,
+                "right" => egui::Key::ArrowRight,
+                "a" => egui::Key::Space,
+                "b" => egui::Key::Enter,
+                _ => continue,
+            };
+            if let Some(key) = Key::from_number(*number) {
+                keymap.bind(host_key, key);
+            }
+        }
+    }
+}
+
+fn platform_from_id(id: &str) -> Option<Platform> {
+    match id {
+        "originalChip8" | "hybridVIP" | "modernChip8" => Some(Platform::Chip8),
+        "megachip8" => Some(Platform::MegaChip),
+        _ => Platform::from_name(id),
+    }
+}
+
+/// How the database's platforms behave before any per-ROM quirks are applied.
+fn platform_quirks(id: &str) -> Vec<(&'static str, bool)> {
+    match id {
+        "originalChip8" | "hybridVIP" | "chip8" | "chip8-hires" | "eti660" => vec![
+            ("assign-shift", true),
+            ("load-store-increment", true),
+            ("jump-with-offset-add", false),
+        ],
+        // CHIP-8 as most interpreters since CHIP-48 run it.
+        "modernChip8" => vec![
+            ("assign-shift", false),
+            ("load-store-increment", false),
+            ("jump-with-offset-add", false),
+        ],
+        // MEGA-CHIP builds on SUPER-CHIP, and has its quirks.
+        "megachip8" | "megachip" => vec![
+            ("assign-shift", false),
+            ("load-store-increment", false),
+            ("jump-with-offset-add", true),
+        ],
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_a_bundled_rom_by_its_hash() {
+        let program = include_bytes!("../roms/6-keypad.ch8");
+        let database = Database::bundled();
+        let entry = database.lookup(program).unwrap();
+        assert_eq!(entry.program().title, "Keypad Test");
+        assert_eq!(entry.platform(), Some(Platform::Chip8));
+        assert_eq!(entry.tickrate(), Some(15));
+        assert_eq!(
+            entry.quirks(None),
+            [
+                ("assign-shift", true),
+                ("load-store-increment", true),
+                ("jump-with-offset-add", false),
+            ]
+        );
+        let mut keymap = Keymap::default();
+        entry.add_controls(&mut keymap);
+        let bindings = keymap.bindings();
+        assert!(bindings.contains(&(egui::Key::Space, Key::from_number(3).unwrap())));
+        assert!(bindings.contains(&(egui::Key::Enter, Key::from_number(5).unwrap())));
+    }
+
+    #[test]
+    fn finds_the_eti660_build_of_a_rom() {
+        let program = include_bytes!("../roms/eti660/1-chip8-logo.ch8");
+        let database = Database::bundled();
+        let entry = database.lookup(program).unwrap();
+        assert_eq!(entry.program().title, "CHIP-8 Logo");
+        assert_eq!(entry.platform(), Some(Platform::Eti660));
+    }
+
+    #[test]
+    fn gives_the_quirks_of_the_platform_a_rom_is_run_on() {
+        let program = include_bytes!("../roms/5-quirks.ch8");
+        let database = Database::bundled();
+        let entry = database.lookup(program).unwrap();
+        assert!(entry
+            .quirks(Some(Platform::MegaChip))
+            .contains(&("jump-with-offset-add", true)));
+        // Not a platform the ROM is listed for, so it gets the platform's usual quirks.
+        assert_eq!(
+            entry.quirks(Some(Platform::Chip8Hires)),
+            entry.quirks(Some(Platform::Chip8))
+        );
+        assert!(entry
+            .quirks(Some(Platform::Chip8Hires))
+            .contains(&("assign-shift", true)));
+    }
+
+    #[test]
+    fn applies_per_rom_quirks_over_the_platform_ones() {
+        let json = r#"[{"title": "Quirky", "roms": {"ABCD": {
+            "platforms": ["modernChip8"],
+            "quirkyPlatforms": {"modernChip8": {"shift": false, "jump": true}}
+        }}}]"#;
+        let mut database = Database::bundled();
+        database.add(json).unwrap();
+        let program = &database.programs[database.hashes["abcd"]];
+        let entry = Entry {
+            program,
+            rom: &program.roms["ABCD"],
+        };
+        let mut quirks = entry.quirks(None);
+        quirks.sort();
+        assert_eq!(
+            quirks,
+            [
+                ("assign-shift", true),
+                ("jump-with-offset-add", true),
+                ("load-store-increment", false),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_tickrates_out_of_range() {
+        let mut database = Database::bundled();
+        for tickrate in [0, MAX_TICKRATE + 1] {
+            let json = format!(
+                r#"[{{"title": "Fast", "roms": {{"abcd": {{"tickrate": {}}}}}}}]"#,
+                tickrate
+            );
+            let error = database.add(&json).err().unwrap();
+            assert!(error.starts_with("the tickrate of Fast must be from 1 to"));
+        }
+    }
+}
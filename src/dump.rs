@@ -0,0 +1,198 @@
+use std::{
+    fmt::Write as _,
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use eframe::epaint::ColorImage;
+
+use crate::{
+    chip8::{
+        display::{Frame, PixelFormat},
+        Chip8,
+    },
+    database, screenshot,
+};
+
+/// Something written out at the end of a headless run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Item {
+    /// The display as text, `#` for each lit pixel and `.` for each unlit one.
+    Screen,
+    /// The SHA-1 of the display's pixels, to compare runs without storing pictures.
+    ScreenHash,
+    /// The display as a PNG, drawn like a screenshot.
+    ScreenPng,
+    Registers,
+    /// A hex dump, or the raw bytes when written to a file.
+    Memory,
+}
+
+impl Item {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "screen" => Some(Item::Screen),
+            "screen-hash" => Some(Item::ScreenHash),
+            "screen-png" => Some(Item::ScreenPng),
+            "registers" => Some(Item::Registers),
+            "memory" => Some(Item::Memory),
+            _ => None,
+        }
+    }
+}
+
+/// An item and where it goes.
+pub struct Dump {
+    pub item: Item,
+    /// The file to write to, or standard output if there isn't one.
+    pub path: Option<PathBuf>,
+}
+
+impl Dump {
+    /// Parses `<item>` or `<item>=<file>`.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let (name, path) = match value.split_once('=') {
+            Some((name, path)) => (name, Some(PathBuf::from(path))),
+            None => (value, None),
+        };
+        let item = Item::from_name(name).ok_or_else(|| {
+            format!(
+                "unknown dump `{}`: use screen, screen-hash, screen-png, registers or memory",
+                name
+            )
+        })?;
+        if item == Item::ScreenPng && path.is_none() {
+            return Err("screen-png needs a file, as in screen-png=final.png".to_string());
+        }
+        Ok(Self { item, path })
+    }
+
+    /// Writes the item out from the machine as it is now. `image` draws the display for
+    /// [`Item::ScreenPng`].
+    pub fn write(&self, chip8: &Chip8, image: impl FnOnce() -> ColorImage) -> Result<(), String> {
+        let contents = match self.item {
+            Item::ScreenPng => {
+                let path = self.path.as_ref().ok_or("screen-png needs a file")?;
+                return screenshot::save_png(&image(), path);
+            }
+            Item::Screen => text_screen(&chip8.screen().frame(0)).into_bytes(),
+            Item::ScreenHash => format!("{}\n", screen_hash(&chip8.screen().frame(0))).into_bytes(),
+            Item::Registers => chip8.cpu_state().to_string().into_bytes(),
+            Item::Memory if self.path.is_some() => chip8.memory().to_vec(),
+            Item::Memory => hex_dump(chip8.memory()).into_bytes(),
+        };
+        match &self.path {
+            Some(path) => fs::write(path, contents)
+                .map_err(|error| format!("could not write {}: {}", path.display(), error)),
+            None => io::stdout()
+                .write_all(&contents)
+                .map_err(|error| format!("could not write to standard output: {}", error)),
+        }
+    }
+}
+
+/// A frame as text, a line to each row with `#` for lit pixels and `.` for unlit ones. Plain
+/// ASCII keeps golden screens readable in any editor and diffable line by line.
+pub fn text_screen(frame: &Frame) -> String {
+    let mut text = String::with_capacity((frame.width + 1) * frame.height);
+    for row in frame.pixels.chunks(frame.width) {
+        text.extend(row.iter().map(|&value| if value != 0 { '#' } else { '.' }));
+        text.push('\n');
+    }
+    text
+}
+
+/// The SHA-1 of a frame's pixels, one byte each for palette indexes or four for ARGB colours.
+pub fn screen_hash(frame: &Frame) -> String {
+    let bytes: Vec<u8> = match frame.format {
+        PixelFormat::Indexed => frame.pixels.iter().map(|&value| value as u8).collect(),
+        PixelFormat::Argb => frame
+            .pixels
+            .iter()
+            .flat_map(|value| value.to_be_bytes())
+            .collect(),
+    };
+    database::sha1_hex(&bytes)
+}
+
+/// Sixteen bytes to a line with their address, with runs of identical lines shown as `*` so
+/// mostly empty memory stays short.
+fn hex_dump(memory: &[u8]) -> String {
+    let mut text = String::new();
+    let mut previous: Option<&[u8]> = None;
+    let mut skipping = false;
+    for (line, bytes) in memory.chunks(16).enumerate() {
+        if previous == Some(bytes) {
+            if !skipping {
+                text.push_str("*\n");
+                skipping = true;
+            }
+            continue;
+        }
+        previous = Some(bytes);
+        skipping = false;
+        let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+        let _ = writeln!(text, "{:06x}  {}", line * 16, hex.join(" "));
+    }
+    let _ = writeln!(text, "{:06x}", memory.len());
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(format: PixelFormat, pixels: &[u32]) -> Frame {
+        Frame {
+            number: 1,
+            width: 3,
+            height: 2,
+            format,
+            pixels: pixels.into(),
+        }
+    }
+
+    #[test]
+    fn draws_lit_pixels_as_hashes() {
+        let indexed = frame(PixelFormat::Indexed, &[1, 0, 0, 0, 3, 1]);
+        assert_eq!(text_screen(&indexed), "#..\n.##\n");
+        let colour = frame(PixelFormat::Argb, &[0, 0xFF00_0000, 0, 0x12, 0, 0]);
+        assert_eq!(text_screen(&colour), ".#.\n#..\n");
+    }
+
+    #[test]
+    fn hashes_colours_in_all_four_bytes() {
+        let indexed = frame(PixelFormat::Indexed, &[1, 0, 0, 0, 0, 0]);
+        let colour = frame(PixelFormat::Argb, &[1, 0, 0, 0, 0, 0]);
+        assert_eq!(
+            screen_hash(&indexed),
+            database::sha1_hex(&[1, 0, 0, 0, 0, 0])
+        );
+        assert_ne!(screen_hash(&indexed), screen_hash(&colour));
+        assert_eq!(screen_hash(&colour).len(), 40);
+    }
+
+    #[test]
+    fn parses_dumps() {
+        assert_eq!(Dump::parse("screen").unwrap().item, Item::Screen);
+        let dump = Dump::parse("memory=ram.bin").unwrap();
+        assert_eq!(dump.item, Item::Memory);
+        assert_eq!(dump.path, Some(PathBuf::from("ram.bin")));
+        assert!(Dump::parse("screen-png").is_err());
+        assert!(Dump::parse("pixels").is_err());
+    }
+
+    #[test]
+    fn shortens_repeated_lines_of_memory() {
+        let mut memory = vec![0; 64];
+        memory[1] = 0xAB;
+        assert_eq!(
+            hex_dump(&memory),
+            "000000  00 ab 00 00 00 00 00 00 00 00 00 00 00 00 00 00\n\
+             000010  00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00\n\
+             *\n\
+             000040\n"
+        );
+    }
+}
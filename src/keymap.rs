@@ -0,0 +1,300 @@
+use std::{
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use eframe::egui;
+
+use crate::{chip8::keypad::Key, config, database};
+
+/// A keymap and the file it is saved to for the ROM it was loaded for.
+pub struct RomKeymap {
+    pub keymap: Keymap,
+    pub path: Option<PathBuf>,
+}
+
+impl RomKeymap {
+    /// Loads the ROM's own keymap file if there is one, otherwise the user's default keymap file
+    /// with the database's controls for the ROM added.
+    pub fn load(program: &[u8], entry: Option<&database::Entry>) -> Result<Self, String> {
+        let path = rom_keymap_path(program);
+        let keymap = match path.as_deref().filter(|path| path.exists()) {
+            Some(path) => Keymap::load(path)?,
+            None => {
+                let mut keymap = match default_keymap_path().filter(|path| path.exists()) {
+                    Some(path) => Keymap::load(&path)?,
+                    None => Keymap::default(),
+                };
+                if let Some(entry) = entry {
+                    entry.add_controls(&mut keymap);
+                }
+                keymap
+            }
+        };
+        Ok(Self { keymap, path })
+    }
+}
+
+/// Which host keys drive which CHIP-8 keys. A CHIP-8 key can have any number of host keys.
+pub struct Keymap {
+    bindings: Vec<(egui::Key, Key)>,
+}
+
+static HOST_KEYS: &[egui::Key] = &[
+    egui::Key::ArrowDown,
+    egui::Key::ArrowLeft,
+    egui::Key::ArrowRight,
+    egui::Key::ArrowUp,
+    egui::Key::Tab,
+    egui::Key::Backspace,
+    egui::Key::Enter,
+    egui::Key::Space,
+    egui::Key::Insert,
+    egui::Key::Delete,
+    egui::Key::Home,
+    egui::Key::End,
+    egui::Key::PageUp,
+    egui::Key::PageDown,
+    egui::Key::Minus,
+    egui::Key::PlusEquals,
+    egui::Key::Num0,
+    egui::Key::Num1,
+    egui::Key::Num2,
+    egui::Key::Num3,
+    egui::Key::Num4,
+    egui::Key::Num5,
+    egui::Key::Num6,
+    egui::Key::Num7,
+    egui::Key::Num8,
+    egui::Key::Num9,
+    egui::Key::A,
+    egui::Key::B,
+    egui::Key::C,
+    egui::Key::D,
+    egui::Key::E,
+    egui::Key::F,
+    egui::Key::G,
+    egui::Key::H,
+    egui::Key::I,
+    egui::Key::J,
+    egui::Key::K,
+    egui::Key::L,
+    egui::Key::M,
+    egui::Key::N,
+    egui::Key::O,
+    egui::Key::P,
+    egui::Key::Q,
+    egui::Key::R,
+    egui::Key::S,
+    egui::Key::T,
+    egui::Key::U,
+    egui::Key::V,
+    egui::Key::W,
+    egui::Key::X,
+    egui::Key::Y,
+    egui::Key::Z,
+];
+
+/// The COSMAC VIP keypad laid over the left of a QWERTY keyboard, in CHIP-8 key order 0-F.
+const DEFAULT_LAYOUT: &str = "X123QWEASDZC4RFV";
+
+impl Keymap {
+    /// Builds a keymap from 16 characters naming the host key for each CHIP-8 key 0-F.
+    pub fn from_layout(layout: &str) -> Result<Self, String> {
+        let characters: Vec<char> = layout.chars().collect();
+        if characters.len() != 16 {
+            return Err(format!(
+                "keymap `{}` must have 16 keys, one for each of 0-F",
+                layout
+            ));
+        }
+        let mut bindings = Vec::new();
+        for (number, character) in characters.into_iter().enumerate() {
+            let name = character.to_ascii_uppercase().to_string();
+            let host_key = host_key(&name)
+                .ok_or_else(|| format!("`{}` is not a key that can be bound", character))?;
+            let key = Key::from_number(number as u8).unwrap();
+            bindings.push((host_key, key));
+        }
+        Ok(Self { bindings })
+    }
+
+    /// Parses a keymap file, which has a `<key> = <host key>, <host key>` line for each CHIP-8
+    /// key 0-F that is bound. Host keys are named as egui names them, such as `A`, `7`, `Up` or
+    /// `Space`. Blank lines and lines starting with `#` are ignored.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut keymap = Self {
+            bindings: Vec::new(),
+        };
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: String| format!("line {}: {}", number + 1, message);
+            let (key, host_keys) = line
+                .split_once('=')
+                .ok_or_else(|| error(format!("`{}` must look like 5 = W, Up", line)))?;
+            let key = u8::from_str_radix(key.trim(), 16)
+                .ok()
+                .and_then(Key::from_number)
+                .ok_or_else(|| error(format!("`{}` is not a CHIP-8 key 0-F", key.trim())))?;
+            for name in host_keys
+                .split(',')
+                .map(str::trim)
+                .filter(|n| !n.is_empty())
+            {
+                let host_key = host_key(name)
+                    .ok_or_else(|| error(format!("`{}` is not a key that can be bound", name)))?;
+                keymap.bind(host_key, key);
+            }
+        }
+        Ok(keymap)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|error| format!("could not read {}: {}", path.display(), error))?;
+        Self::parse(&text).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let error =
+            |error: std::io::Error| format!("could not write {}: {}", path.display(), error);
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory).map_err(error)?;
+        }
+        fs::write(path, self.to_string()).map_err(error)
+    }
+
+    /// Binds a host key to a CHIP-8 key, alongside any other host keys that already drive it.
+    pub fn bind(&mut self, host_key: egui::Key, key: Key) {
+        self.bindings.retain(|(existing, _)| *existing != host_key);
+        self.bindings.push((host_key, key));
+    }
+
+    /// Removes every host key from a CHIP-8 key.
+    pub fn unbind(&mut self, key: Key) {
+        self.bindings.retain(|(_, existing)| *existing != key);
+    }
+
+    pub fn host_keys(&self, key: Key) -> impl Iterator<Item = egui::Key> + '_ {
+        self.bindings
+            .iter()
+            .filter(move |(_, existing)| *existing == key)
+            .map(|(host_key, _)| *host_key)
+    }
+
+    pub fn bindings(&self) -> &[(egui::Key, Key)] {
+        &self.bindings
+    }
+}
+
+impl Display for Keymap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "# CHIP-8 key = host keys")?;
+        for key in Key::ALL {
+            let names: Vec<&str> = self
+                .host_keys(key)
+                .map(|host_key| host_key.name())
+                .collect();
+            if !names.is_empty() {
+                writeln!(f, "{:X} = {}", key as u8, names.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::from_layout(DEFAULT_LAYOUT).unwrap()
+    }
+}
+
+/// Looks up a host key by the name egui gives it, such as `A`, `7` or `Space`.
+pub fn host_key(name: &str) -> Option<egui::Key> {
+    HOST_KEYS
+        .iter()
+        .copied()
+        .find(|key| key.name().eq_ignore_ascii_case(name))
+}
+
+pub fn is_bindable(host_key: egui::Key) -> bool {
+    HOST_KEYS.contains(&host_key)
+}
+
+/// The keymap used for every ROM without one of its own, in the config directory.
+pub fn default_keymap_path() -> Option<PathBuf> {
+    config::config_dir().map(|dir| dir.join("default.keymap"))
+}
+
+/// Where the keymap for one ROM is kept, named after the ROM's SHA-1.
+pub fn rom_keymap_path(program: &[u8]) -> Option<PathBuf> {
+    config::config_dir().map(|dir| {
+        dir.join("keymaps")
+            .join(format!("{}.keymap", database::sha1_hex(program)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_several_host_keys_for_a_key() {
+        let keymap = Keymap::parse("# Arrows too\n5 = W, up\n\n8 = S,Down\n").unwrap();
+        let host_keys: Vec<egui::Key> = keymap.host_keys(Key::KeyW).collect();
+        assert_eq!(host_keys, [egui::Key::W, egui::Key::ArrowUp]);
+        let host_keys: Vec<egui::Key> = keymap.host_keys(Key::KeyS).collect();
+        assert_eq!(host_keys, [egui::Key::S, egui::Key::ArrowDown]);
+        assert_eq!(keymap.bindings().len(), 4);
+    }
+
+    #[test]
+    fn rejects_bad_lines() {
+        assert_eq!(
+            Keymap::parse("5 = W\n5 W").err().unwrap(),
+            "line 2: `5 W` must look like 5 = W, Up"
+        );
+        assert_eq!(
+            Keymap::parse("G = W").err().unwrap(),
+            "line 1: `G` is not a CHIP-8 key 0-F"
+        );
+        assert_eq!(
+            Keymap::parse("5 = F1").err().unwrap(),
+            "line 1: `F1` is not a key that can be bound"
+        );
+    }
+
+    #[test]
+    fn saves_keymaps_it_can_read_back() {
+        let mut keymap = Keymap::default();
+        keymap.bind(egui::Key::Space, Key::KeyE);
+        let parsed = Keymap::parse(&keymap.to_string()).unwrap();
+        for key in Key::ALL {
+            let expected: Vec<egui::Key> = keymap.host_keys(key).collect();
+            let host_keys: Vec<egui::Key> = parsed.host_keys(key).collect();
+            assert_eq!(host_keys, expected);
+        }
+    }
+
+    #[test]
+    fn moves_a_host_key_bound_again() {
+        let mut keymap = Keymap::default();
+        keymap.bind(egui::Key::W, Key::KeyA);
+        assert_eq!(keymap.host_keys(Key::KeyW).count(), 0);
+        let host_keys: Vec<egui::Key> = keymap.host_keys(Key::KeyA).collect();
+        assert_eq!(host_keys, [egui::Key::A, egui::Key::W]);
+    }
+
+    #[test]
+    fn reads_layouts_in_key_order() {
+        let keymap = Keymap::from_layout("x123qweasdzc4rfv").unwrap();
+        let host_keys: Vec<egui::Key> = keymap.host_keys(Key::KeyX).collect();
+        assert_eq!(host_keys, [egui::Key::X]);
+        assert!(Keymap::from_layout("X123").is_err());
+        assert!(Keymap::from_layout("X123QWEASDZC4RF!").is_err());
+    }
+}
@@ -0,0 +1,71 @@
+use std::{fs, path::Path};
+
+use crate::chip8::keypad::{Event, Key};
+
+/// Key presses to play back in a headless run, each at the start of a given frame.
+///
+/// Movie files have one press or release per line, as `<frame> down <key>` or
+/// `<frame> up <key>`, with the key in hex and frames counted from 1. Text after `#` is a
+/// comment.
+#[derive(Default)]
+pub struct Movie {
+    /// Events in the order they happen, with the frame they happen at.
+    events: Vec<(u32, Event)>,
+}
+
+impl Movie {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut events = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = || {
+                format!(
+                    "line {}: `{}` should look like `120 down 5`",
+                    number + 1,
+                    line
+                )
+            };
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let [frame, action, key] = words[..] else {
+                return Err(error());
+            };
+            let frame: u32 = frame.parse().map_err(|_| error())?;
+            let key = u8::from_str_radix(key, 16)
+                .ok()
+                .and_then(Key::from_number)
+                .ok_or_else(error)?;
+            let event = match action {
+                "down" => Event::KeyDown(key),
+                "up" => Event::KeyUp(key),
+                _ => return Err(error()),
+            };
+            events.push((frame, event));
+        }
+        // Keep lines for the same frame in the order they were written.
+        events.sort_by_key(|(frame, _)| *frame);
+        Ok(Self { events })
+    }
+
+    /// Adds an event after any others at the same frame.
+    pub fn add(&mut self, frame: u32, event: Event) {
+        let index = self.events.partition_point(|(at, _)| *at <= frame);
+        self.events.insert(index, (frame, event));
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|error| format!("could not read {}: {}", path.display(), error))?;
+        Self::parse(&text).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+
+    /// The events to send before frame `frame` runs.
+    pub fn events_at(&self, frame: u32) -> impl Iterator<Item = Event> + '_ {
+        self.events
+            .iter()
+            .filter(move |(at, _)| *at == frame)
+            .map(|(_, event)| *event)
+    }
+}
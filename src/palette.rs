@@ -0,0 +1,95 @@
+use eframe::epaint::Color32;
+
+/// The colours monochrome and two-plane pixels are drawn in, indexed by the planes a pixel is
+/// lit in: unlit, the first plane, the second plane, and both, as in XO-CHIP.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    pub colours: [Color32; 4],
+}
+
+/// Palettes that can be picked by name, in `background, foreground, second plane, both planes`
+/// order.
+pub static BUILT_IN: &[(&str, [u32; 4])] = &[
+    ("classic", [0x000000, 0xFFFFFF, 0xAAAAAA, 0x555555]),
+    ("green", [0x001100, 0x33FF33, 0x1A801A, 0x99FF99]),
+    ("amber", [0x1A0F00, 0xFFB000, 0x805800, 0xFFD880]),
+    ("lcd", [0x9BBC0F, 0x0F380F, 0x306230, 0x8BAC0F]),
+    ("high-contrast", [0x000000, 0xFFFF00, 0x00FFFF, 0xFFFFFF]),
+    // From the Okabe-Ito set, which stays distinct with the common kinds of colour blindness.
+    ("colour-blind", [0x000000, 0xE69F00, 0x56B4E9, 0xF0E442]),
+    ("octo", [0x996600, 0xFFCC00, 0xFF6600, 0x662200]),
+];
+
+impl Palette {
+    pub fn built_in(name: &str) -> Option<Self> {
+        BUILT_IN
+            .iter()
+            .find(|(built_in, _)| *built_in == name)
+            .map(|(_, colours)| Self::from_rgb(*colours))
+    }
+
+    fn from_rgb(colours: [u32; 4]) -> Self {
+        Self {
+            colours: colours.map(|rgb| {
+                let [_, r, g, b] = rgb.to_be_bytes();
+                Color32::from_rgb(r, g, b)
+            }),
+        }
+    }
+
+    /// Parses the name of a built-in palette, or hex colours such as `000000,ffffff` separated
+    /// by commas. Two colours give the background and foreground; four also give the second
+    /// plane and both planes for XO-CHIP.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        if let Some(palette) = Self::built_in(value) {
+            return Ok(palette);
+        }
+        let colours = value
+            .split(',')
+            .map(parse_colour)
+            .collect::<Result<Vec<_>, _>>()?;
+        match colours[..] {
+            [background, foreground] => Ok(Self::monochrome(background, foreground)),
+            [background, foreground, second, both] => Ok(Self {
+                colours: [background, foreground, second, both],
+            }),
+            _ => Err(format!(
+                "palette `{}` must be a name or 2 or 4 colours, like 000000,ffffff",
+                value
+            )),
+        }
+    }
+
+    /// A palette for two colours, with pixels in the second plane drawn as a blend of the two.
+    fn monochrome(background: Color32, foreground: Color32) -> Self {
+        let mix = |a: u8, b: u8| ((a as u16 + b as u16) / 2) as u8;
+        let second = Color32::from_rgb(
+            mix(background.r(), foreground.r()),
+            mix(background.g(), foreground.g()),
+            mix(background.b(), foreground.b()),
+        );
+        Self {
+            colours: [background, foreground, second, foreground],
+        }
+    }
+
+    pub fn colour(&self, index: u32) -> Color32 {
+        self.colours[index as usize & 0x3]
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::built_in("classic").unwrap()
+    }
+}
+
+fn parse_colour(value: &str) -> Result<Color32, String> {
+    let hex = value.trim().trim_start_matches('#');
+    let rgb = u32::from_str_radix(hex, 16)
+        .ok()
+        .filter(|_| hex.len() == 6)
+        .ok_or_else(|| format!("`{}` is not a hex colour", value))?;
+    let [_, r, g, b] = rgb.to_be_bytes();
+    Ok(Color32::from_rgb(r, g, b))
+}
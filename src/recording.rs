@@ -0,0 +1,456 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use eframe::epaint::ColorImage;
+
+use crate::screenshot;
+
+/// The kinds of file gameplay can be recorded to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Gif,
+    Apng,
+    /// Uncompressed YUV 4:4:4 video, for piping into an encoder.
+    Y4m,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "gif" => Some(Format::Gif),
+            "apng" | "png" => Some(Format::Apng),
+            "y4m" => Some(Format::Y4m),
+            _ => None,
+        }
+    }
+
+    /// The format a file is written in, from its extension. `-` is Y4M on standard output.
+    pub fn from_path(path: &Path) -> Result<Self, String> {
+        if path == Path::new("-") {
+            return Ok(Format::Y4m);
+        }
+        path.extension()
+            .and_then(|extension| Self::from_name(&extension.to_string_lossy()))
+            .ok_or_else(|| {
+                format!(
+                    "cannot tell the format of {}, expected a .gif, .png, .apng or .y4m file",
+                    path.display()
+                )
+            })
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Gif => "gif",
+            Format::Apng => "png",
+            Format::Y4m => "y4m",
+        }
+    }
+}
+
+/// Writes frames to an animation or video as they arrive, timed by their frame numbers at
+/// 60 Hz. A frame is held until the next different one, so a picture that stays up for
+/// several frames is written once with a longer delay where the format allows it.
+pub struct Recorder {
+    path: PathBuf,
+    format: Format,
+    /// The file, until the first frame gives the size to write the header with. Standard
+    /// output has none.
+    file: Option<File>,
+    output: Option<Output>,
+    size: [usize; 2],
+    /// The frame waiting to be written and the number of the frame it first appeared in.
+    pending: Option<(ColorImage, u64)>,
+    /// Frames of 1/60 s written so far.
+    written: u64,
+}
+
+enum Output {
+    Gif(gif::Encoder<Box<dyn Write>>),
+    Apng(Apng),
+    Y4m(Box<dyn Write>),
+}
+
+impl Recorder {
+    /// Starts a recording to `path` in the format its extension names, or to standard output
+    /// if it is `-`.
+    pub fn new(path: &Path) -> Result<Self, String> {
+        let format = Format::from_path(path)?;
+        let file = if path == Path::new("-") {
+            None
+        } else {
+            let file = File::create(path)
+                .map_err(|error| format!("could not create {}: {}", path.display(), error))?;
+            Some(file)
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            format,
+            file,
+            output: None,
+            size: [0, 0],
+            pending: None,
+            written: 0,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Adds the picture for frame `number`, which stays up until the next one added.
+    pub fn add(&mut self, image: ColorImage, number: u64) -> Result<(), String> {
+        if let Some((pending, start)) = self.pending.take() {
+            if pending == image {
+                self.pending = Some((pending, start));
+                return Ok(());
+            }
+            self.write(&pending, number.saturating_sub(start).max(1))?;
+        }
+        self.pending = Some((image, number));
+        Ok(())
+    }
+
+    /// Writes the last picture, shown until the end of frame `last`, and closes the file.
+    pub fn finish(mut self, last: u64) -> Result<(), String> {
+        if let Some((pending, start)) = self.pending.take() {
+            self.write(&pending, (last + 1).saturating_sub(start).max(1))?;
+        }
+        let result = match self.output.take() {
+            Some(Output::Gif(encoder)) => encoder.into_inner().and_then(|mut file| file.flush()),
+            Some(Output::Apng(apng)) => apng.finish(),
+            Some(Output::Y4m(mut file)) => file.flush(),
+            None => {
+                return Err(format!(
+                    "no frames were recorded to {}",
+                    self.path.display()
+                ))
+            }
+        };
+        result.map_err(|error| self.error(error))
+    }
+
+    /// Writes a picture that stays up for `frames` frames.
+    fn write(&mut self, image: &ColorImage, frames: u64) -> Result<(), String> {
+        if self.output.is_none() {
+            self.start(image.size)?;
+        }
+        // Switching resolution part way through is drawn at the size the recording started at.
+        let resized;
+        let image = if image.size == self.size {
+            image
+        } else {
+            resized = screenshot::resize(image, self.size);
+            &resized
+        };
+        let start = self.written;
+        self.written += frames;
+        let result = match self.output.as_mut().unwrap() {
+            Output::Gif(encoder) => {
+                // GIF delays are in hundredths of a second, so round each frame's end time to
+                // keep the total in step with 60 Hz.
+                let delay = centiseconds(self.written) - centiseconds(start);
+                write_gif_frame(encoder, image, delay)
+            }
+            Output::Apng(apng) => apng.write_frame(image, frames),
+            Output::Y4m(file) => write_y4m_frame(file, image, frames),
+        };
+        result.map_err(|error| self.error(error))
+    }
+
+    fn start(&mut self, size: [usize; 2]) -> Result<(), String> {
+        self.size = size;
+        let file = self.file.take();
+        let [width, height] = size;
+        let output = match self.format {
+            Format::Gif => {
+                let encoder = gif::Encoder::new(buffered(file), width as u16, height as u16, &[])
+                    .and_then(|mut encoder| {
+                        encoder.set_repeat(gif::Repeat::Infinite)?;
+                        Ok(encoder)
+                    });
+                Output::Gif(encoder.map_err(|error| self.error(error))?)
+            }
+            Format::Apng => {
+                // Only Y4M goes to standard output, so there is always a file here.
+                let file = file.expect("APNG recordings are written to a file");
+                Output::Apng(Apng::start(file, size))
+            }
+            Format::Y4m => {
+                let mut writer = buffered(file);
+                writeln!(
+                    writer,
+                    "YUV4MPEG2 W{} H{} F60:1 Ip A1:1 C444 XCOLORRANGE=LIMITED",
+                    width, height
+                )
+                .map_err(|error| self.error(error))?;
+                Output::Y4m(writer)
+            }
+        };
+        self.output = Some(output);
+        Ok(())
+    }
+
+    fn error(&self, error: impl std::fmt::Display) -> String {
+        format!("could not record to {}: {}", self.path.display(), error)
+    }
+}
+
+/// Buffers writes to the file, or to standard output if there is none.
+fn buffered(file: Option<File>) -> Box<dyn Write> {
+    match file {
+        Some(file) => Box::new(BufWriter::new(file)),
+        None => Box::new(BufWriter::new(io::stdout())),
+    }
+}
+
+fn centiseconds(frames: u64) -> u64 {
+    (frames * 100 + 30) / 60
+}
+
+fn write_gif_frame(
+    encoder: &mut gif::Encoder<Box<dyn Write>>,
+    image: &ColorImage,
+    delay: u64,
+) -> io::Result<()> {
+    let [width, height] = image.size;
+    let rgb = rgb(image);
+    // Plain palettes are written exactly; pictures with CRT effects need quantising.
+    let mut colours: Vec<[u8; 3]> = Vec::new();
+    let mut indices = Vec::with_capacity(image.pixels.len());
+    for pixel in rgb.chunks_exact(3) {
+        let pixel = [pixel[0], pixel[1], pixel[2]];
+        let index = match colours.iter().position(|&colour| colour == pixel) {
+            Some(index) => index,
+            None if colours.len() < 256 => {
+                colours.push(pixel);
+                colours.len() - 1
+            }
+            None => break,
+        };
+        indices.push(index as u8);
+    }
+    let mut frame = if indices.len() == image.pixels.len() {
+        let palette: Vec<u8> = colours.concat();
+        gif::Frame::from_palette_pixels(width as u16, height as u16, &indices, &palette, None)
+    } else {
+        gif::Frame::from_rgb_speed(width as u16, height as u16, &rgb, 10)
+    };
+    // Very long pauses are split, since a delay only goes up to about 11 minutes.
+    let mut delay = delay;
+    while delay > 0 {
+        frame.delay = delay.min(u16::MAX as u64) as u16;
+        delay -= frame.delay as u64;
+        encoder.write_frame(&frame).map_err(io::Error::other)?;
+    }
+    Ok(())
+}
+
+fn write_y4m_frame(file: &mut Box<dyn Write>, image: &ColorImage, frames: u64) -> io::Result<()> {
+    let mut planes = vec![0; image.pixels.len() * 3];
+    let (y, rest) = planes.split_at_mut(image.pixels.len());
+    let (u, v) = rest.split_at_mut(image.pixels.len());
+    for (i, colour) in image.pixels.iter().enumerate() {
+        let (r, g, b) = (colour.r() as f32, colour.g() as f32, colour.b() as f32);
+        // BT.601 in the limited range encoders expect.
+        y[i] = (16.0 + 0.257 * r + 0.504 * g + 0.098 * b).round() as u8;
+        u[i] = (128.0 - 0.148 * r - 0.291 * g + 0.439 * b).round() as u8;
+        v[i] = (128.0 + 0.439 * r - 0.368 * g - 0.071 * b).round() as u8;
+    }
+    for _ in 0..frames {
+        file.write_all(b"FRAME\n")?;
+        file.write_all(&planes)?;
+    }
+    Ok(())
+}
+
+/// An animated PNG. Its header gives the number of frames, so each frame is kept as a still
+/// PNG, which for CHIP-8 pictures is small, until the recording is finished.
+struct Apng {
+    file: BufWriter<File>,
+    size: [usize; 2],
+    /// Each frame and the sixtieths of a second it stays up for.
+    frames: Vec<(Vec<u8>, u16)>,
+}
+
+impl Apng {
+    fn start(file: File, size: [usize; 2]) -> Self {
+        Self {
+            file: BufWriter::new(file),
+            size,
+            frames: Vec::new(),
+        }
+    }
+
+    fn write_frame(&mut self, image: &ColorImage, frames: u64) -> io::Result<()> {
+        let mut still = Vec::new();
+        png_encoder(&mut still, self.size)
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&rgb(image)))?;
+        // Very long pauses are split, since a delay only goes up to about 18 minutes.
+        let mut frames = frames;
+        while frames > 0 {
+            let delay = frames.min(u16::MAX as u64) as u16;
+            self.frames.push((still.clone(), delay));
+            frames -= delay as u64;
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> io::Result<()> {
+        let mut encoder = png_encoder(self.file, self.size);
+        // Played on a loop.
+        encoder.set_animated(self.frames.len() as u32, 0)?;
+        let mut writer = encoder.write_header()?;
+        let mut data = Vec::new();
+        for (still, delay) in &self.frames {
+            let mut reader = png::Decoder::new(&still[..])
+                .read_info()
+                .map_err(io::Error::other)?;
+            data.resize(reader.output_buffer_size(), 0);
+            reader.next_frame(&mut data).map_err(io::Error::other)?;
+            // Delays are a fraction of a second, so 60 Hz frames are counted in sixtieths.
+            writer.set_frame_delay(*delay, 60)?;
+            writer.write_image_data(&data)?;
+        }
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+fn png_encoder<W: Write>(writer: W, size: [usize; 2]) -> png::Encoder<'static, W> {
+    let mut encoder = png::Encoder::new(writer, size[0] as u32, size[1] as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+}
+
+fn rgb(image: &ColorImage) -> Vec<u8> {
+    image
+        .pixels
+        .iter()
+        .flat_map(|colour| [colour.r(), colour.g(), colour.b()])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use eframe::epaint::Color32;
+
+    use super::*;
+
+    fn image(colour: Color32) -> ColorImage {
+        ColorImage::new([4, 2], colour)
+    }
+
+    /// Records the pictures added at each frame number, finishing at frame `last`, and returns
+    /// the file written.
+    fn record(extension: &str, frames: &[(Color32, u64)], last: u64) -> Vec<u8> {
+        let path = std::env::temp_dir().join(format!(
+            "chip8-recording-{}-{}-{}.{}",
+            std::process::id(),
+            frames.len(),
+            last,
+            extension
+        ));
+        let mut recorder = Recorder::new(&path).unwrap();
+        for &(colour, number) in frames {
+            recorder.add(image(colour), number).unwrap();
+        }
+        recorder.finish(last).unwrap();
+        let contents = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        contents
+    }
+
+    fn gif_delays(contents: &[u8]) -> Vec<u16> {
+        let mut decoder = gif::DecodeOptions::new().read_info(contents).unwrap();
+        let mut delays = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            delays.push(frame.delay);
+        }
+        delays
+    }
+
+    /// Each frame's delay in sixtieths of a second and its first pixel.
+    fn apng_frames(contents: &[u8]) -> (u32, Vec<(u16, [u8; 3])>) {
+        let mut reader = png::Decoder::new(contents).read_info().unwrap();
+        let count = reader.info().animation_control.unwrap().num_frames;
+        let mut data = vec![0; reader.output_buffer_size()];
+        let mut frames = Vec::new();
+        for _ in 0..count {
+            reader.next_frame(&mut data).unwrap();
+            let control = reader.info().frame_control.unwrap();
+            assert_eq!(control.delay_den, 60);
+            frames.push((control.delay_num, [data[0], data[1], data[2]]));
+        }
+        (count, frames)
+    }
+
+    #[test]
+    fn tells_the_format_from_the_extension() {
+        assert_eq!(Format::from_path(Path::new("a.gif")), Ok(Format::Gif));
+        assert_eq!(Format::from_path(Path::new("a.apng")), Ok(Format::Apng));
+        assert_eq!(Format::from_path(Path::new("-")), Ok(Format::Y4m));
+        assert!(Format::from_path(Path::new("a.mp4")).is_err());
+    }
+
+    #[test]
+    fn times_gif_frames_in_hundredths_that_keep_up_with_60_hz() {
+        let frames = [
+            (Color32::BLACK, 1),
+            (Color32::BLACK, 2),
+            (Color32::WHITE, 3),
+        ];
+        // Two frames are 3.3 hundredths, rounded to 3, and the next three end at 8.3.
+        assert_eq!(gif_delays(&record("gif", &frames, 5)), [3, 5]);
+    }
+
+    #[test]
+    fn splits_long_gif_delays() {
+        let contents = record("gif", &[(Color32::BLACK, 1)], 42_000);
+        assert_eq!(gif_delays(&contents), [65_535, 4_465]);
+    }
+
+    #[test]
+    fn writes_apng_frames_with_their_delays() {
+        let frames = [(Color32::BLACK, 1), (Color32::BLACK, 2), (Color32::RED, 3)];
+        let (count, frames) = apng_frames(&record("png", &frames, 3));
+        assert_eq!(count, 2);
+        assert_eq!(frames, [(2, [0, 0, 0]), (1, [255, 0, 0])]);
+    }
+
+    #[test]
+    fn splits_long_apng_delays() {
+        let contents = record("png", &[(Color32::WHITE, 1)], 70_000);
+        let (count, frames) = apng_frames(&contents);
+        assert_eq!(count, 2);
+        assert_eq!(frames, [(65_535, [255; 3]), (4_465, [255; 3])]);
+    }
+
+    #[test]
+    fn repeats_y4m_frames_for_as_long_as_they_stay_up() {
+        let frames = [(Color32::BLACK, 1), (Color32::WHITE, 4)];
+        let contents = record("y4m", &frames, 5);
+        let header = b"YUV4MPEG2 W4 H2 F60:1 Ip A1:1 C444 XCOLORRANGE=LIMITED\n";
+        assert!(contents.starts_with(header));
+        let frame = b"FRAME\n".len() + 4 * 2 * 3;
+        assert_eq!(contents.len(), header.len() + 5 * frame);
+        // Black is 16 in the limited range and white 235.
+        assert_eq!(contents[header.len() + 6], 16);
+        assert_eq!(contents[header.len() + 3 * frame + 6], 235);
+    }
+
+    #[test]
+    fn fails_to_finish_without_frames() {
+        let path = std::env::temp_dir().join(format!("chip8-empty-{}.gif", std::process::id()));
+        let recorder = Recorder::new(&path).unwrap();
+        assert!(recorder.finish(1).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
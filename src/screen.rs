@@ -0,0 +1,396 @@
+use std::collections::VecDeque;
+
+use eframe::{
+    egui::{self, Sense, TextureHandle, TextureOptions},
+    epaint::{Color32, ColorImage, Pos2, Rect, Vec2},
+};
+
+use crate::{
+    chip8::display::{Frame, PixelFormat},
+    crt::Crt,
+    palette::Palette,
+};
+
+/// How the display is sized to the window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scaling {
+    /// Only whole multiples of the display resolution, so every pixel is the same size.
+    Integer,
+    /// As large as fits, even if pixels come out unevenly sized.
+    Fit,
+}
+
+/// The shape the display is drawn in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aspect {
+    /// Square pixels, so a 64x32 display is twice as wide as it is tall.
+    Square,
+    /// A 4:3 picture, like the TV a COSMAC VIP was plugged into.
+    FourThree,
+    /// Whatever shape the window is.
+    Stretch,
+}
+
+/// How flicker from programs erasing and redrawing sprites is hidden. Only the picture is
+/// changed; the emulated display is left exactly as the program drew it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Flicker {
+    Off,
+    /// Pixels fade out instead of going dark at once, keeping `fade` of their brightness each
+    /// frame, like the phosphor of a CRT.
+    Decay {
+        fade: f32,
+    },
+    /// Each picture is an even mix of the last two frames.
+    Blend,
+    /// Pixels lit in any of the last `frames` frames are drawn lit.
+    Or {
+        frames: usize,
+    },
+}
+
+impl Flicker {
+    pub const DEFAULT_FADE: f32 = 0.6;
+    pub const DEFAULT_FRAMES: usize = 3;
+    /// The most frames `Or` can look back over.
+    pub const MAX_FRAMES: usize = 8;
+
+    /// Parses `off`, `decay`, `blend` or `or`, optionally followed by `=` and the fade or
+    /// number of frames, as in `decay=0.5` or `or=4`.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let (mode, setting) = match value.split_once('=') {
+            Some((mode, setting)) => (mode, Some(setting)),
+            None => (value, None),
+        };
+        match (mode, setting) {
+            ("off", None) => Ok(Flicker::Off),
+            ("blend", None) => Ok(Flicker::Blend),
+            ("decay", None) => Ok(Flicker::Decay {
+                fade: Self::DEFAULT_FADE,
+            }),
+            ("decay", Some(fade)) => match fade.parse() {
+                Ok(fade) if (0.0..1.0).contains(&fade) => Ok(Flicker::Decay { fade }),
+                _ => Err(format!("decay fade `{}` must be from 0 up to 1", fade)),
+            },
+            ("or", None) => Ok(Flicker::Or {
+                frames: Self::DEFAULT_FRAMES,
+            }),
+            ("or", Some(frames)) => match frames.parse() {
+                Ok(frames) if (1..=Self::MAX_FRAMES).contains(&frames) => {
+                    Ok(Flicker::Or { frames })
+                }
+                _ => Err(format!(
+                    "`or` needs 1 to {} frames, not `{}`",
+                    Self::MAX_FRAMES,
+                    frames
+                )),
+            },
+            _ => Err(format!(
+                "flicker mode `{}` must be off, decay, blend or or",
+                value
+            )),
+        }
+    }
+}
+
+/// Draws frames from the emulator as a single texture, scaled with nearest-neighbour filtering.
+pub struct Screen {
+    texture: Option<TextureHandle>,
+    /// The resolution of the last frame uploaded.
+    size: [usize; 2],
+    format: PixelFormat,
+    pub scaling: Scaling,
+    pub aspect: Aspect,
+    pub flicker: Flicker,
+    pub crt: Crt,
+    /// The pixels of recent frames, newest first.
+    history: VecDeque<Box<[u32]>>,
+    /// The number of the newest frame in `history`.
+    last_number: u64,
+    /// How brightly each pixel still glows, from 1 when lit down towards 0.
+    glow: Vec<f32>,
+    /// The value each pixel last had while lit, which it fades from.
+    glow_values: Vec<u32>,
+}
+
+impl Screen {
+    pub fn new() -> Self {
+        Self {
+            texture: None,
+            size: [64, 32],
+            format: PixelFormat::Indexed,
+            scaling: Scaling::Integer,
+            aspect: Aspect::Square,
+            flicker: Flicker::Off,
+            crt: Crt::default(),
+            history: VecDeque::new(),
+            last_number: 0,
+            glow: Vec::new(),
+            glow_values: Vec::new(),
+        }
+    }
+
+    /// Turns a frame into colours and uploads it, replacing the last one. Uploading the same
+    /// frame again, after a change of palette or flicker mode, redraws it without counting it
+    /// as another frame.
+    pub fn upload(&mut self, ctx: &egui::Context, frame: &Frame, palette: &Palette) {
+        let pixels = self.picture(frame, palette);
+        // The CRT picture is already scaled up with the effects drawn in, so it is smoothed
+        // rather than scaled by whole pixels again.
+        let (image, options) = if self.crt.is_active() {
+            (self.crt.apply(&pixels, self.size), TextureOptions::LINEAR)
+        } else {
+            let image = ColorImage {
+                size: self.size,
+                pixels,
+            };
+            (image, TextureOptions::NEAREST)
+        };
+        match &mut self.texture {
+            Some(texture) => texture.set(image, options),
+            None => self.texture = Some(ctx.load_texture("screen", image, options)),
+        }
+    }
+
+    /// The colours of a frame with flicker reduction applied, before any CRT effects.
+    pub fn picture(&mut self, frame: &Frame, palette: &Palette) -> Vec<Color32> {
+        if [frame.width, frame.height] != self.size || frame.format != self.format {
+            self.size = [frame.width, frame.height];
+            self.format = frame.format;
+            self.history.clear();
+        }
+        if self.history.is_empty() || frame.number != self.last_number {
+            self.add_to_history(frame);
+        }
+
+        let format = frame.format;
+        let colour = |value: u32| pixel_colour(format, palette, value);
+        match self.flicker {
+            Flicker::Off => frame.pixels.iter().map(|&value| colour(value)).collect(),
+            Flicker::Decay { .. } => {
+                let off = colour(0);
+                self.glow
+                    .iter()
+                    .zip(&self.glow_values)
+                    .map(|(&glow, &value)| mix(off, colour(value), glow))
+                    .collect()
+            }
+            Flicker::Blend => {
+                let previous = self.history.get(1).unwrap_or(&self.history[0]);
+                self.history[0]
+                    .iter()
+                    .zip(previous.iter())
+                    .map(|(&new, &old)| mix(colour(old), colour(new), 0.5))
+                    .collect()
+            }
+            Flicker::Or { frames } => {
+                let frames: Vec<&[u32]> =
+                    self.history.iter().take(frames).map(|f| &f[..]).collect();
+                (0..frame.pixels.len())
+                    .map(|i| colour(combine(format, frames.iter().map(|f| f[i]))))
+                    .collect()
+            }
+        }
+    }
+
+    /// Remembers a new frame for blending, and fades or relights each pixel's glow by however
+    /// many frames have gone by since the last one.
+    fn add_to_history(&mut self, frame: &Frame) {
+        let elapsed = frame.number.saturating_sub(self.last_number).max(1);
+        self.last_number = frame.number;
+        if self.history.is_empty() {
+            self.glow = vec![0.0; frame.pixels.len()];
+            self.glow_values = vec![0; frame.pixels.len()];
+        } else {
+            // Frames skipped while the window was busy are taken to look like the one before.
+            let previous = self.history[0].clone();
+            for _ in 1..elapsed.min(Flicker::MAX_FRAMES as u64) {
+                self.history.push_front(previous.clone());
+            }
+        }
+        self.history.push_front(frame.pixels.clone());
+        self.history.truncate(Flicker::MAX_FRAMES);
+
+        let fade = match self.flicker {
+            Flicker::Decay { fade } => fade.powi(elapsed.min(60) as i32),
+            _ => 0.0,
+        };
+        for ((glow, glow_value), &value) in self
+            .glow
+            .iter_mut()
+            .zip(&mut self.glow_values)
+            .zip(frame.pixels.iter())
+        {
+            if is_lit(frame.format, value) {
+                *glow = 1.0;
+                *glow_value = value;
+            } else {
+                *glow *= fade;
+            }
+        }
+    }
+
+    /// Fills the rest of `ui` with the display, centred.
+    pub fn show(&self, ui: &mut egui::Ui) {
+        let (response, painter) = ui.allocate_painter(ui.available_size(), Sense::hover());
+        let area = response.rect;
+        let size = self.display_size(area.size(), ui.ctx().pixels_per_point());
+        let rect = Rect::from_center_size(area.center(), size);
+        if let Some(texture) = &self.texture {
+            let uv = Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0));
+            painter.image(texture.id(), rect, uv, Color32::WHITE);
+        }
+    }
+
+    /// The size to draw the display at in a space of `available` points.
+    fn display_size(&self, available: Vec2, pixels_per_point: f32) -> Vec2 {
+        let (width, height) = (self.size[0] as f32, self.size[1] as f32);
+        // Work in physical pixels so integer scaling lines up with the screen's own pixels.
+        let available = available * pixels_per_point;
+        let aspect = match self.aspect {
+            Aspect::Square => width / height,
+            Aspect::FourThree => 4.0 / 3.0,
+            Aspect::Stretch => available.x / available.y,
+        };
+        let mut size = if available.x / available.y > aspect {
+            Vec2::new(available.y * aspect, available.y)
+        } else {
+            Vec2::new(available.x, available.x / aspect)
+        };
+        if self.scaling == Scaling::Integer {
+            let x_scale = (size.x / width).floor().max(1.0);
+            let y_scale = (size.y / height).floor().max(1.0);
+            size = match self.aspect {
+                Aspect::Square => Vec2::new(width, height) * x_scale.min(y_scale),
+                Aspect::FourThree | Aspect::Stretch => Vec2::new(width * x_scale, height * y_scale),
+            };
+        }
+        size / pixels_per_point
+    }
+}
+
+fn is_lit(format: PixelFormat, value: u32) -> bool {
+    match format {
+        PixelFormat::Indexed => value != 0,
+        PixelFormat::Argb => value & 0xFF_FFFF != 0,
+    }
+}
+
+/// The value a pixel is drawn with when it was `values` over several frames, newest first:
+/// lit in every plane it was lit in, or the newest colour it was lit in.
+fn combine(format: PixelFormat, mut values: impl Iterator<Item = u32>) -> u32 {
+    match format {
+        PixelFormat::Indexed => values.fold(0, |planes, value| planes | value),
+        PixelFormat::Argb => values.find(|&value| is_lit(format, value)).unwrap_or(0),
+    }
+}
+
+/// Mixes `amount` of `to` into `from`.
+fn mix(from: Color32, to: Color32, amount: f32) -> Color32 {
+    let channel = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * amount).round() as u8;
+    Color32::from_rgb(
+        channel(from.r(), to.r()),
+        channel(from.g(), to.g()),
+        channel(from.b(), to.b()),
+    )
+}
+
+pub fn pixel_colour(format: PixelFormat, palette: &Palette, value: u32) -> Color32 {
+    match format {
+        PixelFormat::Indexed => palette.colour(value),
+        PixelFormat::Argb => {
+            let [_, r, g, b] = value.to_be_bytes();
+            Color32::from_rgb(r, g, b)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(number: u64, lit: bool) -> Frame {
+        Frame {
+            number,
+            width: 1,
+            height: 1,
+            format: PixelFormat::Indexed,
+            pixels: Box::new([lit as u32]),
+        }
+    }
+
+    /// The colour of the single pixel after each frame in turn.
+    fn pictures(flicker: Flicker, frames: &[Frame]) -> Vec<Color32> {
+        let mut screen = Screen::new();
+        screen.flicker = flicker;
+        let palette = Palette::default();
+        frames
+            .iter()
+            .map(|frame| screen.picture(frame, &palette)[0])
+            .collect()
+    }
+
+    #[test]
+    fn or_looks_back_over_frames_in_time() {
+        let palette = Palette::default();
+        let (off, on) = (palette.colours[0], palette.colours[1]);
+        let frames = [
+            frame(1, true),
+            frame(2, false),
+            frame(3, false),
+            frame(4, false),
+        ];
+        assert_eq!(
+            pictures(Flicker::Or { frames: 3 }, &frames),
+            [on, on, on, off]
+        );
+    }
+
+    #[test]
+    fn skipped_frames_repeat_the_one_before() {
+        let palette = Palette::default();
+        let (off, on) = (palette.colours[0], palette.colours[1]);
+        let frames = [
+            frame(1, true),
+            frame(3, false),
+            frame(4, false),
+            frame(5, false),
+        ];
+        assert_eq!(
+            pictures(Flicker::Or { frames: 3 }, &frames),
+            [on, on, on, off]
+        );
+    }
+
+    #[test]
+    fn blend_mixes_the_last_two_frames() {
+        let palette = Palette::default();
+        let (off, on) = (palette.colours[0], palette.colours[1]);
+        let frames = [frame(1, true), frame(2, false), frame(3, false)];
+        assert_eq!(
+            pictures(Flicker::Blend, &frames),
+            [on, mix(on, off, 0.5), off]
+        );
+    }
+
+    #[test]
+    fn decay_keeps_fading_while_the_display_is_unchanged() {
+        let palette = Palette::default();
+        let (off, on) = (palette.colours[0], palette.colours[1]);
+        let frames = [
+            frame(1, true),
+            frame(2, false),
+            frame(3, false),
+            frame(5, false),
+        ];
+        assert_eq!(
+            pictures(Flicker::Decay { fade: 0.5 }, &frames),
+            [
+                on,
+                mix(off, on, 0.5),
+                mix(off, on, 0.25),
+                mix(off, on, 0.0625)
+            ]
+        );
+    }
+}
@@ -0,0 +1,111 @@
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+use eframe::epaint::{Color32, ColorImage};
+
+use crate::{chip8::display::Frame, crt::Crt, palette::Palette, recording::Format, screen};
+
+/// Where screenshots and recordings are written and how they are named.
+pub struct Screenshots {
+    pub directory: PathBuf,
+    /// The start of each file name, normally the ROM's name.
+    pub name: String,
+    /// Screen pixels per display pixel.
+    pub scale: usize,
+    /// The format recordings started from the window are made in.
+    pub recording_format: Format,
+}
+
+impl Screenshots {
+    /// The file for a screenshot of frame `number`, marked if it is a raw one.
+    pub fn path(&self, number: u64, raw: bool) -> PathBuf {
+        let suffix = if raw { "-raw" } else { "" };
+        self.directory
+            .join(format!("{}-{}{}.png", self.name, number, suffix))
+    }
+
+    /// The file for a recording that starts at frame `number`.
+    pub fn recording_path(&self, number: u64) -> PathBuf {
+        let extension = self.recording_format.extension();
+        // Keep animated PNGs apart from screenshots of the same frame.
+        let suffix = if self.recording_format == Format::Apng {
+            "-recording"
+        } else {
+            ""
+        };
+        self.directory
+            .join(format!("{}-{}{}.{}", self.name, number, suffix, extension))
+    }
+}
+
+/// The name screenshots of a ROM are given, from its file name.
+pub fn rom_name(rom: &Path) -> String {
+    rom.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "chip8".to_string())
+}
+
+/// A frame exactly as the emulator drew it: one image pixel per display pixel, in the palette's
+/// colours and without flicker reduction or effects. Suited to comparing against fixtures.
+pub fn raw(frame: &Frame, palette: &Palette) -> ColorImage {
+    let pixels = frame
+        .pixels
+        .iter()
+        .map(|&value| screen::pixel_colour(frame.format, palette, value))
+        .collect();
+    ColorImage {
+        size: [frame.width, frame.height],
+        pixels,
+    }
+}
+
+/// A picture of `size` display pixels drawn with `crt` and scaled up to `scale` image pixels
+/// per display pixel.
+pub fn render(pixels: Vec<Color32>, size: [usize; 2], crt: &Crt, scale: usize) -> ColorImage {
+    let image = if crt.is_active() {
+        crt.apply(&pixels, size)
+    } else {
+        ColorImage { size, pixels }
+    };
+    resize(&image, [size[0] * scale, size[1] * scale])
+}
+
+/// Scales an image to `size` by picking the nearest pixel.
+pub fn resize(image: &ColorImage, size: [usize; 2]) -> ColorImage {
+    if image.size == size {
+        return image.clone();
+    }
+    let [width, height] = size;
+    let pixels = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let source_x = x * image.size[0] / width;
+            let source_y = y * image.size[1] / height;
+            image.pixels[source_y * image.size[0] + source_x]
+        })
+        .collect();
+    ColorImage { size, pixels }
+}
+
+/// Writes an image to `path` as an 8-bit RGB PNG.
+pub fn save_png(image: &ColorImage, path: &Path) -> Result<(), String> {
+    let error =
+        |error: &dyn std::fmt::Display| format!("could not write {}: {}", path.display(), error);
+    let file = File::create(path).map_err(|e| error(&e))?;
+    let [width, height] = image.size;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let data: Vec<u8> = image
+        .pixels
+        .iter()
+        .flat_map(|colour| [colour.r(), colour.g(), colour.b()])
+        .collect();
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&data))
+        .map_err(|e| error(&e))
+}
@@ -0,0 +1,651 @@
+use std::{
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use crossbeam_channel::Sender;
+
+use crate::{
+    chip8::{
+        keypad::{Event, Key},
+        platform::Platform,
+        settings::Settings,
+        Chip8, CpuState,
+    },
+    dump,
+    movie::Movie,
+};
+
+/// A test of a ROM: how to run it, keys to press, and what to expect along the way.
+///
+/// Scripts have one statement per line, and text after `#` is a comment:
+///
+/// ```text
+/// rom pong.ch8                   # the ROM, relative to the script
+/// rom eti660 pong-600.ch8        # a build of it to use on one platform instead
+/// platform chip8                 # optional, as are ips, quirk and seed
+/// ips 600
+/// quirk assign-shift=false
+/// seed 1
+/// platforms chip8 megachip      # the platforms `chip8 selftest` runs it on, otherwise all
+/// press 5 at frame 30            # held for one frame unless `for 10 frames` follows
+/// run 120 frames
+/// expect screen matches pong-120.txt
+/// expect screen hash 1f0c...
+/// expect v3 == 0x10
+/// expect mem[0x300..0x303] == [1, 2, 3]
+/// ```
+///
+/// Expectations can test `v0` to `vf`, `i`, `pc`, `dt`, `st`, `mem[a]` and `mem[a..b]` with
+/// `==` or `!=`. Golden screens are text as `chip8 headless --dump screen` writes them, with
+/// `#` for lit pixels and `.` for unlit ones. `{platform}` in a golden screen's
+/// file name is replaced with the name of the platform being run, for scripts run on several.
+pub struct Script {
+    rom: PathBuf,
+    /// Builds of the ROM for platforms that load programs somewhere else.
+    platform_roms: Vec<(Platform, PathBuf)>,
+    pub platform: Option<Platform>,
+    pub ips: Option<u32>,
+    pub quirks: Vec<(String, bool)>,
+    pub seed: u64,
+    /// The platforms the conformance suite runs the script on.
+    pub platforms: Vec<Platform>,
+    presses: Movie,
+    /// The runs and expectations in order, with the line each is on.
+    steps: Vec<(usize, Step)>,
+}
+
+enum Step {
+    Run(u32),
+    Expect(Expectation),
+}
+
+enum Expectation {
+    /// The screen matches the golden screen in a file.
+    Screen(PathBuf),
+    ScreenHash(String),
+    Value {
+        target: Target,
+        equal: bool,
+        expected: Vec<u32>,
+    },
+}
+
+#[derive(Clone)]
+enum Target {
+    Register(u8),
+    Index,
+    ProgramCounter,
+    DelayTimer,
+    SoundTimer,
+    Memory(Range<usize>),
+}
+
+impl Target {
+    fn parse(text: &str) -> Option<Self> {
+        let text = text.to_ascii_lowercase();
+        match text.as_str() {
+            "i" => return Some(Target::Index),
+            "pc" => return Some(Target::ProgramCounter),
+            "dt" => return Some(Target::DelayTimer),
+            "st" => return Some(Target::SoundTimer),
+            _ => {}
+        }
+        if let Some(register) = text.strip_prefix('v') {
+            return u8::from_str_radix(register, 16)
+                .ok()
+                .filter(|&register| register < 16 && text.len() == 2)
+                .map(Target::Register);
+        }
+        let address = text.strip_prefix("mem[")?.strip_suffix(']')?;
+        let range = match address.split_once("..") {
+            Some((start, end)) => parse_number(start)? as usize..parse_number(end)? as usize,
+            None => {
+                let start = parse_number(address)? as usize;
+                start..start + 1
+            }
+        };
+        (!range.is_empty()).then_some(Target::Memory(range))
+    }
+
+    /// The value or bytes the target has.
+    fn read(&self, state: &CpuState, memory: &[u8]) -> Result<Vec<u32>, String> {
+        Ok(match self {
+            Target::Register(register) => vec![state.registers[*register as usize] as u32],
+            Target::Index => vec![state.index_register],
+            Target::ProgramCounter => vec![state.program_counter as u32],
+            Target::DelayTimer => vec![state.delay_timer as u32],
+            Target::SoundTimer => vec![state.sound_timer as u32],
+            Target::Memory(range) => memory
+                .get(range.clone())
+                .ok_or_else(|| format!("memory ends at {:#x}", memory.len()))?
+                .iter()
+                .map(|&byte| byte as u32)
+                .collect(),
+        })
+    }
+}
+
+impl std::fmt::Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Target::Register(register) => write!(f, "v{:x}", register),
+            Target::Index => write!(f, "i"),
+            Target::ProgramCounter => write!(f, "pc"),
+            Target::DelayTimer => write!(f, "dt"),
+            Target::SoundTimer => write!(f, "st"),
+            Target::Memory(range) if range.len() == 1 => write!(f, "mem[{:#x}]", range.start),
+            Target::Memory(range) => write!(f, "mem[{:#x}..{:#x}]", range.start, range.end),
+        }
+    }
+}
+
+/// What came of running a script.
+pub struct Outcome {
+    /// The expectations that were checked.
+    pub checks: usize,
+    /// What went wrong, one message per failed expectation or error.
+    pub failures: Vec<String>,
+}
+
+impl Script {
+    /// Parses a script, finding the files it names relative to `path`, where it came from.
+    pub fn parse(text: &str, path: &Path) -> Result<Self, String> {
+        let directory = path.parent().unwrap_or(Path::new(""));
+        let mut rom = None;
+        let mut script = Self {
+            rom: PathBuf::new(),
+            platform_roms: Vec::new(),
+            platform: None,
+            ips: None,
+            quirks: Vec::new(),
+            seed: 0,
+            platforms: Platform::ALL.to_vec(),
+            presses: Movie::default(),
+            steps: Vec::new(),
+        };
+        for (index, line) in text.lines().enumerate() {
+            let number = index + 1;
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |message: &str| format!("line {}: {}", number, message);
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words[..] {
+                ["rom", file] => rom = Some(directory.join(file)),
+                ["rom", name, file] => {
+                    let platform = Platform::from_name(name)
+                        .ok_or_else(|| error(&format!("unknown platform `{}`", name)))?;
+                    script.platform_roms.push((platform, directory.join(file)));
+                }
+                ["platform", name] => {
+                    script.platform = Some(
+                        Platform::from_name(name)
+                            .ok_or_else(|| error(&format!("unknown platform `{}`", name)))?,
+                    )
+                }
+                ["ips", rate] => {
+                    script.ips = Some(
+                        parse_number(rate)
+                            .filter(|&rate| rate > 0)
+                            .ok_or_else(|| error("ips must be a positive number"))?,
+                    )
+                }
+                ["quirk", quirk] => {
+                    let (name, value) = quirk
+                        .split_once('=')
+                        .filter(|(name, _)| Settings::QUIRKS.contains(name))
+                        .ok_or_else(|| error(&format!("unknown quirk `{}`", quirk)))?;
+                    let value = value
+                        .parse()
+                        .map_err(|_| error("quirks must be set to true or false"))?;
+                    script.quirks.push((name.to_string(), value));
+                }
+                ["seed", seed] => {
+                    script.seed = seed
+                        .parse()
+                        .map_err(|_| error("the seed must be a number"))?
+                }
+                ["platforms", ..] => {
+                    script.platforms = words[1..]
+                        .iter()
+                        .map(|name| {
+                            Platform::from_name(name)
+                                .ok_or_else(|| error(&format!("unknown platform `{}`", name)))
+                        })
+                        .collect::<Result<_, _>>()?;
+                }
+                ["press", key, "at", "frame", frame, ..] => {
+                    let key = u8::from_str_radix(key, 16)
+                        .ok()
+                        .and_then(Key::from_number)
+                        .ok_or_else(|| error(&format!("`{}` is not a key 0-F", key)))?;
+                    let frame = parse_number(frame)
+                        .filter(|&frame| frame > 0)
+                        .ok_or_else(|| error("frames count from 1"))?;
+                    let frames = match words[5..] {
+                        [] => 1,
+                        ["for", count, "frames" | "frame"] => parse_number(count)
+                            .filter(|&count| count > 0)
+                            .ok_or_else(|| error("a key must be held for at least a frame"))?,
+                        _ => {
+                            return Err(error(
+                                "expected `press <key> at frame <n> [for <n> frames]`",
+                            ))
+                        }
+                    };
+                    script.presses.add(frame, Event::KeyDown(key));
+                    script.presses.add(frame + frames, Event::KeyUp(key));
+                }
+                ["run", count, "frames" | "frame"] => {
+                    let count = parse_number(count)
+                        .filter(|&count| count > 0)
+                        .ok_or_else(|| error("run needs a positive number of frames"))?;
+                    script.steps.push((number, Step::Run(count)));
+                }
+                ["expect", "screen", "matches", file] => script.steps.push((
+                    number,
+                    Step::Expect(Expectation::Screen(directory.join(file))),
+                )),
+                ["expect", "screen", "hash", hash] => script.steps.push((
+                    number,
+                    Step::Expect(Expectation::ScreenHash(hash.to_ascii_lowercase())),
+                )),
+                ["expect", ..] => {
+                    let expectation = parse_value_expectation(&line["expect".len()..])
+                        .map_err(|message| error(&message))?;
+                    script.steps.push((number, Step::Expect(expectation)));
+                }
+                _ => return Err(error(&format!("`{}` is not a statement", line))),
+            }
+        }
+        script.rom = rom.ok_or("the script must name a ROM with `rom <file>`")?;
+        Ok(script)
+    }
+
+    /// The ROM to run on `platform`, or on the script's own platform if that's `None`.
+    pub fn rom(&self, platform: Option<Platform>) -> &Path {
+        let platform = platform.or(self.platform);
+        self.platform_roms
+            .iter()
+            .find(|(rom_platform, _)| Some(*rom_platform) == platform)
+            .map_or(&self.rom, |(_, rom)| rom)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|error| format!("could not read {}: {}", path.display(), error))?;
+        Self::parse(&text, path)
+    }
+
+    /// Runs the script on `chip8`, which has the script's ROM loaded, sending key presses
+    /// through `events`. A fault ends the run. With `update`, golden screens that don't match
+    /// are written with the screen instead of failing.
+    pub fn run(
+        &self,
+        chip8: &mut Chip8,
+        events: &Sender<Event>,
+        instructions_per_frame: u32,
+        update: bool,
+    ) -> Outcome {
+        let mut outcome = Outcome {
+            checks: 0,
+            failures: Vec::new(),
+        };
+        let mut frame = 0;
+        for (line, step) in &self.steps {
+            match step {
+                Step::Run(count) => {
+                    for _ in 0..*count {
+                        frame += 1;
+                        for event in self.presses.events_at(frame) {
+                            let _ = events.send(event);
+                        }
+                        if let Err(fault) = chip8.try_run_frame(instructions_per_frame) {
+                            outcome.failures.push(format!(
+                                "line {}: the program faulted in frame {}: {}",
+                                line, frame, fault
+                            ));
+                            return outcome;
+                        }
+                    }
+                }
+                Step::Expect(expectation) => {
+                    outcome.checks += 1;
+                    if let Err(message) = expectation.check(chip8, update) {
+                        outcome.failures.push(format!("line {}: {}", line, message));
+                    }
+                }
+            }
+        }
+        outcome
+    }
+}
+
+impl Expectation {
+    fn check(&self, chip8: &Chip8, update: bool) -> Result<(), String> {
+        match self {
+            Expectation::Screen(path) => {
+                let path = &PathBuf::from(
+                    path.to_string_lossy()
+                        .replace("{platform}", chip8.platform().name()),
+                );
+                let actual = dump::text_screen(&chip8.screen().frame(0));
+                let expected = match fs::read_to_string(path) {
+                    Ok(expected) => expected,
+                    Err(_) if update => String::new(),
+                    Err(error) => {
+                        return Err(format!("could not read {}: {}", path.display(), error))
+                    }
+                };
+                match screen_diff(&expected, &actual) {
+                    None => Ok(()),
+                    Some(_) if update => save_screen(path, &actual),
+                    Some(diff) => Err(format!(
+                        "the screen does not match {} (+ lit only on the screen, - lit only \
+                         in the file):\n{}",
+                        path.display(),
+                        diff.trim_end()
+                    )),
+                }
+            }
+            Expectation::ScreenHash(expected) => {
+                let actual = dump::screen_hash(&chip8.screen().frame(0));
+                if actual == *expected {
+                    Ok(())
+                } else {
+                    Err(format!("the screen hash is {}, not {}", actual, expected))
+                }
+            }
+            Expectation::Value {
+                target,
+                equal,
+                expected,
+            } => {
+                let actual = target.read(&chip8.cpu_state(), chip8.memory())?;
+                if (actual == *expected) == *equal {
+                    return Ok(());
+                }
+                let operator = if *equal { "==" } else { "!=" };
+                Err(format!(
+                    "expected {} {} {}, but it is {}",
+                    target,
+                    operator,
+                    format_values(expected),
+                    format_values(&actual)
+                ))
+            }
+        }
+    }
+}
+
+/// Writes a golden screen, making its directory if need be.
+fn save_screen(path: &Path, screen: &str) -> Result<(), String> {
+    let error = |error: std::io::Error| format!("could not write {}: {}", path.display(), error);
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory).map_err(error)?;
+    }
+    fs::write(path, screen).map_err(error)
+}
+
+/// Parses `<target> == <value>` or `<target> != <value>`, where the value is a number or a
+/// list of bytes in brackets.
+fn parse_value_expectation(text: &str) -> Result<Expectation, String> {
+    let (target, equal, value) = if let Some((target, value)) = text.split_once("==") {
+        (target, true, value)
+    } else if let Some((target, value)) = text.split_once("!=") {
+        (target, false, value)
+    } else {
+        return Err(format!(
+            "`expect{}` should look like `expect v3 == 0x10`",
+            text
+        ));
+    };
+    let target = Target::parse(&target.replace(' ', ""))
+        .ok_or_else(|| format!("`{}` is not a register or memory", target.trim()))?;
+    let value = value.trim();
+    let expected = match value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+        Some(list) => list
+            .split(',')
+            .map(|byte| parse_number(byte.trim()).filter(|&byte| byte < 256))
+            .collect::<Option<Vec<u32>>>(),
+        None => parse_number(value).map(|number| vec![number]),
+    }
+    .ok_or_else(|| format!("`{}` is not a number or a list of bytes", value))?;
+    if let Target::Memory(range) = &target {
+        if range.len() != expected.len() {
+            return Err(format!(
+                "{} is {} bytes, but {} are given",
+                target,
+                range.len(),
+                expected.len()
+            ));
+        }
+    } else if expected.len() != 1 {
+        return Err(format!("{} is a single value", target));
+    }
+    Ok(Expectation::Value {
+        target,
+        equal,
+        expected,
+    })
+}
+
+fn parse_number(text: &str) -> Option<u32> {
+    match text.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+fn format_values(values: &[u32]) -> String {
+    let values: Vec<String> = values
+        .iter()
+        .map(|value| format!("{:#04x}", value))
+        .collect();
+    if values.len() == 1 {
+        values[0].clone()
+    } else {
+        format!("[{}]", values.join(", "))
+    }
+}
+
+/// Whether a character of a text screen is a lit pixel, an unlit one, or neither.
+fn pixel(character: char) -> Option<bool> {
+    match character {
+        '#' => Some(true),
+        '.' => Some(false),
+        _ => None,
+    }
+}
+
+/// Compares two text screens pixel by pixel, returning a picture of where they differ if they
+/// do. Lit pixels are `#`, unlit ones `.`, and ones that differ `+` or `-`.
+fn screen_diff(expected: &str, actual: &str) -> Option<String> {
+    let rows = |text: &str| -> Vec<Vec<bool>> {
+        text.lines()
+            .map(|line| line.chars().filter_map(pixel).collect::<Vec<bool>>())
+            .filter(|row| !row.is_empty())
+            .collect()
+    };
+    let (expected, actual) = (rows(expected), rows(actual));
+    if expected == actual {
+        return None;
+    }
+    let height = expected.len().max(actual.len());
+    let mut diff = String::new();
+    for y in 0..height {
+        let expected_row = expected.get(y).map(Vec::as_slice).unwrap_or_default();
+        let actual_row = actual.get(y).map(Vec::as_slice).unwrap_or_default();
+        let width = expected_row.len().max(actual_row.len());
+        let line: String = (0..width)
+            .map(|x| {
+                let expected = expected_row.get(x).copied().unwrap_or(false);
+                let actual = actual_row.get(x).copied().unwrap_or(false);
+                match (expected, actual) {
+                    (true, true) => '#',
+                    (false, false) => '.',
+                    (false, true) => '+',
+                    (true, false) => '-',
+                }
+            })
+            .collect();
+        diff.push_str(&line);
+        diff.push('\n');
+    }
+    if expected.len() != actual.len()
+        || expected.first().map(Vec::len) != actual.first().map(Vec::len)
+    {
+        diff.push_str(&format!(
+            "the file is {}x{} but the screen is {}x{}\n",
+            expected.first().map_or(0, Vec::len),
+            expected.len(),
+            actual.first().map_or(0, Vec::len),
+            actual.len()
+        ));
+    }
+    Some(diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use crossbeam_channel::unbounded;
+
+    use crate::chip8::display;
+
+    use super::*;
+
+    fn parse(text: &str) -> Result<Script, String> {
+        Script::parse(text, Path::new("tests/pong.test"))
+    }
+
+    fn machine(program: &[u8]) -> (Chip8, Sender<Event>) {
+        let (frames, _) = display::frame_slot();
+        let (events, event_receiver) = unbounded();
+        let (_, controls) = unbounded();
+        let (statuses, _) = unbounded();
+        let settings = Settings {
+            timing: crate::chip8::settings::Timing::InstructionsPerSecond(600),
+            ..Settings::default()
+        };
+        let chip8 = Chip8::new(
+            settings,
+            program,
+            frames,
+            event_receiver,
+            controls,
+            statuses,
+        );
+        (chip8.unwrap(), events)
+    }
+
+    #[test]
+    fn parses_a_script() {
+        let script = parse(
+            "rom pong.ch8  # the game\n\
+             rom eti660 pong-600.ch8\n\
+             platforms chip8 eti660\n\
+             ips 1200\n\
+             quirk assign-shift=false\n\
+             seed 7\n\
+             press 5 at frame 30 for 10 frames\n\
+             run 120 frames\n\
+             expect screen matches pong-{platform}.txt\n\
+             expect v3 == 0x10\n",
+        )
+        .unwrap();
+        assert_eq!(script.rom(None), Path::new("tests/pong.ch8"));
+        assert_eq!(
+            script.rom(Some(Platform::Chip8)),
+            Path::new("tests/pong.ch8")
+        );
+        assert_eq!(
+            script.rom(Some(Platform::Eti660)),
+            Path::new("tests/pong-600.ch8")
+        );
+        assert_eq!(script.platforms, [Platform::Chip8, Platform::Eti660]);
+        assert_eq!(script.ips, Some(1200));
+        assert_eq!(script.quirks, [("assign-shift".to_string(), false)]);
+        assert_eq!(script.seed, 7);
+        let presses: Vec<Event> = script.presses.events_at(30).collect();
+        assert_eq!(presses, [Event::KeyDown(Key::KeyW)]);
+        let releases: Vec<Event> = script.presses.events_at(40).collect();
+        assert_eq!(releases, [Event::KeyUp(Key::KeyW)]);
+        let lines: Vec<usize> = script.steps.iter().map(|(line, _)| *line).collect();
+        assert_eq!(lines, [8, 9, 10]);
+    }
+
+    #[test]
+    fn uses_the_scripts_platform_for_its_rom() {
+        let script = parse("rom a.ch8\nrom eti660 b.ch8\nplatform eti660\n").unwrap();
+        assert_eq!(script.rom(None), Path::new("tests/b.ch8"));
+    }
+
+    #[test]
+    fn rejects_bad_statements() {
+        let error = |text: &str| parse(text).err().unwrap();
+        assert_eq!(
+            error("run 5 frames"),
+            "the script must name a ROM with `rom <file>`"
+        );
+        assert_eq!(
+            error("rom a.ch8\nrom vip b.ch8"),
+            "line 2: unknown platform `vip`"
+        );
+        assert_eq!(
+            error("rom a.ch8\npress G at frame 1"),
+            "line 2: `G` is not a key 0-F"
+        );
+        assert_eq!(
+            error("rom a.ch8\nexpect mem[0x300..0x303] == [1, 2]"),
+            "line 2: mem[0x300..0x303] is 3 bytes, but 2 are given"
+        );
+        assert_eq!(
+            error("rom a.ch8\nexpect v3 = 1"),
+            "line 2: `expect v3 = 1` should look like `expect v3 == 0x10`"
+        );
+        assert_eq!(
+            error("rom a.ch8\njump 0x200"),
+            "line 2: `jump 0x200` is not a statement"
+        );
+    }
+
+    #[test]
+    fn checks_registers_and_memory() {
+        let script = parse(
+            "rom a.ch8\n\
+             run 1 frame\n\
+             expect v0 == 0x2a\n\
+             expect mem[0x200..0x202] == [0x60, 0x2a]\n\
+             expect pc != 0x204\n\
+             expect v1 == 1\n",
+        )
+        .unwrap();
+        // V0 = 0x2A, then loop forever.
+        let (mut chip8, events) = machine(&[0x60, 0x2A, 0x12, 0x02]);
+        let outcome = script.run(&mut chip8, &events, 10, false);
+        assert_eq!(outcome.checks, 4);
+        assert_eq!(
+            outcome.failures,
+            ["line 6: expected v1 == 0x01, but it is 0x00"]
+        );
+    }
+
+    #[test]
+    fn reports_faults_with_the_frame() {
+        let script = parse("rom a.ch8\nrun 2 frames\nexpect v0 == 0\n").unwrap();
+        let (mut chip8, events) = machine(&[0xFF, 0xFF]);
+        let outcome = script.run(&mut chip8, &events, 10, false);
+        assert_eq!(outcome.checks, 0);
+        assert_eq!(outcome.failures.len(), 1);
+        assert!(outcome.failures[0].starts_with("line 2: the program faulted in frame 1: "));
+    }
+
+    #[test]
+    fn draws_where_screens_differ() {
+        assert_eq!(screen_diff("#.\n..\n", "#.\n..\n"), None);
+        assert_eq!(screen_diff("#.\n..\n", ".#\n..\n").unwrap(), "-+\n..\n");
+    }
+}
@@ -0,0 +1,651 @@
+use std::{
+    io::{self, Stdout, Write},
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::TryRecvError;
+use crossterm::{
+    cursor,
+    event::{
+        self, Event as TerminalEvent, KeyCode, KeyEvent, KeyEventKind, KeyModifiers,
+        KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
+    queue,
+    style::{self, Color},
+    terminal::{self, ClearType},
+};
+use eframe::{egui, epaint::Color32};
+
+use crate::{
+    app::Channels,
+    chip8::{
+        control::{Control, Reset},
+        display::{Frame, PixelFormat},
+        keypad::{Event, Key},
+        platform::Platform,
+        status::{State, Status},
+    },
+    keymap::{self, Keymap},
+    palette::Palette,
+    screen,
+};
+
+/// How display pixels are drawn with text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Render {
+    /// Two pixels to a character with `▀`, the top one in the foreground colour and the bottom
+    /// one in the background colour, so every pixel keeps its own colour.
+    HalfBlock,
+    /// Eight pixels to a character with Braille dots, for high resolution games in small
+    /// terminals. A character has only one lit colour.
+    Braille,
+}
+
+impl Render {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "half" => Ok(Render::HalfBlock),
+            "braille" => Ok(Render::Braille),
+            _ => Err(format!("render mode `{}` must be half or braille", value)),
+        }
+    }
+
+    /// The display pixels across and down each character covers.
+    fn cell_size(self) -> (usize, usize) {
+        match self {
+            Render::HalfBlock => (1, 2),
+            Render::Braille => (2, 4),
+        }
+    }
+}
+
+/// How often the terminal is redrawn.
+const FRAME_TIME: Duration = Duration::from_micros(16_667);
+
+/// How long a key counts as held after the terminal last reported it, when the terminal only
+/// reports presses. Holding a key down keeps it held through the terminal's key repeat.
+const HOLD_TIME: Duration = Duration::from_millis(150);
+
+/// The dot for each pixel of a Braille character's 2x4 block, by row then column.
+const BRAILLE_DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Cell {
+    character: char,
+    foreground: Color32,
+    background: Color32,
+}
+
+/// Runs the emulator in the terminal until the user quits or the emulator thread stops.
+pub fn run(
+    channels: Channels,
+    palette: Palette,
+    keymap: Keymap,
+    render: Render,
+    name: String,
+) -> Result<(), String> {
+    let mut terminal = Terminal::open().map_err(|error| error.to_string())?;
+    let mut tui = Tui::new(channels, palette, keymap, render, name, terminal.releases);
+    let result = tui.run(&mut terminal.out);
+    tui.send_control(Control::Shutdown);
+    result.map_err(|error| error.to_string())
+}
+
+/// Puts the terminal into raw mode on the alternate screen, and puts it back when dropped, even
+/// when unwinding from a panic.
+struct Terminal {
+    out: Stdout,
+    /// Whether the terminal reports key releases, so keys are held for as long as they are down.
+    releases: bool,
+}
+
+impl Terminal {
+    fn open() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        let mut out = io::stdout();
+        queue!(
+            out,
+            terminal::EnterAlternateScreen,
+            cursor::Hide,
+            terminal::Clear(ClearType::All)
+        )?;
+        let releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        if releases {
+            queue!(
+                out,
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )?;
+        }
+        out.flush()?;
+        Ok(Self { out, releases })
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let mut out = io::stdout();
+        if self.releases {
+            let _ = queue!(out, PopKeyboardEnhancementFlags);
+        }
+        let _ = queue!(
+            out,
+            style::ResetColor,
+            cursor::Show,
+            terminal::LeaveAlternateScreen
+        );
+        let _ = out.flush();
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+struct Tui {
+    channels: Channels,
+    palette: Palette,
+    keymap: Keymap,
+    render: Render,
+    /// The ROM's name, shown in the status line.
+    name: String,
+    state: State,
+    error: Option<String>,
+    rom: Option<(usize, Platform)>,
+    halted_at: Option<u16>,
+    sound_on: bool,
+    /// Frames emulated and instructions per second, as last reported.
+    stats: Option<(u64, u64)>,
+    /// The last complete frame from the emulator.
+    frame: Frame,
+    /// Whether the frame has changed since it was drawn.
+    changed: bool,
+    /// For each CHIP-8 key that is down, when it is due to be released if the terminal does not
+    /// report releases. Otherwise keys stay down until they are released.
+    held: [Option<Instant>; 16],
+    releases: bool,
+    /// The characters on the terminal, so only the ones that change are redrawn.
+    drawn: Vec<Cell>,
+    /// The size in characters of the picture on the terminal, or zero once it needs drawing again
+    /// from scratch.
+    drawn_size: (usize, usize),
+    drawn_status: String,
+}
+
+impl Tui {
+    fn new(
+        channels: Channels,
+        palette: Palette,
+        keymap: Keymap,
+        render: Render,
+        name: String,
+        releases: bool,
+    ) -> Self {
+        Self {
+            channels,
+            palette,
+            keymap,
+            render,
+            name,
+            state: State::Running,
+            error: None,
+            rom: None,
+            halted_at: None,
+            sound_on: false,
+            stats: None,
+            frame: Frame {
+                number: 0,
+                width: 64,
+                height: 32,
+                format: PixelFormat::Indexed,
+                pixels: vec![0; 64 * 32].into_boxed_slice(),
+            },
+            changed: true,
+            held: [None; 16],
+            releases,
+            drawn: Vec::new(),
+            drawn_size: (0, 0),
+            drawn_status: String::new(),
+        }
+    }
+
+    fn run(&mut self, out: &mut Stdout) -> io::Result<()> {
+        let mut next_frame = Instant::now();
+        loop {
+            let now = Instant::now();
+            if now < next_frame {
+                if event::poll(next_frame - now)? && !self.handle_event(event::read()?) {
+                    return Ok(());
+                }
+                continue;
+            }
+            next_frame += FRAME_TIME;
+            // Don't try to catch up after falling behind, such as while the process was stopped.
+            if next_frame < now {
+                next_frame = now + FRAME_TIME;
+            }
+
+            self.release_keys(now);
+            if !self.receive_statuses(out)? {
+                return Ok(());
+            }
+            if let Some(frame) = self.channels.frames.latest() {
+                self.frame = frame;
+                self.changed = true;
+            }
+            self.draw(out)?;
+        }
+    }
+
+    fn send_event(&self, event: Event) {
+        let _ = self.channels.events.send(event);
+    }
+
+    fn send_control(&self, control: Control) {
+        let _ = self.channels.controls.send(control);
+    }
+
+    /// Handles a key press, release or resize. Returns false when the user quits.
+    fn handle_event(&mut self, event: TerminalEvent) -> bool {
+        match event {
+            TerminalEvent::Key(key) => return self.handle_key(key),
+            // Redraw everything, since the terminal may have moved or dropped what was there.
+            TerminalEvent::Resize(..) => self.drawn_size = (0, 0),
+            _ => {}
+        }
+        true
+    }
+
+    fn handle_key(&mut self, event: KeyEvent) -> bool {
+        let pressed = event.kind != KeyEventKind::Release;
+        if pressed {
+            match event.code {
+                KeyCode::Esc => return false,
+                KeyCode::Char('c') if event.modifiers.contains(KeyModifiers::CONTROL) => {
+                    return false
+                }
+                KeyCode::F(5) => {
+                    let reset = if event.modifiers.contains(KeyModifiers::SHIFT) {
+                        Reset::Hard
+                    } else {
+                        Reset::Soft
+                    };
+                    self.send_control(Control::Reset(reset));
+                    self.error = None;
+                    return true;
+                }
+                KeyCode::F(6) => {
+                    match self.state {
+                        State::Running => self.send_control(Control::Pause),
+                        State::Paused => self.send_control(Control::Resume),
+                        _ => {}
+                    }
+                    return true;
+                }
+                KeyCode::F(7) => {
+                    if self.state == State::Paused {
+                        self.send_control(Control::Step);
+                    }
+                    return true;
+                }
+                _ => {}
+            }
+        }
+
+        let Some(host_key) = host_key(event.code) else {
+            return true;
+        };
+        let keys: Vec<Key> = self
+            .keymap
+            .bindings()
+            .iter()
+            .filter(|(bound, _)| *bound == host_key)
+            .map(|(_, key)| *key)
+            .collect();
+        for key in keys {
+            let was_held = self.held[key as usize].is_some();
+            if pressed {
+                self.held[key as usize] = Some(Instant::now() + HOLD_TIME);
+                if !was_held {
+                    self.send_event(Event::KeyDown(key));
+                }
+            } else if was_held {
+                self.held[key as usize] = None;
+                self.send_event(Event::KeyUp(key));
+            }
+        }
+        true
+    }
+
+    /// Releases the keys that have not been reported for a while, for terminals that only
+    /// report presses.
+    fn release_keys(&mut self, now: Instant) {
+        if self.releases {
+            return;
+        }
+        for key in Key::ALL {
+            if self.held[key as usize].is_some_and(|due| due <= now) {
+                self.held[key as usize] = None;
+                self.send_event(Event::KeyUp(key));
+            }
+        }
+    }
+
+    /// Takes in news from the emulator, ringing the terminal bell when the sound starts. Returns
+    /// false once the emulator thread has stopped.
+    fn receive_statuses(&mut self, out: &mut Stdout) -> io::Result<bool> {
+        loop {
+            match self.channels.statuses.try_recv() {
+                Ok(Status::State(state)) => {
+                    self.state = state;
+                    if state == State::Running {
+                        self.halted_at = None;
+                    }
+                }
+                Ok(Status::Rom { size, platform }) => {
+                    self.rom = Some((size, platform));
+                    self.halted_at = None;
+                }
+                Ok(Status::Fault(fault)) => self.error = Some(fault.to_string()),
+                Ok(Status::Halted { address }) => self.halted_at = Some(address),
+                Ok(Status::Sound(on)) => {
+                    if on && !self.sound_on {
+                        queue!(out, style::Print('\x07'))?;
+                    }
+                    self.sound_on = on;
+                }
+                Ok(Status::Stats {
+                    frames,
+                    instructions_per_second,
+                }) => self.stats = Some((frames, instructions_per_second)),
+                Err(TryRecvError::Empty) => return Ok(true),
+                Err(TryRecvError::Disconnected) => return Ok(false),
+            }
+        }
+    }
+
+    /// Draws the characters of the picture and the status line that have changed since the
+    /// last call, or everything after the terminal has been resized.
+    fn draw(&mut self, out: &mut Stdout) -> io::Result<()> {
+        let (columns, rows) = terminal::size()?;
+        let (columns, rows) = (columns as usize, rows as usize);
+        let (cell_width, cell_height) = self.render.cell_size();
+        let size = (
+            self.frame.width.div_ceil(cell_width),
+            self.frame.height.div_ceil(cell_height),
+        );
+        if size != self.drawn_size {
+            queue!(out, style::ResetColor, terminal::Clear(ClearType::All))?;
+            self.drawn.clear();
+            self.drawn_size = size;
+            self.drawn_status.clear();
+            self.changed = true;
+        }
+
+        if self.changed {
+            self.changed = false;
+            let cells = self.cells();
+            let (width, height) = size;
+            let mut colours = None;
+            // Where the cursor is, to skip moving it between neighbouring characters.
+            let mut cursor = None;
+            for y in 0..height.min(rows.saturating_sub(1)) {
+                for x in 0..width.min(columns) {
+                    let cell = cells[y * width + x];
+                    if self.drawn.get(y * width + x) == Some(&cell) {
+                        continue;
+                    }
+                    if cursor != Some((x, y)) {
+                        queue!(out, cursor::MoveTo(x as u16, y as u16))?;
+                    }
+                    if colours != Some((cell.foreground, cell.background)) {
+                        queue!(
+                            out,
+                            style::SetColors(style::Colors::new(
+                                terminal_colour(cell.foreground),
+                                terminal_colour(cell.background)
+                            ))
+                        )?;
+                        colours = Some((cell.foreground, cell.background));
+                    }
+                    queue!(out, style::Print(cell.character))?;
+                    cursor = Some((x + 1, y));
+                }
+            }
+            self.drawn = cells;
+        }
+
+        let status = self.status_line();
+        if status != self.drawn_status {
+            let row = size.1.min(rows.saturating_sub(1)) as u16;
+            let status: String = status.chars().take(columns).collect();
+            queue!(
+                out,
+                style::ResetColor,
+                cursor::MoveTo(0, row),
+                terminal::Clear(ClearType::CurrentLine),
+                style::Print(&status)
+            )?;
+            self.drawn_status = status;
+        }
+        out.flush()
+    }
+
+    /// The characters that draw the frame, row by row.
+    fn cells(&self) -> Vec<Cell> {
+        let frame = &self.frame;
+        let (cell_width, cell_height) = self.render.cell_size();
+        let width = frame.width.div_ceil(cell_width);
+        let height = frame.height.div_ceil(cell_height);
+        let background = self.palette.colour(0);
+        let colour = |x: usize, y: usize| {
+            if x < frame.width && y < frame.height {
+                screen::pixel_colour(
+                    frame.format,
+                    &self.palette,
+                    frame.pixels[y * frame.width + x],
+                )
+            } else {
+                background
+            }
+        };
+        let mut cells = Vec::with_capacity(width * height);
+        for row in 0..height {
+            for column in 0..width {
+                let (x, y) = (column * cell_width, row * cell_height);
+                let cell = match self.render {
+                    Render::HalfBlock => Cell {
+                        character: '▀',
+                        foreground: colour(x, y),
+                        background: colour(x, y + 1),
+                    },
+                    Render::Braille => {
+                        let mut dots = 0;
+                        let mut foreground = self.palette.colour(1);
+                        for (dy, row_dots) in BRAILLE_DOTS.iter().enumerate() {
+                            for (dx, dot) in row_dots.iter().enumerate() {
+                                let pixel = colour(x + dx, y + dy);
+                                if pixel != background {
+                                    dots |= dot;
+                                    foreground = pixel;
+                                }
+                            }
+                        }
+                        Cell {
+                            character: char::from_u32(0x2800 + dots).unwrap(),
+                            foreground,
+                            background,
+                        }
+                    }
+                };
+                cells.push(cell);
+            }
+        }
+        cells
+    }
+
+    fn status_line(&self) -> String {
+        let mut parts = vec![self.name.clone()];
+        let state = match self.state {
+            State::Running => "Running",
+            State::Paused => "Paused",
+            State::Faulted => "Faulted",
+            State::Stopped => "Stopped",
+        };
+        parts.push(match self.halted_at {
+            Some(address) => format!("{}, halted at {:#05x}", state, address),
+            None => state.to_string(),
+        });
+        if let Some((size, platform)) = self.rom {
+            parts.push(format!("{}, {} bytes", platform.name(), size));
+        }
+        if let Some((frames, instructions_per_second)) = self.stats {
+            if instructions_per_second > 0 {
+                parts.push(format!("frame {}, {} IPS", frames, instructions_per_second));
+            } else {
+                parts.push(format!("frame {}", frames));
+            }
+        }
+        if self.sound_on {
+            parts.push("♪".to_string());
+        }
+        match &self.error {
+            Some(error) => parts.push(format!("⚠ {} (F5 resets)", error)),
+            None => parts.push("Esc quits, F5 resets, F6 pauses, F7 steps".to_string()),
+        }
+        parts.join(" | ")
+    }
+}
+
+/// The host key a terminal key stands for, as keymaps name them.
+fn host_key(code: KeyCode) -> Option<egui::Key> {
+    match code {
+        KeyCode::Up => Some(egui::Key::ArrowUp),
+        KeyCode::Down => Some(egui::Key::ArrowDown),
+        KeyCode::Left => Some(egui::Key::ArrowLeft),
+        KeyCode::Right => Some(egui::Key::ArrowRight),
+        KeyCode::Tab => Some(egui::Key::Tab),
+        KeyCode::Backspace => Some(egui::Key::Backspace),
+        KeyCode::Enter => Some(egui::Key::Enter),
+        KeyCode::Insert => Some(egui::Key::Insert),
+        KeyCode::Delete => Some(egui::Key::Delete),
+        KeyCode::Home => Some(egui::Key::Home),
+        KeyCode::End => Some(egui::Key::End),
+        KeyCode::PageUp => Some(egui::Key::PageUp),
+        KeyCode::PageDown => Some(egui::Key::PageDown),
+        KeyCode::Char(' ') => Some(egui::Key::Space),
+        KeyCode::Char('-') => Some(egui::Key::Minus),
+        KeyCode::Char('=' | '+') => Some(egui::Key::PlusEquals),
+        KeyCode::Char(character) => keymap::host_key(&character.to_string()),
+        _ => None,
+    }
+}
+
+fn terminal_colour(colour: Color32) -> Color {
+    Color::Rgb {
+        r: colour.r(),
+        g: colour.g(),
+        b: colour.b(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossbeam_channel::unbounded;
+
+    use super::*;
+    use crate::chip8::display::frame_slot;
+
+    fn tui(render: Render, width: usize, height: usize, lit: &[(usize, usize)]) -> Tui {
+        let channels = Channels {
+            frames: frame_slot().1,
+            events: unbounded().0,
+            controls: unbounded().0,
+            statuses: unbounded().1,
+        };
+        let mut tui = Tui::new(
+            channels,
+            Palette::default(),
+            Keymap::default(),
+            render,
+            "pong".to_string(),
+            true,
+        );
+        let mut pixels = vec![0; width * height];
+        for (x, y) in lit {
+            pixels[y * width + x] = 1;
+        }
+        tui.frame = Frame {
+            number: 1,
+            width,
+            height,
+            format: PixelFormat::Indexed,
+            pixels: pixels.into_boxed_slice(),
+        };
+        tui
+    }
+
+    #[test]
+    fn draws_two_rows_of_pixels_to_each_half_block() {
+        let tui = tui(Render::HalfBlock, 64, 32, &[(0, 0), (1, 1)]);
+        let (off, on) = (tui.palette.colour(0), tui.palette.colour(1));
+        let cells = tui.cells();
+        assert_eq!(cells.len(), 64 * 16);
+        assert_eq!((cells[0].foreground, cells[0].background), (on, off));
+        assert_eq!((cells[1].foreground, cells[1].background), (off, on));
+        assert!(cells.iter().all(|cell| cell.character == '▀'));
+        assert!(cells[2..].iter().all(|cell| cell.foreground == off));
+    }
+
+    #[test]
+    fn pads_an_odd_last_row_with_the_background() {
+        let tui = tui(Render::HalfBlock, 2, 3, &[(0, 2)]);
+        let cells = tui.cells();
+        assert_eq!(cells.len(), 2 * 2);
+        assert_eq!(cells[2].foreground, tui.palette.colour(1));
+        assert_eq!(cells[2].background, tui.palette.colour(0));
+    }
+
+    #[test]
+    fn packs_2x4_pixels_into_each_braille_character() {
+        let tui = tui(Render::Braille, 64, 32, &[(0, 0), (1, 3), (63, 31)]);
+        let cells = tui.cells();
+        assert_eq!(cells.len(), 32 * 8);
+        assert_eq!(cells[0].character, '\u{2881}');
+        assert_eq!(cells[0].foreground, tui.palette.colour(1));
+        assert_eq!(cells[1].character, '\u{2800}');
+        assert_eq!(cells[32 * 8 - 1].character, '\u{2880}');
+    }
+
+    #[test]
+    fn maps_terminal_keys_to_host_keys() {
+        assert_eq!(host_key(KeyCode::Up), Some(egui::Key::ArrowUp));
+        assert_eq!(host_key(KeyCode::Char(' ')), Some(egui::Key::Space));
+        assert_eq!(host_key(KeyCode::Char('+')), Some(egui::Key::PlusEquals));
+        assert_eq!(host_key(KeyCode::Char('q')), Some(egui::Key::Q));
+        assert_eq!(host_key(KeyCode::Char('Q')), Some(egui::Key::Q));
+        assert_eq!(host_key(KeyCode::Char('7')), Some(egui::Key::Num7));
+        assert_eq!(host_key(KeyCode::F(1)), None);
+        assert_eq!(host_key(KeyCode::Char('é')), None);
+    }
+
+    #[test]
+    fn shows_the_state_rom_and_speed_in_the_status_line() {
+        let mut tui = tui(Render::HalfBlock, 64, 32, &[]);
+        assert_eq!(
+            tui.status_line(),
+            "pong | Running | Esc quits, F5 resets, F6 pauses, F7 steps"
+        );
+
+        tui.state = State::Paused;
+        tui.halted_at = Some(0x2A4);
+        tui.rom = Some((246, Platform::Chip8));
+        tui.stats = Some((120, 0));
+        tui.sound_on = true;
+        tui.error = Some("stack overflow".to_string());
+        assert_eq!(
+            tui.status_line(),
+            "pong | Paused, halted at 0x2a4 | chip8, 246 bytes | frame 120 | ♪ \
+             | ⚠ stack overflow (F5 resets)"
+        );
+
+        tui.stats = Some((120, 700));
+        assert!(tui.status_line().contains("| frame 120, 700 IPS |"));
+    }
+}